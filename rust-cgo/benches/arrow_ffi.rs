@@ -0,0 +1,59 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The LanceDB Authors
+
+//! Times exporting a batch through the C data interface at two sizes. The
+//! exported structures share the batch's buffers, so the time per export
+//! should not grow with the amount of data. Run with
+//! `cargo bench --bench arrow_ffi`.
+//!
+//! Importing is not timed: `import_record_batch_from_c` fully validates the
+//! data it receives, UTF-8 included, so its cost is linear in the rows.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use arrow::ffi::{FFI_ArrowArray, FFI_ArrowSchema};
+use arrow_array::{Int64Array, RecordBatch, StringArray};
+use arrow_schema::{DataType, Field, Schema};
+use lancedb_cgo::arrow_ffi::export_record_batch_to_c;
+
+const ITERATIONS: u32 = 10;
+
+fn batch(num_rows: usize) -> RecordBatch {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("name", DataType::Utf8, false),
+    ]));
+    let ids = Int64Array::from_iter_values(0..num_rows as i64);
+    let names = StringArray::from_iter_values((0..num_rows).map(|i| format!("row {}", i)));
+    RecordBatch::try_new(schema, vec![Arc::new(ids), Arc::new(names)]).unwrap()
+}
+
+/// Average time to export `batch` and release the exported structures
+fn time_export(batch: &RecordBatch) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        let mut array_out = std::mem::MaybeUninit::<FFI_ArrowArray>::uninit();
+        let mut schema_out = std::mem::MaybeUninit::<FFI_ArrowSchema>::uninit();
+        unsafe {
+            export_record_batch_to_c(batch, array_out.as_mut_ptr(), schema_out.as_mut_ptr())
+                .unwrap();
+            // Dropping the structures calls their release callbacks
+            drop(array_out.assume_init());
+            drop(schema_out.assume_init());
+        }
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn main() {
+    // 64 MiB of values, where a per-cell copy would dominate the time taken
+    for num_rows in [1024, 8 * 1024 * 1024] {
+        let batch = batch(num_rows);
+        println!(
+            "export of {} rows: {:?} per iteration",
+            num_rows,
+            time_export(&batch)
+        );
+    }
+}
@@ -6,16 +6,20 @@
 //! This module provides conversion functions between Arrow RecordBatch and
 //! the C Data Interface structures (ArrowArray and ArrowSchema).
 
+use std::os::raw::c_int;
 use std::sync::Arc;
 
 use arrow::ffi::{from_ffi, FFI_ArrowArray, FFI_ArrowSchema};
 use arrow_array::{Array, RecordBatch, StructArray};
-use arrow_schema::Schema;
+use arrow_schema::{DataType, Schema};
 
 use crate::error::Result;
 
 /// Import a RecordBatch from C Data Interface structures
 ///
+/// The buffers are shared rather than copied, but every value is validated
+/// (UTF-8 included), so the time taken grows linearly with the rows.
+///
 /// # Safety
 ///
 /// The caller must ensure that:
@@ -27,7 +31,7 @@ pub unsafe fn import_record_batch_from_c(
     schema: *mut FFI_ArrowSchema,
 ) -> Result<RecordBatch> {
     if array.is_null() || schema.is_null() {
-        return Err(crate::error::Error::InvalidArgument {
+        return Err(crate::error::Error::NullPointer {
             message: "array and schema pointers cannot be null".to_string(),
             location: snafu::Location::new(file!(), line!(), column!()),
         });
@@ -35,7 +39,7 @@ pub unsafe fn import_record_batch_from_c(
 
     // Import schema from the C structure
     let ffi_schema_ref = &*schema;
-    let _imported_schema =
+    let imported_schema =
         Arc::new(
             Schema::try_from(ffi_schema_ref).map_err(|e| crate::error::Error::Arrow {
                 message: format!("Failed to convert schema: {}", e),
@@ -54,10 +58,32 @@ pub unsafe fn import_record_batch_from_c(
             location: snafu::Location::new(file!(), line!(), column!()),
         })?;
 
-    // Convert ArrayData to StructArray, then to RecordBatch
+    // from_ffi trusts the producer, so check the array really holds a
+    // record batch laid out as the schema describes before building one
+    if !matches!(array_data.data_type(), DataType::Struct(_)) {
+        return Err(crate::error::Error::Arrow {
+            message: format!(
+                "expected a struct array for a record batch, got {}",
+                array_data.data_type()
+            ),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+    }
+    array_data
+        .validate_full()
+        .map_err(|e| crate::error::Error::Arrow {
+            message: format!("array does not match its schema: {}", e),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        })?;
+
+    // Convert ArrayData to StructArray, then to a RecordBatch checked
+    // against the imported schema
     let struct_array = StructArray::from(array_data);
-    let batch = RecordBatch::from(&struct_array);
-    Ok(batch)
+    let (_, columns, _) = struct_array.into_parts();
+    RecordBatch::try_new(imported_schema, columns).map_err(|e| crate::error::Error::Arrow {
+        message: format!("array does not match its schema: {}", e),
+        location: snafu::Location::new(file!(), line!(), column!()),
+    })
 }
 
 /// Export a RecordBatch to C Data Interface structures
@@ -73,14 +99,16 @@ pub unsafe fn export_record_batch_to_c(
     schema_out: *mut FFI_ArrowSchema,
 ) -> Result<()> {
     if array_out.is_null() || schema_out.is_null() {
-        return Err(crate::error::Error::InvalidArgument {
+        return Err(crate::error::Error::NullPointer {
             message: "array_out and schema_out pointers cannot be null".to_string(),
             location: snafu::Location::new(file!(), line!(), column!()),
         });
     }
 
-    // Convert RecordBatch to StructArray for FFI export
-    // Clone the batch since we need ownership for the conversion
+    // Export the batch as a struct array. Cloning a RecordBatch only clones
+    // the Arcs of its schema and columns, and the conversion reuses them, so
+    // the cost grows with the number of columns, never with the rows: the
+    // exported array shares its buffers with `batch`.
     let struct_array = StructArray::from(batch.clone());
 
     // Export schema
@@ -93,13 +121,77 @@ pub unsafe fn export_record_batch_to_c(
 
     std::ptr::write(schema_out, ffi_schema);
 
-    // Export array data
-    let ffi_array = FFI_ArrowArray::new(&struct_array.to_data());
+    // Export array data, moving rather than cloning the struct's ArrayData
+    let ffi_array = FFI_ArrowArray::new(&struct_array.into_data());
     std::ptr::write(array_out, ffi_array);
 
     Ok(())
 }
 
+/// Export a list of RecordBatches into newly allocated arrays of C Data
+/// Interface structures
+///
+/// On success `*arrays_out` and `*schemas_out` point to `libc::malloc`'d arrays
+/// holding `*count_out` exported batches, to be freed with
+/// [`lancedb_free_batch_arrays`]. When there are no batches both are set to
+/// null and the count to 0. On failure nothing is left allocated.
+///
+/// # Safety
+///
+/// The caller must ensure that:
+/// - `arrays_out`, `schemas_out`, and `count_out` are valid for writes
+/// - The caller takes ownership of the exported structures and the arrays
+pub unsafe fn export_batches_to_c(
+    batches: &[RecordBatch],
+    arrays_out: *mut *mut FFI_ArrowArray,
+    schemas_out: *mut *mut FFI_ArrowSchema,
+    count_out: *mut c_int,
+) -> Result<()> {
+    let num_batches = batches.len();
+
+    if num_batches == 0 {
+        *arrays_out = std::ptr::null_mut();
+        *schemas_out = std::ptr::null_mut();
+        *count_out = 0;
+        return Ok(());
+    }
+
+    // Allocate arrays for Arrow C structures
+    let arrays_size = num_batches * std::mem::size_of::<FFI_ArrowArray>();
+    let schemas_size = num_batches * std::mem::size_of::<FFI_ArrowSchema>();
+
+    let arrays_ptr = libc::malloc(arrays_size) as *mut FFI_ArrowArray;
+    let schemas_ptr = libc::malloc(schemas_size) as *mut FFI_ArrowSchema;
+
+    if arrays_ptr.is_null() || schemas_ptr.is_null() {
+        libc::free(arrays_ptr as *mut libc::c_void);
+        libc::free(schemas_ptr as *mut libc::c_void);
+        return Err(crate::error::Error::Arrow {
+            message: "failed to allocate memory for output arrays".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+    }
+
+    // Export each batch
+    for (i, batch) in batches.iter().enumerate() {
+        if let Err(err) = export_record_batch_to_c(batch, arrays_ptr.add(i), schemas_ptr.add(i)) {
+            // Clean up on error
+            for j in 0..i {
+                lancedb_arrow_array_release(arrays_ptr.add(j));
+                lancedb_arrow_schema_release(schemas_ptr.add(j));
+            }
+            libc::free(arrays_ptr as *mut libc::c_void);
+            libc::free(schemas_ptr as *mut libc::c_void);
+            return Err(err);
+        }
+    }
+
+    *arrays_out = arrays_ptr;
+    *schemas_out = schemas_ptr;
+    *count_out = num_batches as c_int;
+    Ok(())
+}
+
 /// Export a Schema to C Data Interface structure
 ///
 /// # Safety
@@ -109,7 +201,7 @@ pub unsafe fn export_record_batch_to_c(
 /// - The caller takes ownership and must call the release callback
 pub unsafe fn export_schema_to_c(schema: &Schema, schema_out: *mut FFI_ArrowSchema) -> Result<()> {
     if schema_out.is_null() {
-        return Err(crate::error::Error::InvalidArgument {
+        return Err(crate::error::Error::NullPointer {
             message: "schema_out pointer cannot be null".to_string(),
             location: snafu::Location::new(file!(), line!(), column!()),
         });
@@ -133,7 +225,7 @@ pub unsafe fn export_schema_to_c(schema: &Schema, schema_out: *mut FFI_ArrowSche
 /// - The memory follows the Arrow C Data Interface specification
 pub unsafe fn import_schema_from_c(schema: *mut FFI_ArrowSchema) -> Result<Schema> {
     if schema.is_null() {
-        return Err(crate::error::Error::InvalidArgument {
+        return Err(crate::error::Error::NullPointer {
             message: "schema pointer cannot be null".to_string(),
             location: snafu::Location::new(file!(), line!(), column!()),
         });
@@ -183,11 +275,44 @@ pub unsafe extern "C" fn lancedb_arrow_schema_release(schema: *mut FFI_ArrowSche
     }
 }
 
+/// Release every batch in arrays returned by functions such as
+/// lancedb_query_execute and lancedb_table_to_arrow, then free the arrays.
+/// This is the canonical cleanup for those results.
+///
+/// Null `arrays` or `schemas` are skipped, and structures whose release
+/// callback is already null (e.g. moved out by an importer) are left alone,
+/// so freeing a partly consumed result is safe. The arrays themselves must
+/// not be used or freed again afterwards.
+///
+/// # Safety
+///
+/// `arrays` and `schemas` must each be null or an array of at least `count`
+/// structures allocated by this library
+#[no_mangle]
+pub unsafe extern "C" fn lancedb_free_batch_arrays(
+    arrays: *mut FFI_ArrowArray,
+    schemas: *mut FFI_ArrowSchema,
+    count: c_int,
+) {
+    for i in 0..count.max(0) as usize {
+        if !arrays.is_null() {
+            lancedb_arrow_array_release(arrays.add(i));
+        }
+        if !schemas.is_null() {
+            lancedb_arrow_schema_release(schemas.add(i));
+        }
+    }
+    libc::free(arrays as *mut libc::c_void);
+    libc::free(schemas as *mut libc::c_void);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use arrow_array::{Int32Array, RecordBatch, StringArray};
+    use arrow::buffer::{Buffer, ScalarBuffer};
+    use arrow_array::{Int32Array, Int64Array, RecordBatch, StringArray};
     use arrow_schema::{DataType, Field, Schema};
+    use std::ptr::NonNull;
     use std::sync::Arc;
 
     #[test]
@@ -227,6 +352,118 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_import_rejects_non_struct_array() {
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)])),
+            vec![Arc::new(Int32Array::from(vec![1, 2, 3]))],
+        )
+        .unwrap();
+
+        let mut array_out = std::mem::MaybeUninit::<FFI_ArrowArray>::uninit();
+        let mut schema_out = std::mem::MaybeUninit::<FFI_ArrowSchema>::uninit();
+
+        unsafe {
+            export_record_batch_to_c(&batch, array_out.as_mut_ptr(), schema_out.as_mut_ptr())
+                .unwrap();
+            // Describe the struct array as a plain Int32 array instead
+            lancedb_arrow_schema_release(schema_out.as_mut_ptr());
+            let mut schema = FFI_ArrowSchema::try_from(&DataType::Int32).unwrap();
+
+            let err = import_record_batch_from_c(array_out.as_mut_ptr(), &mut schema).unwrap_err();
+            assert!(matches!(err, crate::error::Error::Arrow { .. }));
+        }
+    }
+
+    #[test]
+    fn test_import_rejects_nulls_in_non_nullable_field() {
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![Field::new("name", DataType::Utf8, true)])),
+            vec![Arc::new(StringArray::from(vec![Some("Alice"), None]))],
+        )
+        .unwrap();
+
+        let mut array_out = std::mem::MaybeUninit::<FFI_ArrowArray>::uninit();
+        let mut schema_out = std::mem::MaybeUninit::<FFI_ArrowSchema>::uninit();
+
+        unsafe {
+            export_record_batch_to_c(&batch, array_out.as_mut_ptr(), schema_out.as_mut_ptr())
+                .unwrap();
+            // Declare the column non-nullable although it holds a null
+            lancedb_arrow_schema_release(schema_out.as_mut_ptr());
+            let fields = vec![Field::new("name", DataType::Utf8, false)];
+            let mut schema = FFI_ArrowSchema::try_from(&DataType::Struct(fields.into())).unwrap();
+
+            let err = import_record_batch_from_c(array_out.as_mut_ptr(), &mut schema).unwrap_err();
+            assert!(matches!(err, crate::error::Error::Arrow { .. }));
+            assert!(err.to_string().contains("does not match its schema"));
+        }
+    }
+
+    #[test]
+    fn test_free_batch_arrays_releases_buffers() {
+        // Back the column with memory we own so we can see when every
+        // exported reference to it has been released
+        let values = Arc::new(vec![1i32, 2, 3, 4]);
+        let buffer = unsafe {
+            Buffer::from_custom_allocation(
+                NonNull::new(values.as_ptr() as *mut u8).unwrap(),
+                values.len() * std::mem::size_of::<i32>(),
+                values.clone(),
+            )
+        };
+        let column = Int32Array::new(ScalarBuffer::from(buffer), None);
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(schema, vec![Arc::new(column)]).unwrap();
+        let batches = vec![batch.slice(0, 2), batch.slice(2, 2)];
+        drop(batch);
+
+        let mut arrays: *mut FFI_ArrowArray = std::ptr::null_mut();
+        let mut schemas: *mut FFI_ArrowSchema = std::ptr::null_mut();
+        let mut count: c_int = 0;
+        unsafe { export_batches_to_c(&batches, &mut arrays, &mut schemas, &mut count) }.unwrap();
+        drop(batches);
+        assert_eq!(count, 2);
+        assert!(Arc::strong_count(&values) > 1);
+
+        unsafe { lancedb_free_batch_arrays(arrays, schemas, count) };
+        assert_eq!(Arc::strong_count(&values), 1);
+
+        // Null arrays are ignored
+        unsafe { lancedb_free_batch_arrays(std::ptr::null_mut(), std::ptr::null_mut(), 3) };
+    }
+
+    #[test]
+    fn test_export_batch_shares_buffers() {
+        // A per-cell copy would produce buffers at new addresses; the time
+        // taken for large batches is measured by benches/arrow_ffi.rs
+        let num_rows = 4096;
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        let ids = Int64Array::from_iter_values(0..num_rows as i64);
+        let names = StringArray::from_iter_values((0..num_rows).map(|i| format!("row {}", i)));
+        let batch = RecordBatch::try_new(schema, vec![Arc::new(ids), Arc::new(names)]).unwrap();
+
+        let mut array_out = std::mem::MaybeUninit::<FFI_ArrowArray>::uninit();
+        let mut schema_out = std::mem::MaybeUninit::<FFI_ArrowSchema>::uninit();
+        let imported = unsafe {
+            export_record_batch_to_c(&batch, array_out.as_mut_ptr(), schema_out.as_mut_ptr())
+                .unwrap();
+            import_record_batch_from_c(array_out.as_mut_ptr(), schema_out.as_mut_ptr()).unwrap()
+        };
+
+        assert_eq!(imported.num_rows(), num_rows);
+        for (original, roundtripped) in batch.columns().iter().zip(imported.columns()) {
+            let original = original.to_data();
+            let roundtripped = roundtripped.to_data();
+            for (a, b) in original.buffers().iter().zip(roundtripped.buffers()) {
+                assert_eq!(a.as_ptr(), b.as_ptr());
+            }
+        }
+    }
+
     #[test]
     fn test_roundtrip_schema() {
         let schema = Schema::new(vec![
@@ -1,23 +1,56 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-FileCopyrightText: Copyright The LanceDB Authors
 
-use std::ffi::{CStr, CString};
+use std::collections::HashMap;
+use std::ffi::CString;
 use std::os::raw::{c_char, c_int};
+use std::time::Duration;
 
-use crate::error::Result;
+use crate::error::{Error, Result};
+use crate::table::TableHandle;
 use crate::{c_result, RT};
 use lancedb::connection::{connect, Connection};
+use lancedb::table::AddDataMode;
 
 /// Opaque handle to a LanceDB connection
 #[derive(Clone)]
 pub struct ConnectionHandle {
     pub inner: Connection,
+    /// Object store options the connection was opened with, reused when a
+    /// table's Lance dataset is opened directly
+    pub(crate) storage_options: HashMap<String, String>,
 }
 
 impl ConnectionHandle {
     pub fn create(dataset_uri: &str) -> Result<Self> {
         let inner = RT.block_on(connect(dataset_uri).execute())?;
-        Ok(Self { inner })
+        Ok(Self {
+            inner,
+            storage_options: HashMap::new(),
+        })
+    }
+
+    /// Connect with object store options such as `aws_access_key_id`,
+    /// `aws_secret_access_key`, `region`, or `endpoint`. Keys are passed
+    /// through unchanged for LanceDB to validate.
+    ///
+    /// `read_consistency_interval` controls how often open tables check for
+    /// versions written by other connections: `None` never checks, zero
+    /// checks on every read, and any other duration checks at most that often.
+    pub fn create_with_options(
+        dataset_uri: &str,
+        options: &[(String, String)],
+        read_consistency_interval: Option<Duration>,
+    ) -> Result<Self> {
+        let mut builder = connect(dataset_uri).storage_options(options.iter().cloned());
+        if let Some(interval) = read_consistency_interval {
+            builder = builder.read_consistency_interval(interval);
+        }
+        let inner = RT.block_on(builder.execute())?;
+        Ok(Self {
+            inner,
+            storage_options: options.iter().cloned().collect(),
+        })
     }
 
     pub fn table_names(
@@ -34,6 +67,85 @@ impl ConnectionHandle {
         }
         Ok(RT.block_on(op.execute())?)
     }
+
+    /// Whether a table named `name` exists. Failures other than the table
+    /// being missing (e.g. I/O errors) are returned as errors.
+    pub fn table_exists(&self, name: &str) -> Result<bool> {
+        match RT.block_on(self.inner.open_table(name).execute()) {
+            Ok(_) => Ok(true),
+            Err(lancedb::Error::TableNotFound { .. }) => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Rename a table.
+    ///
+    /// LanceDB 0.10 only implements rename for remote (LanceDB Cloud)
+    /// databases; for local and object store databases this fails with a
+    /// NotSupported error and leaves the table untouched.
+    pub fn rename_table(&self, old_name: &str, new_name: &str) -> Result<()> {
+        validate_table_name(new_name)?;
+        RT.block_on(self.inner.rename_table(old_name, new_name))?;
+        Ok(())
+    }
+
+    /// Drop a table and delete its data
+    pub fn drop_table(&self, name: &str) -> Result<()> {
+        RT.block_on(self.inner.drop_table(name))?;
+        Ok(())
+    }
+
+    /// Drop every table in the database and delete their data.
+    ///
+    /// This is best effort with respect to concurrent writers: the tables
+    /// present when the call starts are dropped, a table another caller
+    /// drops in the meantime is skipped, and a table created while the call
+    /// runs may survive it.
+    pub fn drop_all_tables(&self) -> Result<()> {
+        // LanceDB 0.10 has no drop_all_tables, and its drop_db removes the
+        // database directory itself, so drop the tables one at a time
+        for name in self.table_names(None, None)? {
+            match RT.block_on(self.inner.drop_table(&name)) {
+                Ok(()) | Err(lancedb::Error::TableNotFound { .. }) => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Append every row of the table `source` to the table `target` in a
+    /// single commit, leaving `source` unchanged.
+    ///
+    /// The source is read as a stream and written as it is read, so it is
+    /// never held in memory as a whole. Both tables must have the same
+    /// fields, with the same types and nullability.
+    pub fn merge_tables(&self, target: &str, source: &str) -> Result<()> {
+        let target = TableHandle::open(self, target)?;
+        let source = TableHandle::open(self, source)?;
+        source.copy_into(&target, None, AddDataMode::Append)?;
+        Ok(())
+    }
+}
+
+/// Check a table name with the same rules LanceDB applies on create:
+/// non-empty and made of alphanumerics, underscores, hyphens, and periods
+fn validate_table_name(name: &str) -> Result<()> {
+    if name.is_empty() {
+        return Err(Error::InvalidTableName {
+            name: name.to_string(),
+            reason: "Table names cannot be empty strings".to_string(),
+        });
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+    {
+        return Err(Error::InvalidTableName {
+            name: name.to_string(),
+            reason: "Table names can only contain alphanumeric characters, underscores, hyphens, and periods".to_string(),
+        });
+    }
+    Ok(())
 }
 
 // C API for connections
@@ -44,19 +156,71 @@ impl ConnectionHandle {
 #[no_mangle]
 pub extern "C" fn lancedb_connect(dataset_uri: *const c_char) -> *mut ConnectionHandle {
     if dataset_uri.is_null() {
-        let error_msg = "dataset_uri cannot be null";
-        let c_error = CString::new(error_msg).unwrap();
-        crate::lancedb_set_last_error(c_error.as_ptr());
+        crate::set_last_error(&Error::NullPointer {
+            message: "dataset_uri cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
         return std::ptr::null_mut();
     }
 
-    let c_str = unsafe { CStr::from_ptr(dataset_uri) };
-    let uri = c_result!(c_str.to_str());
+    let uri = c_result!(unsafe { crate::c_str_arg(dataset_uri, "dataset_uri") });
 
     let handle = c_result!(ConnectionHandle::create(uri));
     Box::into_raw(Box::new(handle))
 }
 
+/// Create a new database connection with object store options, e.g.
+/// credentials and endpoint overrides for `s3://` or `gs://` URIs.
+/// Returns a pointer to ConnectionHandle on success, null on failure.
+///
+/// `keys` and `values` are parallel arrays of `n` C strings. When `n` is 0
+/// both may be null.
+///
+/// `read_consistency_ms` sets how often tables opened through this
+/// connection check for writes made by other connections or processes:
+/// 0 checks before every read (strong consistency), a positive value checks
+/// at most once per that many milliseconds, and -1 never checks. Without
+/// it, an open table handle does not see concurrent writes from elsewhere
+/// until it is reopened.
+#[no_mangle]
+pub extern "C" fn lancedb_connect_with_options(
+    dataset_uri: *const c_char,
+    keys: *const *const c_char,
+    values: *const *const c_char,
+    n: c_int,
+    read_consistency_ms: i64,
+) -> *mut ConnectionHandle {
+    if dataset_uri.is_null() {
+        crate::set_last_error(&Error::NullPointer {
+            message: "dataset_uri cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return std::ptr::null_mut();
+    }
+
+    let uri = c_result!(unsafe { crate::c_str_arg(dataset_uri, "dataset_uri") });
+    let options = if n == 0 {
+        Vec::new()
+    } else {
+        let keys = c_result!(unsafe { crate::c_str_array_arg(keys, n, "keys") });
+        let values = c_result!(unsafe { crate::c_str_array_arg(values, n, "values") });
+        keys.into_iter().zip(values).collect()
+    };
+
+    let read_consistency_interval = if read_consistency_ms < 0 {
+        None
+    } else {
+        Some(Duration::from_millis(read_consistency_ms as u64))
+    };
+
+    let handle = c_result!(ConnectionHandle::create_with_options(
+        uri,
+        &options,
+        read_consistency_interval
+    ));
+    Box::into_raw(Box::new(handle))
+}
+
 /// Close a database connection and free resources.
 #[no_mangle]
 pub extern "C" fn lancedb_connection_close(handle: *mut ConnectionHandle) {
@@ -67,6 +231,189 @@ pub extern "C" fn lancedb_connection_close(handle: *mut ConnectionHandle) {
     }
 }
 
+/// Check whether a table exists.
+/// Returns 1 if it exists, 0 if it does not, -1 on failure (e.g. an I/O error).
+#[no_mangle]
+pub extern "C" fn lancedb_connection_table_exists(
+    handle: *const ConnectionHandle,
+    name: *const c_char,
+) -> c_int {
+    if handle.is_null() || name.is_null() {
+        crate::set_last_error(&Error::NullPointer {
+            message: "connection handle and name cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let conn = unsafe { &*handle };
+    let name_str = match unsafe { crate::c_str_arg(name, "name") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match conn.table_exists(name_str) {
+        Ok(true) => 1,
+        Ok(false) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Rename a table.
+/// Returns 0 on success, -1 on failure, e.g. when `old_name` does not exist,
+/// `new_name` is already taken, or `new_name` is not a valid table name.
+/// Only remote databases support renaming; for any other database this fails
+/// with ERROR_NOT_SUPPORTED.
+#[no_mangle]
+pub extern "C" fn lancedb_connection_rename_table(
+    handle: *const ConnectionHandle,
+    old_name: *const c_char,
+    new_name: *const c_char,
+) -> c_int {
+    if handle.is_null() || old_name.is_null() || new_name.is_null() {
+        crate::set_last_error(&Error::NullPointer {
+            message: "connection handle, old_name, and new_name cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let conn = unsafe { &*handle };
+    let old_str = match unsafe { crate::c_str_arg(old_name, "old_name") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+    let new_str = match unsafe { crate::c_str_arg(new_name, "new_name") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match conn.rename_table(old_str, new_str) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Drop a table from the database, deleting its data.
+/// Returns 0 on success, -1 on failure (including when the table does not exist).
+#[no_mangle]
+pub extern "C" fn lancedb_connection_drop_table(
+    handle: *const ConnectionHandle,
+    name: *const c_char,
+) -> c_int {
+    if handle.is_null() || name.is_null() {
+        crate::set_last_error(&Error::NullPointer {
+            message: "connection handle and name cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let conn = unsafe { &*handle };
+    let name_str = match unsafe { crate::c_str_arg(name, "name") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match conn.drop_table(name_str) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Drop every table in the database, deleting their data.
+/// Returns 0 on success, -1 on failure.
+///
+/// Best effort with respect to concurrent writers: a table created while
+/// the call runs may survive it.
+#[no_mangle]
+pub extern "C" fn lancedb_connection_drop_all_tables(handle: *const ConnectionHandle) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&Error::NullPointer {
+            message: "connection handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let conn = unsafe { &*handle };
+
+    match conn.drop_all_tables() {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Append every row of the table named `source` to the table named
+/// `target`, e.g. to fold a daily table into a master table.
+/// Returns 0 on success, -1 on failure (including when the schemas differ,
+/// in which case the error names the first mismatched field).
+///
+/// The source is streamed into the target without being collected in
+/// memory, and is left unchanged. The rows are added in one commit.
+#[no_mangle]
+pub extern "C" fn lancedb_connection_merge_tables(
+    handle: *const ConnectionHandle,
+    target: *const c_char,
+    source: *const c_char,
+) -> c_int {
+    if handle.is_null() || target.is_null() || source.is_null() {
+        crate::set_last_error(&Error::NullPointer {
+            message: "connection handle, target, and source cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let conn = unsafe { &*handle };
+    let target_str = match unsafe { crate::c_str_arg(target, "target") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+    let source_str = match unsafe { crate::c_str_arg(source, "source") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match conn.merge_tables(target_str, source_str) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
 /// Get table names from the database.
 /// Returns the number of table names on success, -1 on failure.
 /// table_names_out will be populated with a null-terminated array of C strings.
@@ -80,9 +427,10 @@ pub extern "C" fn lancedb_connection_table_names(
     count_out: *mut c_int,
 ) -> c_int {
     if handle.is_null() {
-        let error_msg = "connection handle cannot be null";
-        let c_error = CString::new(error_msg).unwrap();
-        crate::lancedb_set_last_error(c_error.as_ptr());
+        crate::set_last_error(&Error::NullPointer {
+            message: "connection handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
         return -1;
     }
 
@@ -91,13 +439,10 @@ pub extern "C" fn lancedb_connection_table_names(
     let start_after_opt = if start_after.is_null() {
         None
     } else {
-        let c_str = unsafe { CStr::from_ptr(start_after) };
-        let s = match c_str.to_str() {
+        let s = match unsafe { crate::c_str_arg(start_after, "start_after") } {
             Ok(s) => s,
             Err(err) => {
-                let error_msg = format!("{}", err);
-                let c_error = CString::new(error_msg).unwrap();
-                crate::lancedb_set_last_error(c_error.as_ptr());
+                crate::set_last_error(&err);
                 return -1;
             }
         };
@@ -109,9 +454,7 @@ pub extern "C" fn lancedb_connection_table_names(
     let table_names = match connection.table_names(start_after_opt, limit_opt) {
         Ok(names) => names,
         Err(err) => {
-            let error_msg = format!("{}", err);
-            let c_error = CString::new(error_msg).unwrap();
-            crate::lancedb_set_last_error(c_error.as_ptr());
+            crate::set_last_error(&err);
             return -1;
         }
     };
@@ -122,9 +465,7 @@ pub extern "C" fn lancedb_connection_table_names(
         let c_string = match CString::new(name) {
             Ok(s) => s,
             Err(err) => {
-                let error_msg = format!("{}", err);
-                let c_error = CString::new(error_msg).unwrap();
-                crate::lancedb_set_last_error(c_error.as_ptr());
+                crate::set_last_error(&err.into());
                 return -1;
             }
         };
@@ -172,3 +513,145 @@ pub extern "C" fn lancedb_free_string_array(array: *mut *mut c_char, count: c_in
         libc::free(array as *mut libc::c_void);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{temp_connection, vector_table};
+
+    #[test]
+    fn test_drop_table() {
+        let (_dir, conn) = temp_connection();
+        vector_table(&conn, "doomed", 3, 2);
+        assert_eq!(conn.table_names(None, None).unwrap(), vec!["doomed"]);
+
+        let name = CString::new("doomed").unwrap();
+        assert_eq!(lancedb_connection_drop_table(&conn, name.as_ptr()), 0);
+        assert!(conn.table_names(None, None).unwrap().is_empty());
+
+        // Dropping it again reports an error instead of panicking
+        assert_eq!(lancedb_connection_drop_table(&conn, name.as_ptr()), -1);
+    }
+
+    #[test]
+    fn test_drop_all_tables() {
+        let (_dir, conn) = temp_connection();
+        for name in ["first", "second", "third"] {
+            vector_table(&conn, name, 3, 2);
+        }
+        assert_eq!(conn.table_names(None, None).unwrap().len(), 3);
+
+        assert_eq!(lancedb_connection_drop_all_tables(&conn), 0);
+        assert!(conn.table_names(None, None).unwrap().is_empty());
+
+        // Dropping from an empty database succeeds, and the connection
+        // stays usable
+        assert_eq!(lancedb_connection_drop_all_tables(&conn), 0);
+        vector_table(&conn, "fresh", 1, 2);
+        assert_eq!(conn.table_names(None, None).unwrap(), vec!["fresh"]);
+    }
+
+    #[test]
+    fn test_rename_table() {
+        let (_dir, conn) = temp_connection();
+        vector_table(&conn, "staging", 3, 2);
+
+        let err = conn.rename_table("staging", "bad/name").unwrap_err();
+        assert!(matches!(err, Error::InvalidTableName { .. }));
+
+        // Local databases cannot rename, and the table is left in place
+        let old_name = CString::new("staging").unwrap();
+        let new_name = CString::new("production").unwrap();
+        assert_eq!(
+            lancedb_connection_rename_table(&conn, old_name.as_ptr(), new_name.as_ptr()),
+            -1
+        );
+        assert_eq!(
+            crate::lancedb_get_last_error_code(),
+            crate::error::ERROR_NOT_SUPPORTED
+        );
+        let table = TableHandle::open(&conn, "staging").unwrap();
+        assert_eq!(table.count_rows().unwrap(), 3);
+        assert!(TableHandle::open(&conn, "production").is_err());
+    }
+
+    /// Connects to object storage when `LANCEDB_TEST_S3_URI` is set, e.g. to a
+    /// MinIO bucket, using `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`, and
+    /// `AWS_ENDPOINT` from the environment
+    #[test]
+    fn test_connect_with_options_object_store() {
+        let Ok(uri) = std::env::var("LANCEDB_TEST_S3_URI") else {
+            return;
+        };
+        let mut options = vec![("allow_http".to_string(), "true".to_string())];
+        for (key, var) in [
+            ("aws_access_key_id", "AWS_ACCESS_KEY_ID"),
+            ("aws_secret_access_key", "AWS_SECRET_ACCESS_KEY"),
+            ("endpoint", "AWS_ENDPOINT"),
+            ("region", "AWS_REGION"),
+        ] {
+            if let Ok(value) = std::env::var(var) {
+                options.push((key.to_string(), value));
+            }
+        }
+
+        let conn = ConnectionHandle::create_with_options(&uri, &options, None).unwrap();
+        conn.table_names(None, None).unwrap();
+    }
+
+    #[test]
+    fn test_read_consistency_interval() {
+        let (dir, writer) = temp_connection();
+        let uri = dir.path().to_str().unwrap();
+        let writer_table = vector_table(&writer, "shared", 2, 2);
+
+        let strong = ConnectionHandle::create_with_options(uri, &[], Some(Duration::ZERO)).unwrap();
+        let lazy = ConnectionHandle::create_with_options(uri, &[], None).unwrap();
+        let strong_table = TableHandle::open(&strong, "shared").unwrap();
+        let lazy_table = TableHandle::open(&lazy, "shared").unwrap();
+
+        writer_table
+            .add_data(
+                crate::test_util::vector_batch(2, 3, 2),
+                lancedb::table::AddDataMode::Append,
+            )
+            .unwrap();
+
+        assert_eq!(strong_table.count_rows().unwrap(), 5);
+        assert_eq!(lazy_table.count_rows().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_table_exists() {
+        let (_dir, conn) = temp_connection();
+        vector_table(&conn, "present", 1, 2);
+
+        let present = CString::new("present").unwrap();
+        let absent = CString::new("absent_7f3a9c").unwrap();
+        assert_eq!(lancedb_connection_table_exists(&conn, present.as_ptr()), 1);
+        assert_eq!(lancedb_connection_table_exists(&conn, absent.as_ptr()), 0);
+    }
+
+    #[test]
+    fn test_merge_tables() {
+        let (_dir, conn) = temp_connection();
+        vector_table(&conn, "master", 5, 2);
+        let daily = vector_table(&conn, "daily", 5, 2);
+
+        let target = CString::new("master").unwrap();
+        let source = CString::new("daily").unwrap();
+        assert_eq!(
+            lancedb_connection_merge_tables(&conn, target.as_ptr(), source.as_ptr()),
+            0
+        );
+        // Reopen, as handles opened before the merge do not see it
+        let master = TableHandle::open(&conn, "master").unwrap();
+        assert_eq!(master.count_rows().unwrap(), 10);
+        assert_eq!(daily.count_rows().unwrap(), 5);
+
+        vector_table(&conn, "wider", 5, 3);
+        let err = conn.merge_tables("master", "wider").unwrap_err();
+        assert!(err.to_string().contains("field 'vector'"), "{}", err);
+        assert_eq!(master.count_rows().unwrap(), 10);
+    }
+}
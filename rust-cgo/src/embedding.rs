@@ -0,0 +1,427 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The LanceDB Authors
+
+//! Embedding functions supplied by the C caller
+//!
+//! A caller registers a named callback that turns a piece of text into a
+//! fixed-size float vector. Tables can then fill a vector column from a text
+//! column on write, and queries can search by text.
+
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_float, c_int, c_void};
+use std::sync::{Arc, RwLock};
+
+use arrow_array::{Array, ArrayRef, FixedSizeListArray, Float32Array, RecordBatch, StringArray};
+use arrow_schema::{DataType, Field, Schema};
+use lazy_static::lazy_static;
+
+use crate::error::{Error, Result};
+
+/// Callback that embeds the NUL-terminated `text` by writing `dim` floats to
+/// `out`. Returns 0 on success and any other value on failure.
+pub type EmbedCallback = Option<
+    extern "C" fn(
+        text: *const c_char,
+        out: *mut c_float,
+        dim: c_int,
+        user_data: *mut c_void,
+    ) -> c_int,
+>;
+
+/// A registered callback with the dimension of the vectors it produces
+pub struct EmbeddingFunction {
+    name: String,
+    dim: usize,
+    callback: extern "C" fn(*const c_char, *mut c_float, c_int, *mut c_void) -> c_int,
+    user_data: *mut c_void,
+}
+
+// The caller promises, when registering, that the callback may be invoked
+// from any thread and that `user_data` stays valid until every call made
+// through the function has returned (see lancedb_unregister_embedding_function).
+unsafe impl Send for EmbeddingFunction {}
+unsafe impl Sync for EmbeddingFunction {}
+
+impl EmbeddingFunction {
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// Embed a single piece of text
+    pub fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let c_text = CString::new(text)?;
+        let mut out = vec![0.0f32; self.dim];
+        let status = (self.callback)(
+            c_text.as_ptr(),
+            out.as_mut_ptr(),
+            self.dim as c_int,
+            self.user_data,
+        );
+        if status != 0 {
+            return Err(Error::InvalidArgument {
+                message: format!(
+                    "embedding function '{}' failed with status {}",
+                    self.name, status
+                ),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+        Ok(out)
+    }
+
+    /// Embed every value of a string array into a vector column whose
+    /// elements are described by `item`. Null strings produce null vectors.
+    pub fn embed_array(&self, texts: &StringArray, item: Arc<Field>) -> Result<FixedSizeListArray> {
+        let mut values = Vec::with_capacity(texts.len() * self.dim);
+        for text in texts.iter() {
+            match text {
+                Some(text) => values.extend(self.embed(text)?),
+                None => values.extend(std::iter::repeat(0.0).take(self.dim)),
+            }
+        }
+        let array = FixedSizeListArray::try_new(
+            item,
+            self.dim as i32,
+            Arc::new(Float32Array::from(values)),
+            texts.nulls().cloned(),
+        )?;
+        Ok(array)
+    }
+}
+
+lazy_static! {
+    static ref EMBEDDING_FUNCTIONS: RwLock<HashMap<String, Arc<EmbeddingFunction>>> =
+        RwLock::new(HashMap::new());
+}
+
+/// Register `function` under its name, replacing any function of that name
+pub fn register(function: EmbeddingFunction) -> Result<()> {
+    EMBEDDING_FUNCTIONS
+        .write()?
+        .insert(function.name.clone(), Arc::new(function));
+    Ok(())
+}
+
+/// Remove the function registered as `name`. Returns whether one existed.
+///
+/// Calls that looked the function up before it was removed still run to
+/// completion with it.
+pub fn unregister(name: &str) -> Result<bool> {
+    Ok(EMBEDDING_FUNCTIONS.write()?.remove(name).is_some())
+}
+
+/// Look up the function registered as `name`
+pub fn get(name: &str) -> Result<Arc<EmbeddingFunction>> {
+    EMBEDDING_FUNCTIONS
+        .read()?
+        .get(name)
+        .cloned()
+        .ok_or_else(|| Error::EmbeddingFunctionNotFound {
+            name: name.to_string(),
+            reason: "no embedding function is registered under this name".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        })
+}
+
+/// A vector column filled by embedding a text column
+#[derive(Debug, Clone)]
+pub struct EmbeddingColumn {
+    pub source_column: String,
+    pub dest_column: String,
+    pub function_name: String,
+}
+
+impl EmbeddingColumn {
+    /// Add the embedded column to `batch` if it is missing. `table_schema`
+    /// supplies the type and nullability of the vector column.
+    pub fn apply(&self, batch: RecordBatch, table_schema: &Schema) -> Result<RecordBatch> {
+        if batch.schema().column_with_name(&self.dest_column).is_some() {
+            return Ok(batch);
+        }
+        let function = get(&self.function_name)?;
+        let dest = table_schema.field_with_name(&self.dest_column)?;
+        let item = match dest.data_type() {
+            DataType::FixedSizeList(item, dim) if *dim as usize == function.dim() => item.clone(),
+            other => {
+                return Err(Error::InvalidArgument {
+                    message: format!(
+                        "column '{}' must be a list of {} floats to hold embeddings from '{}', found {}",
+                        self.dest_column,
+                        function.dim(),
+                        self.function_name,
+                        other
+                    ),
+                    location: snafu::Location::new(file!(), line!(), column!()),
+                })
+            }
+        };
+        let texts = batch
+            .column_by_name(&self.source_column)
+            .and_then(|c| c.as_any().downcast_ref::<StringArray>())
+            .ok_or_else(|| Error::InvalidArgument {
+                message: format!(
+                    "embedding source column '{}' must be present and of type Utf8",
+                    self.source_column
+                ),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            })?;
+        let vectors: ArrayRef = Arc::new(function.embed_array(texts, item)?);
+
+        let mut fields = batch.schema().fields().to_vec();
+        fields.push(Arc::new(dest.clone()));
+        let mut columns = batch.columns().to_vec();
+        columns.push(vectors);
+        Ok(RecordBatch::try_new(
+            Arc::new(Schema::new(fields)),
+            columns,
+        )?)
+    }
+}
+
+// C API for embedding functions
+
+/// Register an embedding function under `name`, replacing any existing
+/// function of that name. Returns 0 on success, -1 on failure.
+///
+/// The callback receives the text to embed and must write exactly `dim`
+/// floats to `out`, returning 0 on success. It is called on the thread that
+/// writes or queries the table, possibly from several threads at once, so it
+/// must be thread-safe. `user_data` is passed through unchanged and must stay
+/// valid until the function is unregistered and every write or query that
+/// may use it has returned.
+#[no_mangle]
+pub extern "C" fn lancedb_register_embedding_function(
+    name: *const c_char,
+    dim: c_int,
+    callback: EmbedCallback,
+    user_data: *mut c_void,
+) -> c_int {
+    let Some(callback) = callback else {
+        crate::set_last_error(&Error::NullPointer {
+            message: "callback cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    };
+    if dim <= 0 {
+        crate::set_last_error(&Error::InvalidArgument {
+            message: "dim must be positive".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let name = match unsafe { crate::c_str_arg(name, "name") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match register(EmbeddingFunction {
+        name: name.to_string(),
+        dim: dim as usize,
+        callback,
+        user_data,
+    }) {
+        Ok(()) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Unregister the embedding function named `name`.
+/// Returns 0 on success, -1 if no function has that name.
+///
+/// Writes and queries look the function up by name when they run, so none
+/// start using it after this returns. Those already running on other threads
+/// may still invoke the callback until they return, so free `user_data` only
+/// once they have.
+#[no_mangle]
+pub extern "C" fn lancedb_unregister_embedding_function(name: *const c_char) -> c_int {
+    let name = match unsafe { crate::c_str_arg(name, "name") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match unregister(name) {
+        Ok(true) => 0,
+        Ok(false) => {
+            crate::set_last_error(&Error::EmbeddingFunctionNotFound {
+                name: name.to_string(),
+                reason: "no embedding function is registered under this name".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+            -1
+        }
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::QueryHandle;
+    use crate::table::TableHandle;
+    use crate::test_util::temp_connection;
+    use arrow_array::Int32Array;
+    use lancedb::table::AddDataMode;
+    use std::ffi::CStr;
+
+    /// Embeds text as [length, number of 'a' characters]
+    extern "C" fn letter_counts(
+        text: *const c_char,
+        out: *mut c_float,
+        dim: c_int,
+        _user_data: *mut c_void,
+    ) -> c_int {
+        let text = unsafe { CStr::from_ptr(text) }.to_str().unwrap();
+        let out = unsafe { std::slice::from_raw_parts_mut(out, dim as usize) };
+        out[0] = text.len() as f32;
+        out[1] = text.matches('a').count() as f32;
+        0
+    }
+
+    fn register_letter_counts(name: &str) {
+        let name = CString::new(name).unwrap();
+        let status = lancedb_register_embedding_function(
+            name.as_ptr(),
+            2,
+            Some(letter_counts),
+            std::ptr::null_mut(),
+        );
+        assert_eq!(status, 0);
+    }
+
+    fn text_table(conn: &crate::connection::ConnectionHandle) -> TableHandle {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("text", DataType::Utf8, true),
+            Field::new(
+                "vector",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 2),
+                true,
+            ),
+        ]));
+        TableHandle::create(conn, "docs", schema).unwrap()
+    }
+
+    fn text_batch(ids: Vec<i32>, texts: Vec<Option<&str>>) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("text", DataType::Utf8, true),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from(ids)),
+                Arc::new(StringArray::from(texts)),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_auto_embed_and_search_by_text() {
+        register_letter_counts("letter_counts_search");
+        let (_dir, conn) = temp_connection();
+        let table = text_table(&conn);
+        table
+            .add_embedding_column("text", "vector", "letter_counts_search")
+            .unwrap();
+
+        let batch = text_batch(
+            vec![1, 2, 3, 4],
+            vec![Some("banana"), Some("kiwi"), Some("aa"), None],
+        );
+        table.add_data(batch, AddDataMode::Append).unwrap();
+        assert_eq!(table.count_rows().unwrap(), 4);
+
+        let mut query = QueryHandle::new(table.inner.clone());
+        query.set_embedding_columns(table.embedding_columns());
+        let text = CString::new("papaya").unwrap();
+        assert_eq!(
+            crate::query::lancedb_query_nearest_to_text(&mut query, text.as_ptr()),
+            0
+        );
+        query.limit(1).unwrap();
+        let batches = query.execute().unwrap();
+        let ids = batches[0]
+            .column_by_name("id")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .unwrap();
+        // "papaya" embeds to [6, 3], the same as "banana"
+        assert_eq!(ids.value(0), 1);
+    }
+
+    #[test]
+    fn test_unknown_embedding_function() {
+        let (_dir, conn) = temp_connection();
+        let table = text_table(&conn);
+        let err = table
+            .add_embedding_column("text", "vector", "not_registered")
+            .unwrap_err();
+        assert!(matches!(err, Error::EmbeddingFunctionNotFound { .. }));
+
+        let name = CString::new("not_registered").unwrap();
+        assert_eq!(lancedb_unregister_embedding_function(name.as_ptr()), -1);
+        assert_eq!(
+            crate::lancedb_get_last_error_code(),
+            crate::error::ERROR_EMBEDDING_FUNCTION_NOT_FOUND
+        );
+    }
+
+    #[test]
+    fn test_unregistered_function_is_not_called() {
+        register_letter_counts("letter_counts_unregister");
+        let (_dir, conn) = temp_connection();
+        let table = text_table(&conn);
+        table
+            .add_embedding_column("text", "vector", "letter_counts_unregister")
+            .unwrap();
+
+        let name = CString::new("letter_counts_unregister").unwrap();
+        assert_eq!(lancedb_unregister_embedding_function(name.as_ptr()), 0);
+        let err = table
+            .add_data(text_batch(vec![1], vec![Some("kiwi")]), AddDataMode::Append)
+            .unwrap_err();
+        assert!(matches!(err, Error::EmbeddingFunctionNotFound { .. }));
+        assert_eq!(table.count_rows().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_embedding_dimension_mismatch() {
+        let name = CString::new("three_dims").unwrap();
+        assert_eq!(
+            lancedb_register_embedding_function(
+                name.as_ptr(),
+                3,
+                Some(letter_counts),
+                std::ptr::null_mut()
+            ),
+            0
+        );
+        let (_dir, conn) = temp_connection();
+        let table = text_table(&conn);
+        let err = table
+            .add_embedding_column("text", "vector", "three_dims")
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("has 2 dims"),
+            "unexpected error: {}",
+            err
+        );
+    }
+}
@@ -1,7 +1,10 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-FileCopyrightText: Copyright The LanceDB Authors
 
+use std::ffi::NulError;
+use std::os::raw::c_int;
 use std::str::Utf8Error;
+use std::sync::PoisonError;
 
 use arrow_schema::ArrowError;
 use serde_json::Error as JsonError;
@@ -48,14 +51,66 @@ pub enum Error {
     OtherLance { message: String, location: Location },
     #[snafu(display("Other LanceDB error: {message}, {location}"))]
     OtherLanceDB { message: String, location: Location },
-    #[snafu(display("Null pointer error, {location}"))]
-    NullPointer { location: Location },
+    #[snafu(display("Null pointer: {message}, {location}"))]
+    NullPointer { message: String, location: Location },
     #[snafu(display("UTF-8 conversion error: {message}, {location}"))]
     Utf8Error { message: String, location: Location },
+    #[snafu(display("Timed out: {message}, {location}"))]
+    TimedOut { message: String, location: Location },
+    #[snafu(display("Not supported: {message}, {location}"))]
+    NotSupported { message: String, location: Location },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+// Error codes reported by lancedb_get_last_error_code. These values are part
+// of the C ABI: never renumber them, only append.
+
+/// No error has been recorded on this thread
+pub const ERROR_NONE: c_int = 0;
+pub const ERROR_INVALID_ARGUMENT: c_int = 1;
+pub const ERROR_TABLE_NOT_FOUND: c_int = 2;
+pub const ERROR_TABLE_ALREADY_EXISTS: c_int = 3;
+pub const ERROR_INVALID_TABLE_NAME: c_int = 4;
+pub const ERROR_DATASET_NOT_FOUND: c_int = 5;
+pub const ERROR_DATASET_ALREADY_EXISTS: c_int = 6;
+pub const ERROR_IO: c_int = 7;
+pub const ERROR_ARROW: c_int = 8;
+pub const ERROR_INDEX: c_int = 9;
+pub const ERROR_JSON: c_int = 10;
+pub const ERROR_EMBEDDING_FUNCTION_NOT_FOUND: c_int = 11;
+pub const ERROR_NULL_POINTER: c_int = 12;
+pub const ERROR_UTF8: c_int = 13;
+pub const ERROR_OTHER_LANCE: c_int = 14;
+pub const ERROR_OTHER_LANCEDB: c_int = 15;
+pub const ERROR_TIMED_OUT: c_int = 16;
+pub const ERROR_NOT_SUPPORTED: c_int = 17;
+
+impl Error {
+    /// The stable code identifying this kind of error across the C ABI
+    pub fn code(&self) -> c_int {
+        match self {
+            Self::InvalidArgument { .. } => ERROR_INVALID_ARGUMENT,
+            Self::IO { .. } => ERROR_IO,
+            Self::Arrow { .. } => ERROR_ARROW,
+            Self::Index { .. } => ERROR_INDEX,
+            Self::JSON { .. } => ERROR_JSON,
+            Self::DatasetNotFound { .. } => ERROR_DATASET_NOT_FOUND,
+            Self::DatasetAlreadyExists { .. } => ERROR_DATASET_ALREADY_EXISTS,
+            Self::TableAlreadyExists { .. } => ERROR_TABLE_ALREADY_EXISTS,
+            Self::TableNotFound { .. } => ERROR_TABLE_NOT_FOUND,
+            Self::InvalidTableName { .. } => ERROR_INVALID_TABLE_NAME,
+            Self::EmbeddingFunctionNotFound { .. } => ERROR_EMBEDDING_FUNCTION_NOT_FOUND,
+            Self::OtherLance { .. } => ERROR_OTHER_LANCE,
+            Self::OtherLanceDB { .. } => ERROR_OTHER_LANCEDB,
+            Self::NullPointer { .. } => ERROR_NULL_POINTER,
+            Self::Utf8Error { .. } => ERROR_UTF8,
+            Self::TimedOut { .. } => ERROR_TIMED_OUT,
+            Self::NotSupported { .. } => ERROR_NOT_SUPPORTED,
+        }
+    }
+}
+
 impl From<Utf8Error> for Error {
     #[track_caller]
     fn from(source: Utf8Error) -> Self {
@@ -66,6 +121,16 @@ impl From<Utf8Error> for Error {
     }
 }
 
+impl From<NulError> for Error {
+    #[track_caller]
+    fn from(source: NulError) -> Self {
+        Self::InvalidArgument {
+            message: source.to_string(),
+            location: std::panic::Location::caller().to_snafu_location(),
+        }
+    }
+}
+
 impl From<ArrowError> for Error {
     #[track_caller]
     fn from(source: ArrowError) -> Self {
@@ -76,6 +141,16 @@ impl From<ArrowError> for Error {
     }
 }
 
+impl From<std::io::Error> for Error {
+    #[track_caller]
+    fn from(source: std::io::Error) -> Self {
+        Self::IO {
+            source: Box::new(source),
+            location: std::panic::Location::caller().to_snafu_location(),
+        }
+    }
+}
+
 impl From<JsonError> for Error {
     #[track_caller]
     fn from(source: JsonError) -> Self {
@@ -86,6 +161,18 @@ impl From<JsonError> for Error {
     }
 }
 
+/// A lock whose holder panicked. The guarded data is not trusted, so the
+/// operation fails instead of unwinding across the C boundary.
+impl<T> From<PoisonError<T>> for Error {
+    #[track_caller]
+    fn from(source: PoisonError<T>) -> Self {
+        Self::OtherLanceDB {
+            message: source.to_string(),
+            location: std::panic::Location::caller().to_snafu_location(),
+        }
+    }
+}
+
 impl From<lance::Error> for Error {
     #[track_caller]
     fn from(source: lance::Error) -> Self {
@@ -141,6 +228,10 @@ impl From<lancedb::Error> for Error {
                 location: std::panic::Location::caller().to_snafu_location(),
             },
             lancedb::Error::Lance { source } => Self::from(source),
+            lancedb::Error::NotSupported { message } => Self::NotSupported {
+                message,
+                location: std::panic::Location::caller().to_snafu_location(),
+            },
             _ => Self::OtherLanceDB {
                 message: source.to_string(),
                 location: std::panic::Location::caller().to_snafu_location(),
@@ -4,6 +4,7 @@
 use lazy_static::lazy_static;
 use std::ffi::{CStr, CString};
 use std::os::raw::{c_char, c_int};
+use std::sync::OnceLock;
 
 // Error handling macros similar to JNI
 #[macro_export]
@@ -12,9 +13,7 @@ macro_rules! c_result {
         match $result {
             Ok(value) => value,
             Err(err) => {
-                let error_msg = format!("{}", err);
-                let c_error = std::ffi::CString::new(error_msg).unwrap();
-                $crate::lancedb_set_last_error(c_error.as_ptr());
+                $crate::set_last_error(&$crate::Error::from(err));
                 return std::ptr::null_mut();
             }
         }
@@ -27,9 +26,7 @@ macro_rules! c_result_int {
         match $result {
             Ok(value) => value as std::os::raw::c_int,
             Err(err) => {
-                let error_msg = format!("{}", err);
-                let c_error = std::ffi::CString::new(error_msg).unwrap();
-                $crate::lancedb_set_last_error(c_error.as_ptr());
+                $crate::set_last_error(&$crate::Error::from(err));
                 return -1;
             }
         }
@@ -38,19 +35,107 @@ macro_rules! c_result_int {
 
 pub mod arrow_ffi;
 mod connection;
+mod embedding;
 pub mod error;
+mod logging;
 mod query;
+mod rerank;
 mod table;
+#[cfg(test)]
+mod test_util;
 
 pub use error::{Error, Result};
 
+/// The runtime behind [`RT`], built by lancedb_init_with_threads or, with
+/// the default worker count, on first use
+static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+
 lazy_static! {
-    static ref RT: tokio::runtime::Runtime = tokio::runtime::Builder::new_multi_thread()
-        .enable_all()
-        .build()
-        .expect("Failed to create tokio runtime");
+    static ref RT: &'static tokio::runtime::Runtime =
+        RUNTIME.get_or_init(|| build_runtime(None).expect("Failed to create tokio runtime"));
 }
 
+/// Build the multi-threaded runtime, with `worker_threads` workers if given
+/// and otherwise one per CPU
+fn build_runtime(worker_threads: Option<usize>) -> std::io::Result<tokio::runtime::Runtime> {
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    if let Some(worker_threads) = worker_threads {
+        builder.worker_threads(worker_threads);
+    }
+    builder.enable_all().build()
+}
+
+/// Convert a C string argument into a `&str`.
+///
+/// Every entry point goes through this helper so callers see the same errors
+/// for bad string input: a null pointer yields a `NullPointer` error
+/// ("<name> cannot be null") and invalid UTF-8 yields a `Utf8Error`
+/// ("invalid UTF-8 in <name>: ...").
+///
+/// # Safety
+///
+/// `ptr` must be null or point to a NUL-terminated string that stays valid
+/// for the lifetime of the returned `&str`.
+pub(crate) unsafe fn c_str_arg<'a>(ptr: *const c_char, name: &str) -> Result<&'a str> {
+    if ptr.is_null() {
+        return Err(Error::NullPointer {
+            message: format!("{} cannot be null", name),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+    }
+
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map_err(|err| Error::Utf8Error {
+            message: format!("invalid UTF-8 in {}: {}", name, err),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        })
+}
+
+/// Convert an array of `len` C string arguments into owned Rust strings,
+/// validating each one with [`c_str_arg`].
+///
+/// # Safety
+///
+/// `ptr` must be null or point to `len` pointers that each satisfy the
+/// requirements of [`c_str_arg`].
+pub(crate) unsafe fn c_str_array_arg(
+    ptr: *const *const c_char,
+    len: c_int,
+    name: &str,
+) -> Result<Vec<String>> {
+    if ptr.is_null() {
+        return Err(Error::NullPointer {
+            message: format!("{} cannot be null", name),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+    }
+    if len <= 0 {
+        return Err(Error::InvalidArgument {
+            message: format!("{} cannot be empty", name),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+    }
+
+    std::slice::from_raw_parts(ptr, len as usize)
+        .iter()
+        .map(|&item| c_str_arg(item, name).map(|s| s.to_string()))
+        .collect()
+}
+
+/// Convert `s` into a C string without panicking.
+///
+/// C strings cannot hold NUL bytes, but error messages can quote user input
+/// or data, so any interior NUL is escaped as the two characters `\0`.
+pub(crate) fn to_c_string(s: &str) -> CString {
+    CString::new(s.replace('\0', "\\0")).unwrap_or_else(|_| {
+        CString::new(INVALID_STRING_SENTINEL).expect("sentinel has no NUL bytes")
+    })
+}
+
+/// Returned by [`to_c_string`] if a string still cannot be converted
+const INVALID_STRING_SENTINEL: &str = "<string could not be converted to a C string>";
+
 // C API functions
 
 /// Initialize the LanceDB runtime. Must be called before any other functions.
@@ -60,6 +145,46 @@ pub extern "C" fn lancedb_init() -> c_int {
     0
 }
 
+/// Initialize the LanceDB runtime with `num_threads` worker threads instead
+/// of one per CPU. Returns 0 on success, -1 on failure.
+///
+/// Must be called before any other function that does work: it fails if the
+/// runtime has already started, including from an earlier call.
+#[no_mangle]
+pub extern "C" fn lancedb_init_with_threads(num_threads: c_int) -> c_int {
+    if num_threads <= 0 {
+        set_last_error(&Error::InvalidArgument {
+            message: "num_threads must be positive".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+    if RUNTIME.get().is_some() {
+        set_last_error(&Error::InvalidArgument {
+            message: "the runtime has already been initialized".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let runtime = match build_runtime(Some(num_threads as usize)) {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            set_last_error(&err.into());
+            return -1;
+        }
+    };
+    // Another thread may have started the runtime since the check above
+    if RUNTIME.set(runtime).is_err() {
+        set_last_error(&Error::InvalidArgument {
+            message: "the runtime has already been initialized".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+    0
+}
+
 /// Clean up resources. Call this when done with LanceDB.
 #[no_mangle]
 pub extern "C" fn lancedb_cleanup() {
@@ -87,21 +212,171 @@ pub extern "C" fn lancedb_free_string(s: *mut c_char) {
     }
 }
 
+/// Get the code of the last error on this thread, identifying its kind
+/// (see the ERROR_* constants in error.rs, e.g. 2 = table not found).
+/// Returns 0 if no error has been recorded.
+#[no_mangle]
+pub extern "C" fn lancedb_get_last_error_code() -> c_int {
+    LAST_ERROR_CODE.with(|c| c.get())
+}
+
 // Thread-local storage for error messages
 thread_local! {
     static LAST_ERROR: std::cell::RefCell<Option<CString>> = std::cell::RefCell::new(None);
+    static LAST_ERROR_CODE: std::cell::Cell<c_int> = std::cell::Cell::new(error::ERROR_NONE);
 }
 
+/// Set the last error message. A null pointer clears the last error.
+///
+/// An error recorded by message alone has code ERROR_INVALID_ARGUMENT; use
+/// [`set_last_error`] to record an [`Error`] with its own code.
 #[no_mangle]
 pub extern "C" fn lancedb_set_last_error(error: *const c_char) {
     if error.is_null() {
         LAST_ERROR.with(|e| *e.borrow_mut() = None);
+        LAST_ERROR_CODE.with(|c| c.set(error::ERROR_NONE));
         return;
     }
 
-    let c_str = unsafe { CStr::from_ptr(error) };
-    let error_string = c_str.to_string_lossy().into_owned();
-    let c_error = CString::new(error_string).unwrap();
+    let error_string = unsafe { CStr::from_ptr(error) }.to_string_lossy();
+    let c_error = to_c_string(&error_string);
 
     LAST_ERROR.with(|e| *e.borrow_mut() = Some(c_error));
+    LAST_ERROR_CODE.with(|c| c.set(error::ERROR_INVALID_ARGUMENT));
+}
+
+/// Record `err` as this thread's last error, with its message and code
+pub(crate) fn set_last_error(err: &Error) {
+    let c_error = to_c_string(&err.to_string());
+    lancedb_set_last_error(c_error.as_ptr());
+    LAST_ERROR_CODE.with(|c| c.set(err.code()));
+}
+
+/// Record `err` as this thread's last error, prefixing its message with `context`
+pub(crate) fn set_last_error_with_context(context: &str, err: &Error) {
+    let c_error = to_c_string(&format!("{}: {}", context, err));
+    lancedb_set_last_error(c_error.as_ptr());
+    LAST_ERROR_CODE.with(|c| c.set(err.code()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INVALID_UTF8: &[u8] = b"tab\xffle\0";
+
+    #[test]
+    fn test_c_str_arg_valid() {
+        let s = CString::new("my_table").unwrap();
+        let converted = unsafe { c_str_arg(s.as_ptr(), "name") }.unwrap();
+        assert_eq!(converted, "my_table");
+    }
+
+    #[test]
+    fn test_c_str_arg_null() {
+        let err = unsafe { c_str_arg(std::ptr::null(), "name") }.unwrap_err();
+        assert!(matches!(err, Error::NullPointer { .. }));
+        assert!(err.to_string().contains("name cannot be null"));
+    }
+
+    #[test]
+    fn test_c_str_arg_invalid_utf8() {
+        let ptr = INVALID_UTF8.as_ptr() as *const c_char;
+        let err = unsafe { c_str_arg(ptr, "name") }.unwrap_err();
+        assert!(matches!(err, Error::Utf8Error { .. }));
+        assert!(err
+            .to_string()
+            .starts_with("UTF-8 conversion error: invalid UTF-8 in name:"));
+    }
+
+    #[test]
+    fn test_to_c_string_escapes_nul() {
+        let c_string = to_c_string("id = 'a\0b'");
+        assert_eq!(c_string.to_str().unwrap(), "id = 'a\\0b'");
+    }
+
+    #[test]
+    fn test_set_last_error_with_nul() {
+        let err = Error::InvalidArgument {
+            message: "bad filter: x\0y".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        };
+        set_last_error(&err);
+
+        let error = unsafe { CStr::from_ptr(lancedb_get_last_error()) };
+        assert!(error.to_str().unwrap().contains("bad filter: x\\0y"));
+        assert_eq!(lancedb_get_last_error_code(), error::ERROR_INVALID_ARGUMENT);
+    }
+
+    #[test]
+    fn test_init_with_threads() {
+        assert_eq!(lancedb_init_with_threads(0), -1);
+
+        let runtime = build_runtime(Some(2)).unwrap();
+        assert_eq!(runtime.metrics().num_workers(), 2);
+
+        // Once the runtime is running its worker count cannot change
+        let (_dir, conn) = test_util::temp_connection();
+        assert!(conn.table_names(None, None).unwrap().is_empty());
+        assert_eq!(lancedb_init_with_threads(2), -1);
+        let error = unsafe { CStr::from_ptr(lancedb_get_last_error()) };
+        assert!(error
+            .to_str()
+            .unwrap()
+            .contains("the runtime has already been initialized"));
+        assert_eq!(lancedb_get_last_error_code(), error::ERROR_INVALID_ARGUMENT);
+    }
+
+    #[test]
+    fn test_connect_invalid_utf8_sets_error() {
+        let ptr = INVALID_UTF8.as_ptr() as *const c_char;
+        let handle = connection::lancedb_connect(ptr);
+        assert!(handle.is_null());
+
+        let error = unsafe { CStr::from_ptr(lancedb_get_last_error()) };
+        assert!(error
+            .to_str()
+            .unwrap()
+            .starts_with("UTF-8 conversion error: invalid UTF-8 in dataset_uri:"));
+        assert_eq!(lancedb_get_last_error_code(), error::ERROR_UTF8);
+    }
+
+    #[test]
+    fn test_error_code_table_not_found() {
+        let (_dir, conn) = test_util::temp_connection();
+        let name = CString::new("missing").unwrap();
+
+        let handle = table::lancedb_table_open(&conn, name.as_ptr());
+        assert!(handle.is_null());
+        assert_eq!(lancedb_get_last_error_code(), error::ERROR_TABLE_NOT_FOUND);
+    }
+
+    #[test]
+    fn test_error_code_table_already_exists() {
+        let (_dir, conn) = test_util::temp_connection();
+        let name = CString::new("docs").unwrap();
+
+        let handle = table::lancedb_table_create(&conn, name.as_ptr());
+        assert!(!handle.is_null());
+        table::lancedb_table_close(handle);
+
+        let handle = table::lancedb_table_create(&conn, name.as_ptr());
+        assert!(handle.is_null());
+        assert_eq!(
+            lancedb_get_last_error_code(),
+            error::ERROR_TABLE_ALREADY_EXISTS
+        );
+    }
+
+    #[test]
+    fn test_error_code_null_argument() {
+        let (_dir, conn) = test_util::temp_connection();
+
+        let handle = table::lancedb_table_open(&conn, std::ptr::null());
+        assert!(handle.is_null());
+        assert_eq!(lancedb_get_last_error_code(), error::ERROR_NULL_POINTER);
+
+        lancedb_set_last_error(std::ptr::null());
+        assert_eq!(lancedb_get_last_error_code(), error::ERROR_NONE);
+    }
 }
@@ -0,0 +1,205 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The LanceDB Authors
+
+//! Forwarding of `tracing` events to a callback supplied by the C caller
+//!
+//! LanceDB and Lance report what they are doing through `tracing`. Once a
+//! callback is installed, every event at or above the configured level is
+//! formatted into a single line and passed to it.
+
+use std::fmt::Write as _;
+use std::os::raw::{c_char, c_int};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{OnceLock, RwLock};
+
+use tracing::field::{Field, Visit};
+use tracing::subscriber::Interest;
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+pub const LOG_LEVEL_ERROR: c_int = 0;
+pub const LOG_LEVEL_WARN: c_int = 1;
+pub const LOG_LEVEL_INFO: c_int = 2;
+pub const LOG_LEVEL_DEBUG: c_int = 3;
+
+/// Callback receiving a log level (see the LOG_LEVEL_* constants) and a
+/// NUL-terminated message that is only valid for the duration of the call
+pub type LogCallback = Option<extern "C" fn(level: c_int, msg: *const c_char)>;
+
+static LOG_CALLBACK: RwLock<LogCallback> = RwLock::new(None);
+static LOG_LEVEL: AtomicI32 = AtomicI32::new(LOG_LEVEL_INFO);
+static SUBSCRIBER_INSTALLED: OnceLock<bool> = OnceLock::new();
+
+fn level_code(level: &Level) -> c_int {
+    match *level {
+        Level::ERROR => LOG_LEVEL_ERROR,
+        Level::WARN => LOG_LEVEL_WARN,
+        Level::INFO => LOG_LEVEL_INFO,
+        Level::DEBUG => LOG_LEVEL_DEBUG,
+        // Trace events are never forwarded
+        Level::TRACE => LOG_LEVEL_DEBUG + 1,
+    }
+}
+
+/// The installed callback, copied out so the lock is released before the
+/// callback runs and a callback that installs another cannot deadlock
+fn current_callback() -> LogCallback {
+    LOG_CALLBACK.read().map(|cb| *cb).unwrap_or(None)
+}
+
+/// Collects an event's message and fields into one line
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+/// Whether events described by `metadata` would reach the callback
+fn forwarded(metadata: &Metadata<'_>) -> bool {
+    metadata.is_event()
+        && level_code(metadata.level()) <= LOG_LEVEL.load(Ordering::Relaxed)
+        && current_callback().is_some()
+}
+
+/// Layer passing enabled events to the installed callback
+struct CallbackLayer;
+
+impl<S: Subscriber> Layer<S> for CallbackLayer {
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        // Cached per callsite, so a disabled callsite costs nothing; the
+        // cache is rebuilt whenever the level or callback changes
+        if forwarded(metadata) {
+            Interest::always()
+        } else {
+            Interest::never()
+        }
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        forwarded(metadata)
+    }
+
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let Some(callback) = current_callback() else {
+            return;
+        };
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let metadata = event.metadata();
+        let msg = crate::to_c_string(&format!(
+            "{}: {}{}",
+            metadata.target(),
+            visitor.message,
+            visitor.fields
+        ));
+        callback(level_code(metadata.level()), msg.as_ptr());
+    }
+}
+
+// C API functions
+
+/// Install a callback receiving LanceDB's log events, replacing any previous
+/// one; a null callback stops forwarding. Returns 0 on success, -1 on failure.
+///
+/// The callback is invoked on whichever thread emitted the event, including
+/// the runtime's worker threads, with no library locks held. The message is
+/// only valid for the duration of the call. Events below the level set with
+/// lancedb_set_log_level (LOG_LEVEL_INFO by default) are dropped.
+///
+/// Fails if another global `tracing` subscriber was installed in the process.
+#[no_mangle]
+pub extern "C" fn lancedb_set_log_callback(callback: LogCallback) -> c_int {
+    let installed = *SUBSCRIBER_INSTALLED.get_or_init(|| {
+        let subscriber = tracing_subscriber::registry().with(CallbackLayer);
+        tracing::subscriber::set_global_default(subscriber).is_ok()
+    });
+    if !installed {
+        crate::set_last_error(&crate::error::Error::NotSupported {
+            message: "another tracing subscriber is already installed".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    match LOG_CALLBACK.write() {
+        Ok(mut cb) => *cb = callback,
+        Err(poisoned) => *poisoned.into_inner() = callback,
+    }
+    tracing::callsite::rebuild_interest_cache();
+    0
+}
+
+/// Set the most detailed level forwarded to the log callback: 0 (error),
+/// 1 (warn), 2 (info), or 3 (debug). Returns 0 on success, -1 on failure.
+#[no_mangle]
+pub extern "C" fn lancedb_set_log_level(level: c_int) -> c_int {
+    if !(LOG_LEVEL_ERROR..=LOG_LEVEL_DEBUG).contains(&level) {
+        crate::set_last_error(&crate::error::Error::InvalidArgument {
+            message: "level must be 0 (error), 1 (warn), 2 (info), or 3 (debug)".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    LOG_LEVEL.store(level, Ordering::Relaxed);
+    tracing::callsite::rebuild_interest_cache();
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+    use std::sync::Mutex;
+
+    static RECEIVED: Mutex<Vec<(c_int, String)>> = Mutex::new(Vec::new());
+
+    extern "C" fn record(level: c_int, msg: *const c_char) {
+        let msg = unsafe { CStr::from_ptr(msg) }
+            .to_string_lossy()
+            .into_owned();
+        RECEIVED.lock().unwrap().push((level, msg));
+    }
+
+    fn log_debug(table: &str) {
+        tracing::debug!(table, "opening table");
+    }
+
+    fn received(table: &str) -> bool {
+        RECEIVED
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|(level, msg)| *level == LOG_LEVEL_DEBUG && msg.contains(table))
+    }
+
+    #[test]
+    fn test_log_callback_follows_level() {
+        assert_eq!(lancedb_set_log_level(4), -1);
+        assert_eq!(lancedb_set_log_level(LOG_LEVEL_DEBUG), 0);
+        assert_eq!(lancedb_set_log_callback(Some(record)), 0);
+        log_debug("first_log_table");
+        assert!(received("first_log_table"));
+
+        // Lowering the level disables the callsite registered above
+        assert_eq!(lancedb_set_log_level(LOG_LEVEL_WARN), 0);
+        log_debug("second_log_table");
+        assert!(!received("second_log_table"));
+
+        // So does removing the callback, whatever the level
+        assert_eq!(lancedb_set_log_level(LOG_LEVEL_DEBUG), 0);
+        assert_eq!(lancedb_set_log_callback(None), 0);
+        log_debug("third_log_table");
+        assert!(!received("third_log_table"));
+    }
+}
@@ -1,337 +1,2363 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-FileCopyrightText: Copyright The LanceDB Authors
 
-use std::ffi::{CStr, CString};
-use std::os::raw::{c_char, c_float, c_int};
+use std::collections::BinaryHeap;
+use std::ffi::CString;
+use std::fs::File;
+use std::future::Future;
+use std::io::{BufWriter, Write};
+use std::os::raw::{c_char, c_double, c_float, c_int};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use arrow::compute::{
+    cast, concat_batches, filter_record_batch, interleave, sort_to_indices, take_record_batch,
+    SortOptions,
+};
 use arrow::ffi::FFI_ArrowArray;
 use arrow::ffi::FFI_ArrowSchema;
-use arrow_array::RecordBatch;
+use arrow::ffi_stream::FFI_ArrowArrayStream;
+use arrow::json::writer::{LineDelimited, WriterBuilder};
+use arrow::row::{OwnedRow, RowConverter, SortField};
+use arrow_array::{
+    Array, ArrayRef, BooleanArray, Float32Array, Float64Array, RecordBatch, RecordBatchReader,
+};
+use arrow_schema::{ArrowError, DataType, Field, SchemaRef};
+use datafusion_common::stats::Precision;
+use datafusion_execution::TaskContext;
+use datafusion_physical_plan::{DisplayableExecutionPlan, ExecutionPlan};
+use futures::future::Either;
 use futures::stream::BoxStream;
 use futures::StreamExt;
+use serde::Deserialize;
 
 use crate::arrow_ffi::export_record_batch_to_c;
+use crate::embedding::EmbeddingColumn;
 use crate::error::Result;
+use crate::rerank::Reranker;
 use crate::RT;
-use lancedb::query::{ExecutableQuery, Query as LanceQuery, QueryBase, VectorQuery};
+use lancedb::arrow::{RecordBatchStream, SendableRecordBatchStream};
+use lancedb::index::scalar::FullTextSearchQuery;
+use lancedb::index::IndexType;
+use lancedb::query::{
+    ExecutableQuery, Query as LanceQuery, QueryBase, QueryExecutionOptions, VectorQuery,
+};
+use lancedb::table::Table;
 use lancedb::DistanceType;
+use tokio::sync::Notify;
 
-/// Opaque handle to a LanceDB query
-/// Can be either a regular Query or a VectorQuery
-pub enum QueryHandle {
+/// Whether `field` is a variable-length list of floats, which may hold
+/// embeddings but cannot be searched
+fn is_float_list(field: &Field) -> bool {
+    match field.data_type() {
+        DataType::List(item) | DataType::LargeList(item) => item.data_type().is_floating(),
+        _ => false,
+    }
+}
+
+fn variable_length_error(field: &Field) -> crate::error::Error {
+    crate::error::Error::InvalidArgument {
+        message: format!(
+            "column '{}' is a variable-length list ({}); vector search needs a fixed-size list column",
+            field.name(),
+            field.data_type()
+        ),
+        location: snafu::Location::new(file!(), line!(), column!()),
+    }
+}
+
+/// Query options accepted by lancedb_query_configure.
+/// Every field is optional; absent fields leave the query unchanged.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QueryConfig {
+    pub vector: Option<Vec<f32>>,
+    pub distance_type: Option<i32>,
+    pub nprobes: Option<usize>,
+    pub filter: Option<String>,
+    pub select: Option<Vec<String>>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+/// The query being built: a regular Query until a vector is supplied,
+/// then a VectorQuery
+#[derive(Clone)]
+pub enum QueryState {
     Plain(LanceQuery),
     Vector(VectorQuery),
 }
 
+/// Opaque handle to a LanceDB query.
+///
+/// Building a query mutates the handle without any locking, so a handle must
+/// not be modified while another thread uses it. Executing only reads it, so
+/// one finished query may be executed from several threads at once; to build
+/// variations concurrently, give each thread its own copy from `clone`.
+#[derive(Clone)]
+pub struct QueryHandle {
+    /// The table being queried, used to validate arguments against its schema
+    table: Table,
+    state: QueryState,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    /// Column to sort the results by, descending
+    order_by_desc: Option<String>,
+    /// Bounds on `_distance` for a vector search, lower inclusive and upper
+    /// exclusive
+    distance_range: Option<(Option<f32>, Option<f32>)>,
+    /// Whether vector search results keep the `_distance` column
+    with_distance: bool,
+    /// Whether a vector search applies its filter after the search
+    postfilter: bool,
+    /// Embedding columns of the table, used to search by text
+    embeddings: Vec<EmbeddingColumn>,
+    /// Vector column chosen for the search, if any
+    vector_column: Option<String>,
+    /// Maximum number of rows in each result batch
+    batch_size: Option<usize>,
+    /// Time allowed for each execution of the query
+    timeout: Option<Duration>,
+    /// Text and vector searched together by a hybrid search
+    hybrid: Option<HybridSearch>,
+    /// How the results of a hybrid search are combined
+    reranker: Reranker,
+}
+
+/// The two searches run by a hybrid query
+#[derive(Clone)]
+struct HybridSearch {
+    text: String,
+    fts_column: String,
+    vector: Vec<f32>,
+    vector_column: String,
+}
+
+/// Number of results of a hybrid search without a limit, as in LanceDB
+const DEFAULT_HYBRID_LIMIT: usize = 10;
+
+/// Comparison operators accepted by lancedb_query_filter_timestamp
+pub const FILTER_OP_EQ: c_int = 0;
+pub const FILTER_OP_GT: c_int = 1;
+pub const FILTER_OP_GE: c_int = 2;
+pub const FILTER_OP_LT: c_int = 3;
+pub const FILTER_OP_LE: c_int = 4;
+
+/// The SQL operator for one of the FILTER_OP_* constants
+fn filter_op_sql(op: c_int) -> Option<&'static str> {
+    match op {
+        FILTER_OP_EQ => Some("="),
+        FILTER_OP_GT => Some(">"),
+        FILTER_OP_GE => Some(">="),
+        FILTER_OP_LT => Some("<"),
+        FILTER_OP_LE => Some("<="),
+        _ => None,
+    }
+}
+
+/// Number of results of a vector search without a limit, as in LanceDB
+const DEFAULT_VECTOR_LIMIT: usize = 10;
+
 impl QueryHandle {
-    pub fn new(query: LanceQuery) -> Self {
-        Self::Plain(query)
+    pub fn new(table: Table) -> Self {
+        let state = QueryState::Plain(table.query());
+        Self {
+            table,
+            state,
+            limit: None,
+            offset: None,
+            order_by_desc: None,
+            distance_range: None,
+            with_distance: true,
+            postfilter: false,
+            embeddings: Vec::new(),
+            vector_column: None,
+            batch_size: None,
+            timeout: None,
+            hybrid: None,
+            reranker: Reranker::default(),
+        }
+    }
+
+    /// Discard everything set on the query, leaving a fresh query of the
+    /// same table. The table's embedding columns are kept.
+    pub fn reset(&mut self) {
+        let embeddings = std::mem::take(&mut self.embeddings);
+        *self = Self::new(self.table.clone());
+        self.embeddings = embeddings;
+    }
+
+    /// The fixed-size-list columns of the table, which a search can run against
+    fn vector_fields(&self) -> Result<Vec<Field>> {
+        self.vector_fields_until(None)
+    }
+
+    /// [`Self::vector_fields`], failing with a TimedOut error if `deadline`
+    /// passes while the schema is read
+    fn vector_fields_until(&self, deadline: Option<Instant>) -> Result<Vec<Field>> {
+        let schema = block_on_until(deadline, self.table.schema())??;
+        Ok(schema
+            .fields()
+            .iter()
+            .filter(|f| matches!(f.data_type(), DataType::FixedSizeList(_, _)))
+            .map(|f| f.as_ref().clone())
+            .collect())
+    }
+
+    /// Find the vector column a search will run against.
+    ///
+    /// Returns the only fixed-size-list column in the schema, or `None` when
+    /// there is no such column or more than one, leaving the choice to LanceDB.
+    fn default_vector_field(&self) -> Result<Option<Field>> {
+        let mut vector_fields = self.vector_fields()?;
+        if vector_fields.len() == 1 {
+            Ok(vector_fields.pop())
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Check that a query vector of `dims` elements of `element_type` matches
+    /// the dimension and element type of the vector column being searched.
+    fn check_query_vector(&self, element_type: &DataType, dims: usize) -> Result<()> {
+        let Some(field) = self.default_vector_field()? else {
+            if self.vector_fields()?.is_empty() {
+                let schema = RT.block_on(self.table.schema())?;
+                if let Some(field) = schema.fields().iter().find(|f| is_float_list(f)) {
+                    return Err(variable_length_error(field));
+                }
+            }
+            return Ok(());
+        };
+        if let DataType::FixedSizeList(item, column_dims) = field.data_type() {
+            if dims != *column_dims as usize {
+                return Err(crate::error::Error::InvalidArgument {
+                    message: format!(
+                        "query vector has {} dims but column '{}' has {}",
+                        dims,
+                        field.name(),
+                        column_dims
+                    ),
+                    location: snafu::Location::new(file!(), line!(), column!()),
+                });
+            }
+            if item.data_type() != element_type {
+                return Err(crate::error::Error::InvalidArgument {
+                    message: format!(
+                        "query vector has element type {} but column '{}' stores {}",
+                        element_type,
+                        field.name(),
+                        item.data_type()
+                    ),
+                    location: snafu::Location::new(file!(), line!(), column!()),
+                });
+            }
+        }
+        Ok(())
     }
 
+    /// Search for the rows nearest to `vector`. When the vector column
+    /// stores Float16 elements the query is narrowed to half precision.
     pub fn nearest_to(&mut self, vector: Vec<f32>) -> Result<()> {
-        match self {
-            QueryHandle::Plain(q) => {
+        let half_precision = self.default_vector_field()?.is_some_and(|field| {
+            matches!(field.data_type(), DataType::FixedSizeList(item, _)
+                if item.data_type() == &DataType::Float16)
+        });
+        let vector: ArrayRef = if half_precision {
+            self.check_query_vector(&DataType::Float16, vector.len())?;
+            cast(&Float32Array::from(vector), &DataType::Float16)?
+        } else {
+            self.check_query_vector(&DataType::Float32, vector.len())?;
+            Arc::new(Float32Array::from(vector))
+        };
+        match &self.state {
+            QueryState::Plain(q) => {
                 let vector_query = q.clone().nearest_to(vector)?;
-                *self = QueryHandle::Vector(vector_query);
+                self.state = QueryState::Vector(vector_query);
                 Ok(())
             }
-            QueryHandle::Vector(_) => Err(crate::error::Error::InvalidArgument {
+            QueryState::Vector(_) => Err(crate::error::Error::InvalidArgument {
                 message: "nearest_to can only be called once on a query".to_string(),
                 location: snafu::Location::new(file!(), line!(), column!()),
             }),
         }
     }
 
-    pub fn distance_type(&mut self, distance_type: DistanceType) -> Result<()> {
-        match self {
-            QueryHandle::Vector(q) => {
-                *self = QueryHandle::Vector(q.clone().distance_type(distance_type));
+    /// Set the embedding columns used by [`Self::nearest_to_text`]
+    pub fn set_embedding_columns(&mut self, embeddings: Vec<EmbeddingColumn>) {
+        self.embeddings = embeddings;
+    }
+
+    /// Search for the rows nearest to `text`, embedded with the function of
+    /// the table's embedding column. The table must have exactly one.
+    pub fn nearest_to_text(&mut self, text: &str) -> Result<()> {
+        let embedding = match self.embeddings.as_slice() {
+            [embedding] => embedding.clone(),
+            [] => {
+                return Err(crate::error::Error::InvalidArgument {
+                    message: "searching by text requires an embedding column on the table"
+                        .to_string(),
+                    location: snafu::Location::new(file!(), line!(), column!()),
+                })
+            }
+            _ => {
+                return Err(crate::error::Error::InvalidArgument {
+                    message: "the table has several embedding columns; search one with a vector"
+                        .to_string(),
+                    location: snafu::Location::new(file!(), line!(), column!()),
+                })
+            }
+        };
+        let vector = crate::embedding::get(&embedding.function_name)?.embed(text)?;
+        self.nearest_to(vector)?;
+        self.column(&embedding.dest_column)
+    }
+
+    /// Search with a double-precision query vector.
+    /// The vector column must store Float64 elements.
+    pub fn nearest_to_f64(&mut self, vector: Vec<f64>) -> Result<()> {
+        self.check_query_vector(&DataType::Float64, vector.len())?;
+        match &self.state {
+            QueryState::Plain(q) => {
+                let vector: ArrayRef = Arc::new(Float64Array::from(vector));
+                let vector_query = q.clone().nearest_to(vector)?;
+                self.state = QueryState::Vector(vector_query);
                 Ok(())
             }
-            QueryHandle::Plain(_) => Err(crate::error::Error::InvalidArgument {
-                message: "distance_type can only be set on vector queries".to_string(),
+            QueryState::Vector(_) => Err(crate::error::Error::InvalidArgument {
+                message: "nearest_to can only be called once on a query".to_string(),
                 location: snafu::Location::new(file!(), line!(), column!()),
             }),
         }
     }
 
-    pub fn limit(&mut self, limit: usize) -> Result<()> {
-        match self {
-            QueryHandle::Plain(q) => {
-                *self = QueryHandle::Plain(q.clone().limit(limit));
-                Ok(())
-            }
-            QueryHandle::Vector(q) => {
-                *self = QueryHandle::Vector(q.clone().limit(limit));
+    pub fn distance_type(&mut self, distance_type: DistanceType) -> Result<()> {
+        match &self.state {
+            QueryState::Vector(q) => {
+                self.state = QueryState::Vector(q.clone().distance_type(distance_type));
                 Ok(())
             }
+            QueryState::Plain(_) => Err(crate::error::Error::InvalidArgument {
+                message: "distance_type can only be set on vector queries".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            }),
         }
     }
 
-    pub fn offset(&mut self, offset: usize) -> Result<()> {
-        match self {
-            QueryHandle::Plain(q) => {
-                *self = QueryHandle::Plain(q.clone().offset(offset));
-                Ok(())
-            }
-            QueryHandle::Vector(q) => {
-                *self = QueryHandle::Vector(q.clone().offset(offset));
+    pub fn nprobes(&mut self, nprobes: usize) -> Result<()> {
+        match &self.state {
+            QueryState::Vector(q) => {
+                self.state = QueryState::Vector(q.clone().nprobes(nprobes));
                 Ok(())
             }
+            QueryState::Plain(_) => Err(crate::error::Error::InvalidArgument {
+                message: "nprobes can only be set on vector queries".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            }),
         }
     }
 
-    pub fn filter(&mut self, filter: &str) -> Result<()> {
-        match self {
-            QueryHandle::Plain(q) => {
-                *self = QueryHandle::Plain(q.clone().only_if(filter));
-                Ok(())
+    /// Keep only vector search results whose `_distance` lies in
+    /// `[lower, upper)`. Either bound may be omitted.
+    ///
+    /// The range filters the page of results the search returns: the query
+    /// must have a limit, the search finds the `offset + limit` nearest rows
+    /// as usual, and the rows of the page outside the range are dropped. A
+    /// page can therefore hold fewer than `limit` rows, or none when a lower
+    /// bound excludes all of the nearest rows; raise the limit or page further
+    /// to reach rows further away.
+    pub fn distance_range(&mut self, lower: Option<f32>, upper: Option<f32>) -> Result<()> {
+        if lower.is_some_and(f32::is_nan) || upper.is_some_and(f32::is_nan) {
+            return Err(crate::error::Error::InvalidArgument {
+                message: "distance_range bounds cannot be NaN".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+        if let (Some(lower), Some(upper)) = (lower, upper) {
+            if lower >= upper {
+                return Err(crate::error::Error::InvalidArgument {
+                    message: format!(
+                        "distance_range lower bound {} must be less than upper bound {}",
+                        lower, upper
+                    ),
+                    location: snafu::Location::new(file!(), line!(), column!()),
+                });
             }
-            QueryHandle::Vector(q) => {
-                *self = QueryHandle::Vector(q.clone().only_if(filter));
+        }
+        match &self.state {
+            QueryState::Vector(_) => {
+                self.distance_range = Some((lower, upper));
                 Ok(())
             }
+            QueryState::Plain(_) => Err(crate::error::Error::InvalidArgument {
+                message: "distance_range can only be set on vector queries".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            }),
         }
     }
 
-    pub fn select(&mut self, columns: Vec<String>) -> Result<()> {
-        match self {
-            QueryHandle::Plain(q) => {
-                *self =
-                    QueryHandle::Plain(q.clone().select(lancedb::query::Select::columns(&columns)));
-                Ok(())
-            }
-            QueryHandle::Vector(q) => {
-                *self = QueryHandle::Vector(
-                    q.clone().select(lancedb::query::Select::columns(&columns)),
-                );
+    /// Search by computing the exact distance to every row, ignoring any
+    /// vector index. `nprobes` and `ef` have no effect on such a search.
+    pub fn bypass_vector_index(&mut self) -> Result<()> {
+        match &self.state {
+            QueryState::Vector(q) => {
+                self.state = QueryState::Vector(q.clone().bypass_vector_index());
                 Ok(())
             }
+            QueryState::Plain(_) => Err(crate::error::Error::InvalidArgument {
+                message: "bypass_vector_index can only be set on vector queries".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            }),
         }
     }
 
-    pub fn execute(&self) -> Result<Vec<RecordBatch>> {
-        let stream = match self {
-            QueryHandle::Plain(q) => RT.block_on(q.execute())?,
-            QueryHandle::Vector(q) => RT.block_on(q.execute())?,
+    /// Search only the rows covered by the vector index, skipping the flat
+    /// scan of fragments added since the index was last built. Lowers
+    /// latency, but the results may miss recently added rows.
+    ///
+    /// The searched column must have a vector index; without one a fast
+    /// search could only ever return nothing.
+    pub fn fast_search(&mut self) -> Result<()> {
+        let QueryState::Vector(q) = &self.state else {
+            return Err(crate::error::Error::InvalidArgument {
+                message: "fast_search can only be set on vector queries".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
         };
-
-        let batches: Vec<RecordBatch> = RT.block_on(async {
-            use futures::TryStreamExt;
-            stream.try_collect::<Vec<_>>().await
-        })?;
-        Ok(batches)
-    }
-
-    pub fn execute_stream(&self) -> Result<BoxStream<'static, lancedb::Result<RecordBatch>>> {
-        let stream = match self {
-            QueryHandle::Plain(q) => RT.block_on(q.execute())?,
-            QueryHandle::Vector(q) => RT.block_on(q.execute())?,
+        let column = match &self.vector_column {
+            Some(column) => Some(column.clone()),
+            None => self.default_vector_field()?.map(|f| f.name().clone()),
         };
-        Ok(stream)
+        let indices = RT.block_on(self.table.list_indices())?;
+        let indexed = indices.iter().any(|idx| {
+            matches!(
+                idx.index_type,
+                IndexType::IvfPq | IndexType::IvfHnswPq | IndexType::IvfHnswSq
+            ) && column
+                .as_ref()
+                .map_or(true, |column| idx.columns.contains(column))
+        });
+        if !indexed {
+            return Err(crate::error::Error::InvalidArgument {
+                message: format!(
+                    "fast_search requires a vector index on {}",
+                    column.map_or("the searched column".to_string(), |c| format!("'{}'", c))
+                ),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+        self.state = QueryState::Vector(q.clone().fast_search());
+        Ok(())
     }
-}
-
-pub struct QueryStreamHandle {
-    stream: BoxStream<'static, lancedb::Result<RecordBatch>>,
-}
-
-// C API for queries
 
-/// Create a new query for a table.
-/// Returns a pointer to QueryHandle on success, null on failure.
-#[no_mangle]
-pub extern "C" fn lancedb_query_new(table: *const super::table::TableHandle) -> *mut QueryHandle {
-    if table.is_null() {
-        let error_msg = "table handle cannot be null";
-        let c_error = CString::new(error_msg).unwrap();
-        crate::lancedb_set_last_error(c_error.as_ptr());
-        return std::ptr::null_mut();
+    /// Choose whether the filter of a vector search is applied after the
+    /// search (postfilter) or before it (prefilter, the default).
+    ///
+    /// A postfilter is cheaper but filters only the nearest `limit` rows, so
+    /// it can return fewer than `limit` rows, or none, even when more rows
+    /// match the filter.
+    pub fn postfilter(&mut self, enable: bool) -> Result<()> {
+        match &self.state {
+            QueryState::Vector(_) => {
+                self.postfilter = enable;
+                Ok(())
+            }
+            QueryState::Plain(_) => Err(crate::error::Error::InvalidArgument {
+                message: "postfilter can only be set on vector queries".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            }),
+        }
     }
 
-    let table = unsafe { &*table };
-    let query = table.inner.query();
-    let handle = QueryHandle::new(query);
-    Box::into_raw(Box::new(handle))
-}
-
-/// Close a query and free resources.
-#[no_mangle]
-pub extern "C" fn lancedb_query_close(handle: *mut QueryHandle) {
-    if !handle.is_null() {
-        unsafe {
-            let _ = Box::from_raw(handle);
+    /// Set the size of the candidate list explored in the HNSW graph.
+    /// Larger values improve recall at the cost of latency. Only searches
+    /// served by an HNSW index (IVF_HNSW_SQ, IVF_HNSW_PQ) are affected.
+    pub fn ef(&mut self, ef: usize) -> Result<()> {
+        if ef == 0 {
+            return Err(crate::error::Error::InvalidArgument {
+                message: "ef must be positive".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+        match &self.state {
+            QueryState::Vector(q) => {
+                self.state = QueryState::Vector(q.clone().ef(ef));
+                Ok(())
+            }
+            QueryState::Plain(_) => Err(crate::error::Error::InvalidArgument {
+                message: "ef can only be set on vector queries".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            }),
         }
     }
-}
 
-/// Set the vector to search for nearest neighbors.
-/// Returns 0 on success, -1 on failure.
-#[no_mangle]
-pub extern "C" fn lancedb_query_nearest_to(
-    handle: *mut QueryHandle,
-    vector: *const c_float,
-    vector_len: c_int,
-) -> c_int {
-    if handle.is_null() || vector.is_null() || vector_len <= 0 {
-        let error_msg = "handle, vector cannot be null and vector_len must be positive";
-        let c_error = CString::new(error_msg).unwrap();
-        crate::lancedb_set_last_error(c_error.as_ptr());
-        return -1;
+    /// Cap the number of rows in each result batch. Smaller batches lower
+    /// peak memory; larger ones raise throughput.
+    pub fn with_batch_size(&mut self, size: usize) -> Result<()> {
+        if size == 0 || size > u32::MAX as usize {
+            return Err(crate::error::Error::InvalidArgument {
+                message: "batch size must be positive and fit in 32 bits".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+        self.batch_size = Some(size);
+        Ok(())
     }
 
-    let query = unsafe { &mut *handle };
+    /// Fail executions of the query that take longer than `millis`
+    /// milliseconds with a TimedOut error.
+    ///
+    /// The timeout bounds a whole execution, not each batch: all of
+    /// [`Self::execute`], or the opening of a stream by
+    /// [`Self::execute_stream`].
+    pub fn timeout(&mut self, millis: u64) -> Result<()> {
+        if millis == 0 {
+            return Err(crate::error::Error::InvalidArgument {
+                message: "timeout must be positive".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+        self.timeout = Some(Duration::from_millis(millis));
+        Ok(())
+    }
 
-    // Convert C array to Rust Vec
-    let vector_slice = unsafe { std::slice::from_raw_parts(vector, vector_len as usize) };
-    let vector_vec = vector_slice.to_vec();
+    /// When an execution starting now must finish, if the query has a timeout
+    fn deadline(&self) -> Option<Instant> {
+        self.timeout.map(|timeout| Instant::now() + timeout)
+    }
 
-    match query.nearest_to(vector_vec) {
-        Ok(_) => 0,
-        Err(err) => {
-            let error_msg = format!("{}", err);
-            let c_error = CString::new(error_msg).unwrap();
-            crate::lancedb_set_last_error(c_error.as_ptr());
-            -1
+    /// Options used whenever the query is executed or planned
+    fn execution_options(&self) -> QueryExecutionOptions {
+        let mut options = QueryExecutionOptions::default();
+        if let Some(size) = self.batch_size {
+            options.max_batch_length = size as u32;
         }
+        options
     }
-}
 
-/// Set the distance metric for the query.
-/// distance_type: 0 = L2, 1 = Cosine, 2 = Dot
-/// Returns 0 on success, -1 on failure.
-#[no_mangle]
-pub extern "C" fn lancedb_query_distance_type(
-    handle: *mut QueryHandle,
-    distance_type: c_int,
-) -> c_int {
-    if handle.is_null() {
-        let error_msg = "handle cannot be null";
-        let c_error = CString::new(error_msg).unwrap();
-        crate::lancedb_set_last_error(c_error.as_ptr());
-        return -1;
+    /// Choose the vector column to search. Required when the table has more
+    /// than one vector column; with a single one it is searched by default.
+    pub fn column(&mut self, name: &str) -> Result<()> {
+        let QueryState::Vector(q) = &self.state else {
+            return Err(crate::error::Error::InvalidArgument {
+                message: "the vector column can only be set on vector queries".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        };
+        if !self.vector_fields()?.iter().any(|f| f.name() == name) {
+            let schema = RT.block_on(self.table.schema())?;
+            if let Some(field) = schema.fields().iter().find(|f| f.name() == name) {
+                if is_float_list(field) {
+                    return Err(variable_length_error(field));
+                }
+            }
+            return Err(crate::error::Error::InvalidArgument {
+                message: format!("'{}' is not a vector column of the table", name),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+        self.state = QueryState::Vector(q.clone().column(name));
+        self.vector_column = Some(name.to_string());
+        Ok(())
     }
 
-    let query = unsafe { &mut *handle };
-
-    let dist_type = match distance_type {
-        0 => DistanceType::L2,
-        1 => DistanceType::Cosine,
-        2 => DistanceType::Dot,
-        _ => {
-            let error_msg = "invalid distance type: must be 0 (L2), 1 (Cosine), or 2 (Dot)";
-            let c_error = CString::new(error_msg).unwrap();
-            crate::lancedb_set_last_error(c_error.as_ptr());
-            return -1;
+    /// Restrict the query to rows matching a full-text search of `column`.
+    /// The column must have an FTS index.
+    pub fn full_text_search(&mut self, column: &str, query: &str) -> Result<()> {
+        let indices = RT.block_on(self.table.list_indices())?;
+        let indexed = indices.iter().any(|idx| {
+            matches!(idx.index_type, IndexType::FTS) && idx.columns.iter().any(|c| c == column)
+        });
+        if !indexed {
+            return Err(crate::error::Error::InvalidArgument {
+                message: format!(
+                    "column '{}' has no FTS index; create one with index type \"FTS\" first",
+                    column
+                ),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
         }
-    };
 
-    match query.distance_type(dist_type) {
-        Ok(_) => 0,
-        Err(err) => {
-            let error_msg = format!("{}", err);
-            let c_error = CString::new(error_msg).unwrap();
-            crate::lancedb_set_last_error(c_error.as_ptr());
-            -1
+        let fts_query =
+            FullTextSearchQuery::new(query.to_string()).columns(Some(vec![column.to_string()]));
+        match &self.state {
+            QueryState::Plain(q) => {
+                self.state = QueryState::Plain(q.clone().full_text_search(fts_query));
+                Ok(())
+            }
+            QueryState::Vector(_) => Err(crate::error::Error::InvalidArgument {
+                message: "full_text_search cannot be combined with a vector search".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            }),
         }
     }
-}
 
-/// Set the maximum number of results to return.
-/// Returns 0 on success, -1 on failure.
-#[no_mangle]
-pub extern "C" fn lancedb_query_limit(handle: *mut QueryHandle, limit: c_int) -> c_int {
-    if handle.is_null() || limit < 0 {
-        let error_msg = "handle cannot be null and limit must be non-negative";
-        let c_error = CString::new(error_msg).unwrap();
-        crate::lancedb_set_last_error(c_error.as_ptr());
-        return -1;
+    /// Search `text` with the table's FTS index and `vector` with its vector
+    /// index, and combine the two result sets with the query's reranker
+    /// (reciprocal rank fusion by default).
+    ///
+    /// The table must have exactly one FTS index and one vector index. The
+    /// filter and selection apply to both searches, each of which fetches
+    /// `limit + offset` candidates. Results carry `_rowid` and
+    /// `_relevance_score`, best first, in a single batch; options that shape
+    /// vector or ordered results (order_by_desc, distance_range,
+    /// with_distance, postfilter, batch size) cannot be combined with it.
+    pub fn hybrid(&mut self, text: &str, vector: Vec<f32>) -> Result<()> {
+        if !matches!(self.state, QueryState::Plain(_)) || self.hybrid.is_some() {
+            return Err(crate::error::Error::InvalidArgument {
+                message: "hybrid search cannot be combined with another search".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+        self.check_hybrid_options()?;
+
+        let indices = RT.block_on(self.table.list_indices())?;
+        let mut fts_columns = Vec::new();
+        let mut vector_columns = Vec::new();
+        for index in indices {
+            match index.index_type {
+                IndexType::FTS => fts_columns.extend(index.columns),
+                IndexType::IvfPq | IndexType::IvfHnswPq | IndexType::IvfHnswSq => {
+                    vector_columns.extend(index.columns)
+                }
+                _ => {}
+            }
+        }
+        let (Some(fts_column), Some(vector_column)) = (fts_columns.pop(), vector_columns.pop())
+        else {
+            return Err(crate::error::Error::InvalidArgument {
+                message: "hybrid search requires both an FTS index and a vector index".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        };
+        if !fts_columns.is_empty() || !vector_columns.is_empty() {
+            return Err(crate::error::Error::InvalidArgument {
+                message: "hybrid search requires exactly one FTS index and one vector index"
+                    .to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+        self.check_query_vector(&DataType::Float32, vector.len())?;
+
+        self.hybrid = Some(HybridSearch {
+            text: text.to_string(),
+            fts_column,
+            vector,
+            vector_column,
+        });
+        Ok(())
     }
 
-    let query = unsafe { &mut *handle };
+    /// Combine the results of a hybrid search with `reranker` instead of
+    /// reciprocal rank fusion
+    pub fn set_reranker(&mut self, reranker: Reranker) {
+        self.reranker = reranker;
+    }
 
-    match query.limit(limit as usize) {
-        Ok(_) => 0,
-        Err(err) => {
-            let error_msg = format!("{}", err);
-            let c_error = CString::new(error_msg).unwrap();
-            crate::lancedb_set_last_error(c_error.as_ptr());
-            -1
+    /// Fail if the query sets an option the hybrid search would ignore
+    fn check_hybrid_options(&self) -> Result<()> {
+        let conflicting = [
+            ("order_by_desc", self.order_by_desc.is_some()),
+            ("distance_range", self.distance_range.is_some()),
+            ("with_distance", !self.with_distance),
+            ("postfilter", self.postfilter),
+            ("batch size", self.batch_size.is_some()),
+        ];
+        match conflicting.iter().find(|(_, set)| *set) {
+            Some((option, _)) => Err(crate::error::Error::InvalidArgument {
+                message: format!("{} cannot be combined with a hybrid search", option),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            }),
+            None => Ok(()),
         }
     }
-}
 
-/// Set the offset for query results.
-/// Returns 0 on success, -1 on failure.
-#[no_mangle]
-pub extern "C" fn lancedb_query_offset(handle: *mut QueryHandle, offset: c_int) -> c_int {
-    if handle.is_null() || offset < 0 {
-        let error_msg = "handle cannot be null and offset must be non-negative";
-        let c_error = CString::new(error_msg).unwrap();
-        crate::lancedb_set_last_error(c_error.as_ptr());
-        return -1;
+    /// Run both searches of a hybrid query and rerank their results
+    fn execute_hybrid(
+        &self,
+        hybrid: &HybridSearch,
+        deadline: Option<Instant>,
+    ) -> Result<RecordBatch> {
+        let QueryState::Plain(base) = &self.state else {
+            return Err(crate::error::Error::InvalidArgument {
+                message: "hybrid search cannot be combined with another search".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        };
+        // Options can still be set after hybrid() was called
+        self.check_hybrid_options()?;
+        let offset = self.offset.unwrap_or(0);
+        let limit = self.limit.unwrap_or(DEFAULT_HYBRID_LIMIT);
+
+        let vector_query = base
+            .clone()
+            .nearest_to(hybrid.vector.clone())?
+            .column(&hybrid.vector_column)
+            .with_row_id()
+            .limit(limit.saturating_add(offset));
+        let fts_query = base
+            .clone()
+            .full_text_search(
+                FullTextSearchQuery::new(hybrid.text.clone())
+                    .columns(Some(vec![hybrid.fts_column.clone()])),
+            )
+            .with_row_id()
+            .limit(limit.saturating_add(offset));
+
+        let (vector_options, fts_options) = (self.execution_options(), self.execution_options());
+        let (vector_results, fts_results) = block_on_until(deadline, async move {
+            let vector_results =
+                collect_batch(vector_query.execute_with_options(vector_options).await?).await?;
+            let fts_results =
+                collect_batch(fts_query.execute_with_options(fts_options).await?).await?;
+            Ok::<_, lancedb::Error>((vector_results, fts_results))
+        })??;
+
+        let reranked = self.reranker.rerank(
+            &hybrid.text,
+            &hybrid.fts_column,
+            &vector_results,
+            &fts_results,
+        )?;
+        let start = offset.min(reranked.num_rows());
+        let len = (reranked.num_rows() - start).min(limit);
+        Ok(reranked.slice(start, len))
     }
 
-    let query = unsafe { &mut *handle };
+    /// Include the `_rowid` column, a stable identifier for each row, in the results
+    pub fn with_row_id(&mut self) -> Result<()> {
+        match &self.state {
+            QueryState::Plain(q) => {
+                self.state = QueryState::Plain(q.clone().with_row_id());
+                Ok(())
+            }
+            QueryState::Vector(q) => {
+                self.state = QueryState::Vector(q.clone().with_row_id());
+                Ok(())
+            }
+        }
+    }
 
-    match query.offset(offset as usize) {
-        Ok(_) => 0,
-        Err(err) => {
-            let error_msg = format!("{}", err);
-            let c_error = CString::new(error_msg).unwrap();
-            crate::lancedb_set_last_error(c_error.as_ptr());
-            -1
+    /// Limit and offset are applied when the query runs, since an ordered
+    /// query must apply them after sorting rather than during the scan.
+    ///
+    /// A plain query with a limit of 0 returns no rows. A vector search
+    /// needs at least one neighbour, so it rejects a limit of 0; without a
+    /// limit it returns the 10 nearest rows.
+    pub fn limit(&mut self, limit: usize) -> Result<()> {
+        if limit == 0 && matches!(self.state, QueryState::Vector(_)) {
+            return Err(zero_vector_limit());
         }
+        self.limit = Some(limit);
+        Ok(())
     }
-}
 
-/// Set a filter predicate for the query.
-/// Returns 0 on success, -1 on failure.
-#[no_mangle]
-pub extern "C" fn lancedb_query_filter(handle: *mut QueryHandle, filter: *const c_char) -> c_int {
-    if handle.is_null() || filter.is_null() {
-        let error_msg = "handle and filter cannot be null";
-        let c_error = CString::new(error_msg).unwrap();
-        crate::lancedb_set_last_error(c_error.as_ptr());
-        return -1;
+    /// Skip the first `offset` results. A plain query skips rows of the
+    /// scan; a vector search finds the `limit + offset` nearest rows and
+    /// drops the nearest `offset` of them, so the same search paged with
+    /// increasing offsets never repeats or misses a row.
+    pub fn offset(&mut self, offset: usize) -> Result<()> {
+        self.offset = Some(offset);
+        Ok(())
     }
 
-    let query = unsafe { &mut *handle };
-    let c_str = unsafe { CStr::from_ptr(filter) };
-    let filter_str = match c_str.to_str() {
-        Ok(s) => s,
-        Err(err) => {
-            let error_msg = format!("invalid UTF-8 in filter: {}", err);
-            let c_error = CString::new(error_msg).unwrap();
-            crate::lancedb_set_last_error(c_error.as_ptr());
-            return -1;
+    /// The rows a vector search must skip and keep after searching, as
+    /// `(offset, limit)`, or `None` when LanceDB's own limit is enough
+    fn post_search_page(&self) -> Option<(usize, Option<usize>)> {
+        if !matches!(self.state, QueryState::Vector(_)) {
+            return None;
         }
-    };
+        match self.offset {
+            Some(offset) if offset > 0 => {
+                Some((offset, Some(self.limit.unwrap_or(DEFAULT_VECTOR_LIMIT))))
+            }
+            _ => None,
+        }
+    }
 
-    match query.filter(filter_str) {
-        Ok(_) => 0,
-        Err(err) => {
-            let error_msg = format!("{}", err);
-            let c_error = CString::new(error_msg).unwrap();
-            crate::lancedb_set_last_error(c_error.as_ptr());
-            -1
+    /// Return results sorted by `column`, largest first, with nulls last.
+    /// Only plain (non-vector) queries can be ordered; vector results are
+    /// already ordered by distance.
+    pub fn order_by_desc(&mut self, column: &str) -> Result<()> {
+        if matches!(self.state, QueryState::Vector(_)) {
+            return Err(crate::error::Error::InvalidArgument {
+                message: "order_by_desc cannot be combined with a vector search".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
         }
+        let schema = RT.block_on(self.table.schema())?;
+        schema.field_with_name(column)?;
+        self.order_by_desc = Some(column.to_string());
+        Ok(())
     }
-}
 
-/// Set the columns to select in the query results.
-/// columns is a pointer to an array of C strings.
-/// Returns 0 on success, -1 on failure.
-#[no_mangle]
-pub extern "C" fn lancedb_query_select(
-    handle: *mut QueryHandle,
-    columns: *const *const c_char,
+    /// The query to hand to LanceDB. Unordered queries push the limit and
+    /// offset into the scan; ordered queries read every matching row and
+    /// apply them after sorting. Reading the schema to pick the vector
+    /// column fails with a TimedOut error if `deadline` passes.
+    fn planned(&self, deadline: Option<Instant>) -> Result<QueryState> {
+        if self.hybrid.is_some() {
+            return Err(crate::error::Error::InvalidArgument {
+                message: "hybrid searches run two plans and cannot be planned as one".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+        if self.order_by_desc.is_some() {
+            return match &self.state {
+                QueryState::Plain(q) => Ok(QueryState::Plain(q.clone())),
+                QueryState::Vector(_) => Err(crate::error::Error::InvalidArgument {
+                    message: "order_by_desc cannot be combined with a vector search".to_string(),
+                    location: snafu::Location::new(file!(), line!(), column!()),
+                }),
+            };
+        }
+        let state = match &self.state {
+            QueryState::Plain(q) => {
+                let mut q = q.clone();
+                if let Some(limit) = self.limit {
+                    q = q.limit(limit);
+                }
+                if let Some(offset) = self.offset {
+                    q = q.offset(offset);
+                }
+                QueryState::Plain(q)
+            }
+            QueryState::Vector(q) => {
+                if self.vector_column.is_none() {
+                    let vector_fields = self.vector_fields_until(deadline)?;
+                    if vector_fields.len() > 1 {
+                        let names: Vec<&str> =
+                            vector_fields.iter().map(|f| f.name().as_str()).collect();
+                        return Err(crate::error::Error::InvalidArgument {
+                            message: format!(
+                                "the table has several vector columns ({}); choose one to search",
+                                names.join(", ")
+                            ),
+                            location: snafu::Location::new(file!(), line!(), column!()),
+                        });
+                    }
+                }
+                if self.limit == Some(0) {
+                    return Err(zero_vector_limit());
+                }
+                if self.distance_range.is_some() && self.limit.is_none() {
+                    return Err(crate::error::Error::InvalidArgument {
+                        message: "distance_range requires a limit on the rows searched".to_string(),
+                        location: snafu::Location::new(file!(), line!(), column!()),
+                    });
+                }
+                let mut q = q.clone();
+                // The offset is never handed to LanceDB: the search fetches
+                // the skipped rows too and execute_planned drops them
+                if let Some((offset, Some(limit))) = self.post_search_page() {
+                    q = q.limit(limit.saturating_add(offset));
+                } else if let Some(limit) = self.limit {
+                    q = q.limit(limit);
+                }
+                if self.postfilter {
+                    q = q.postfilter();
+                }
+                QueryState::Vector(q)
+            }
+        };
+        Ok(state)
+    }
+
+    pub fn filter(&mut self, filter: &str) -> Result<()> {
+        match &self.state {
+            QueryState::Plain(q) => {
+                self.state = QueryState::Plain(q.clone().only_if(filter));
+                Ok(())
+            }
+            QueryState::Vector(q) => {
+                self.state = QueryState::Vector(q.clone().only_if(filter));
+                Ok(())
+            }
+        }
+    }
+
+    /// Filter on the timestamp column `column`, comparing it with `micros`
+    /// microseconds since the Unix epoch using `op`, one of the FILTER_OP_*
+    /// constants. Like `filter`, this replaces any previous filter.
+    ///
+    /// The bound is written as a microsecond timestamp in UTC, so the
+    /// predicate compares instants whatever time zone and unit the column
+    /// stores. Values of a column without a time zone are taken to be UTC.
+    pub fn filter_timestamp(&mut self, column: &str, op: c_int, micros: i64) -> Result<()> {
+        let op = filter_op_sql(op).ok_or_else(|| crate::error::Error::InvalidArgument {
+            message: format!(
+                "invalid filter operator {}: must be 0 (=), 1 (>), 2 (>=), 3 (<), or 4 (<=)",
+                op
+            ),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        })?;
+        let schema = RT.block_on(self.table.schema())?;
+        let field =
+            schema
+                .field_with_name(column)
+                .map_err(|_| crate::error::Error::InvalidArgument {
+                    message: format!("'{}' is not a column of the table", column),
+                    location: snafu::Location::new(file!(), line!(), column!()),
+                })?;
+        if !matches!(field.data_type(), DataType::Timestamp(_, _)) {
+            return Err(crate::error::Error::InvalidArgument {
+                message: format!(
+                    "column '{}' has type {}, not a timestamp",
+                    column,
+                    field.data_type()
+                ),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+        // A TIMESTAMP '...' literal has no time zone, and comparing it with a
+        // zoned column would read it as local time in the column's zone
+        self.filter(&format!(
+            "{} {} arrow_cast({}, 'Timestamp(Microsecond, Some(\"UTC\"))')",
+            crate::table::quote_identifier(column),
+            op,
+            micros
+        ))
+    }
+
+    /// Project the results onto `columns`.
+    ///
+    /// Vector searches append `_distance` to any projection, so it is
+    /// returned whether or not `columns` lists it; use
+    /// [`Self::with_distance`] to drop it.
+    pub fn select(&mut self, columns: Vec<String>) -> Result<()> {
+        match &self.state {
+            QueryState::Plain(q) => {
+                self.state =
+                    QueryState::Plain(q.clone().select(lancedb::query::Select::columns(&columns)));
+                Ok(())
+            }
+            QueryState::Vector(q) => {
+                let columns: Vec<String> =
+                    columns.into_iter().filter(|c| c != "_distance").collect();
+                self.state =
+                    QueryState::Vector(q.clone().select(lancedb::query::Select::columns(&columns)));
+                Ok(())
+            }
+        }
+    }
+
+    /// Undo any earlier `select` or `select_expr` so every table column is
+    /// returned again. `_distance` and `_rowid` are still governed by
+    /// [`Self::with_distance`] and [`Self::with_row_id`].
+    pub fn select_all(&mut self) {
+        self.state = match &self.state {
+            QueryState::Plain(q) => {
+                QueryState::Plain(q.clone().select(lancedb::query::Select::All))
+            }
+            QueryState::Vector(q) => {
+                QueryState::Vector(q.clone().select(lancedb::query::Select::All))
+            }
+        };
+    }
+
+    /// Select computed columns. Each entry is an `(alias, expression)` pair
+    /// where the expression is SQL over the table's columns, e.g.
+    /// `("price_with_tax", "price * 1.1")`. A plain column is selected by
+    /// using its name as the expression.
+    ///
+    /// The expressions are checked by planning the query, so a malformed
+    /// expression is reported here and leaves the query unchanged.
+    pub fn select_expr(&mut self, columns: Vec<(String, String)>) -> Result<()> {
+        let select = lancedb::query::Select::Dynamic(columns);
+        let previous = match &self.state {
+            QueryState::Plain(q) => {
+                std::mem::replace(&mut self.state, QueryState::Plain(q.clone().select(select)))
+            }
+            QueryState::Vector(q) => std::mem::replace(
+                &mut self.state,
+                QueryState::Vector(q.clone().select(select)),
+            ),
+        };
+        if let Err(err) = self.explain_plan(false) {
+            self.state = previous;
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// Choose whether vector search results include the `_distance` column
+    /// (Float32). It is included by default.
+    pub fn with_distance(&mut self, include: bool) -> Result<()> {
+        match &self.state {
+            QueryState::Vector(_) => {
+                self.with_distance = include;
+                Ok(())
+            }
+            QueryState::Plain(_) => Err(crate::error::Error::InvalidArgument {
+                message: "with_distance can only be set on vector queries".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            }),
+        }
+    }
+
+    /// Apply every option present in `config` to the query.
+    /// The vector is applied first so vector-only options can follow it.
+    pub fn configure(&mut self, config: QueryConfig) -> Result<()> {
+        if let Some(vector) = config.vector {
+            self.nearest_to(vector)?;
+        }
+        if let Some(distance_type) = config.distance_type {
+            let distance_type = match distance_type {
+                0 => DistanceType::L2,
+                1 => DistanceType::Cosine,
+                2 => DistanceType::Dot,
+                _ => {
+                    return Err(crate::error::Error::InvalidArgument {
+                        message: "invalid distance_type: must be 0 (L2), 1 (Cosine), or 2 (Dot)"
+                            .to_string(),
+                        location: snafu::Location::new(file!(), line!(), column!()),
+                    })
+                }
+            };
+            self.distance_type(distance_type)?;
+        }
+        if let Some(nprobes) = config.nprobes {
+            self.nprobes(nprobes)?;
+        }
+        if let Some(filter) = config.filter {
+            self.filter(&filter)?;
+        }
+        if let Some(columns) = config.select {
+            self.select(columns)?;
+        }
+        if let Some(limit) = config.limit {
+            self.limit(limit)?;
+        }
+        if let Some(offset) = config.offset {
+            self.offset(offset)?;
+        }
+        Ok(())
+    }
+
+    /// Ask the planner how many rows the query is expected to return,
+    /// without executing it. Returns `None` when the plan has no estimate.
+    pub fn estimated_rows(&self) -> Result<Option<usize>> {
+        let options = self.execution_options();
+        let plan = match self.planned(None)? {
+            QueryState::Plain(q) => RT.block_on(q.create_plan(options))?,
+            QueryState::Vector(q) => RT.block_on(q.create_plan(options))?,
+        };
+        let statistics = plan
+            .statistics()
+            .map_err(|e| crate::error::Error::OtherLanceDB {
+                message: format!("failed to compute plan statistics: {}", e),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            })?;
+        Ok(match statistics.num_rows {
+            Precision::Exact(n) | Precision::Inexact(n) => Some(n),
+            Precision::Absent => None,
+        })
+    }
+
+    /// Describe the physical plan the query would run, without executing it
+    pub fn explain_plan(&self, verbose: bool) -> Result<String> {
+        let plan = match self.planned(None)? {
+            QueryState::Plain(q) => RT.block_on(q.explain_plan(verbose))?,
+            QueryState::Vector(q) => RT.block_on(q.explain_plan(verbose))?,
+        };
+        Ok(plan)
+    }
+
+    /// Run the query and describe its physical plan annotated with the
+    /// metrics collected while running, such as the rows each operator
+    /// produced and the time it spent.
+    ///
+    /// Unlike [`Self::explain_plan`] this executes the query in full; the
+    /// results are read and discarded.
+    pub fn analyze_plan(&self) -> Result<String> {
+        let options = self.execution_options();
+        let plan = match self.planned(None)? {
+            QueryState::Plain(q) => RT.block_on(q.create_plan(options))?,
+            QueryState::Vector(q) => RT.block_on(q.create_plan(options))?,
+        };
+        let context = Arc::new(TaskContext::default());
+        RT.block_on(datafusion_physical_plan::collect(plan.clone(), context))
+            .map_err(|e| crate::error::Error::OtherLanceDB {
+                message: format!("failed to execute query: {}", e),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            })?;
+        Ok(DisplayableExecutionPlan::with_metrics(plan.as_ref())
+            .indent(true)
+            .to_string())
+    }
+
+    /// Run the query and collect its results.
+    ///
+    /// Execution does not consume the query: a handle can be executed any
+    /// number of times, each run reading the table as it is at that moment.
+    pub fn execute(&self) -> Result<Vec<RecordBatch>> {
+        let deadline = self.deadline();
+        let (_, stream) = self.execute_with_schema(deadline)?;
+
+        let batches: Vec<RecordBatch> = block_on_until(deadline, async {
+            use futures::TryStreamExt;
+            stream.try_collect::<Vec<_>>().await
+        })??;
+        Ok(batches)
+    }
+
+    pub fn execute_stream(&self) -> Result<BoxStream<'static, lancedb::Result<RecordBatch>>> {
+        let (_, stream) = self.execute_with_schema(self.deadline())?;
+        Ok(stream)
+    }
+
+    /// Execute the query, returning the schema of the results along with
+    /// the stream of batches. Opening the stream fails if `deadline` passes.
+    fn execute_with_schema(
+        &self,
+        deadline: Option<Instant>,
+    ) -> Result<(SchemaRef, BoxStream<'static, lancedb::Result<RecordBatch>>)> {
+        let (schema, stream) = self.execute_planned(deadline)?;
+        if self.with_distance || !matches!(self.state, QueryState::Vector(_)) {
+            return Ok((schema, stream));
+        }
+        let Ok(distance_index) = schema.index_of("_distance") else {
+            return Ok((schema, stream));
+        };
+        let indices: Vec<usize> = (0..schema.fields().len())
+            .filter(|i| *i != distance_index)
+            .collect();
+        let schema = Arc::new(schema.project(&indices)?);
+        let stream = stream
+            .map(move |batch| {
+                batch?
+                    .project(&indices)
+                    .map_err(|source| lancedb::Error::Arrow { source })
+            })
+            .boxed();
+        Ok((schema, stream))
+    }
+
+    fn execute_planned(
+        &self,
+        deadline: Option<Instant>,
+    ) -> Result<(SchemaRef, BoxStream<'static, lancedb::Result<RecordBatch>>)> {
+        if let Some(hybrid) = &self.hybrid {
+            let batch = self.execute_hybrid(hybrid, deadline)?;
+            return Ok((
+                batch.schema(),
+                futures::stream::once(async move { Ok(batch) }).boxed(),
+            ));
+        }
+        let options = self.execution_options();
+        let stream = match self.planned(deadline)? {
+            QueryState::Plain(q) => block_on_until(deadline, q.execute_with_options(options))??,
+            QueryState::Vector(q) => block_on_until(deadline, q.execute_with_options(options))??,
+        };
+        let schema = stream.schema();
+        // Only plain queries are ordered, and only vector searches are paged
+        // after searching or filtered by distance
+        if let Some(column) = &self.order_by_desc {
+            let offset = self.offset.unwrap_or(0);
+            let fetch = self.limit.map(|limit| limit.saturating_add(offset));
+            let sorted = block_on_until(deadline, top_n_desc(stream, column, fetch))??;
+            let start = offset.min(sorted.num_rows());
+            let len = (sorted.num_rows() - start).min(self.limit.unwrap_or(usize::MAX));
+            let page = sorted.slice(start, len);
+            return Ok((
+                schema,
+                futures::stream::once(async move { Ok(page) }).boxed(),
+            ));
+        }
+        let stream = match self.post_search_page() {
+            Some((offset, limit)) => page_stream(stream, offset, limit),
+            None => stream.boxed(),
+        };
+        match self.distance_range {
+            Some((lower, upper)) => Ok((schema, filter_distance_range(stream, lower, upper))),
+            None => Ok((schema, stream)),
+        }
+    }
+
+    /// Execute the query as a blocking reader that pulls each batch from
+    /// the stream only when the consumer asks for it
+    pub fn execute_reader(&self) -> Result<BlockingBatchReader> {
+        let (schema, stream) = self.execute_with_schema(self.deadline())?;
+        Ok(BlockingBatchReader { schema, stream })
+    }
+
+    /// Execute the query and write each result row to `path` as a JSON
+    /// object on its own line. Batches are written as they arrive, so the
+    /// full result is never held in memory unless the query is ordered.
+    /// Returns the number of rows written.
+    pub fn execute_ndjson(&self, path: &str) -> Result<usize> {
+        let mut stream = self.execute_stream()?;
+        let file = BufWriter::new(File::create(path)?);
+        let mut writer = WriterBuilder::new()
+            .with_explicit_nulls(true)
+            .build::<_, LineDelimited>(file);
+
+        let mut rows = 0;
+        while let Some(batch) = RT.block_on(stream.next()) {
+            let batch = batch?;
+            writer.write(&batch)?;
+            rows += batch.num_rows();
+        }
+        writer.finish()?;
+        writer.into_inner().flush()?;
+        Ok(rows)
+    }
+}
+
+/// Run `future` on the runtime, failing with a TimedOut error if `deadline`
+/// passes before it completes
+fn block_on_until<F: Future>(deadline: Option<Instant>, future: F) -> Result<F::Output> {
+    let Some(deadline) = deadline else {
+        return Ok(RT.block_on(future));
+    };
+    RT.block_on(async { tokio::time::timeout_at(deadline.into(), future).await })
+        .map_err(|_| crate::error::Error::TimedOut {
+            message: "query did not complete within its timeout".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        })
+}
+
+/// Read every batch of `stream` into a single batch
+async fn collect_batch(stream: SendableRecordBatchStream) -> lancedb::Result<RecordBatch> {
+    use futures::TryStreamExt;
+    let schema = stream.schema();
+    let batches: Vec<RecordBatch> = stream.try_collect().await?;
+    concat_batches(&schema, &batches).map_err(|source| lancedb::Error::Arrow { source })
+}
+
+/// Sort the rows of `stream` by `column` descending, keeping the first
+/// `fetch` rows if given.
+///
+/// With a fetch bound the best `fetch` rows read so far are kept in a heap,
+/// so memory stays proportional to `fetch` however many rows match. Without
+/// one every matching row is buffered and sorted at the end.
+async fn top_n_desc(
+    mut stream: SendableRecordBatchStream,
+    column: &str,
+    fetch: Option<usize>,
+) -> Result<RecordBatch> {
+    use futures::TryStreamExt;
+    let schema = stream.schema();
+    let index = order_column_index(&schema, column)?;
+    let Some(fetch) = fetch else {
+        let batches: Vec<RecordBatch> = stream.try_collect().await?;
+        return sort_desc(&concat_batches(&schema, &batches)?, column, None);
+    };
+    let converter = RowConverter::new(vec![SortField::new_with_options(
+        schema.field(index).data_type().clone(),
+        DESC_NULLS_LAST,
+    )])?;
+
+    // Encoded rows compare in output order, so the top of the max-heap is
+    // the worst row kept. Each entry points into one of `kept`, which holds
+    // the rows of each batch that entered the heap; `live` counts how many
+    // of them are still in it so evicted batches can be dropped.
+    let mut heap: BinaryHeap<(OwnedRow, usize, usize)> = BinaryHeap::with_capacity(fetch);
+    let mut kept: Vec<Option<RecordBatch>> = Vec::new();
+    let mut live: Vec<usize> = Vec::new();
+    while fetch > 0 {
+        let Some(batch) = stream.next().await else {
+            break;
+        };
+        let candidates = sort_desc(&batch?, column, Some(fetch))?;
+        let rows = converter.convert_columns(&[candidates.column(index).clone()])?;
+        let slot = kept.len();
+        kept.push(Some(candidates));
+        live.push(0);
+        for (row_index, row) in rows.iter().enumerate() {
+            if heap.len() == fetch {
+                let worst = heap.peek().expect("heap is full");
+                // Candidates are sorted, so none after this one can enter
+                if row >= worst.0.row() {
+                    break;
+                }
+                let (_, evicted, _) = heap.pop().expect("heap is full");
+                live[evicted] -= 1;
+                if live[evicted] == 0 {
+                    kept[evicted] = None;
+                }
+            }
+            heap.push((row.owned(), slot, row_index));
+            live[slot] += 1;
+        }
+        if live[slot] == 0 {
+            kept[slot] = None;
+        }
+    }
+    if heap.is_empty() {
+        return Ok(RecordBatch::new_empty(schema));
+    }
+
+    let mut positions = vec![0; kept.len()];
+    let mut batches = Vec::new();
+    for (slot, batch) in kept.iter().enumerate() {
+        if let Some(batch) = batch {
+            positions[slot] = batches.len();
+            batches.push(batch);
+        }
+    }
+    let indices: Vec<(usize, usize)> = heap
+        .into_sorted_vec()
+        .into_iter()
+        .map(|(_, slot, row_index)| (positions[slot], row_index))
+        .collect();
+    let columns = (0..schema.fields().len())
+        .map(|i| {
+            let arrays: Vec<&dyn Array> = batches.iter().map(|b| b.column(i).as_ref()).collect();
+            interleave(&arrays, &indices)
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
+const DESC_NULLS_LAST: SortOptions = SortOptions {
+    descending: true,
+    nulls_first: false,
+};
+
+fn order_column_index(schema: &SchemaRef, column: &str) -> Result<usize> {
+    schema
+        .index_of(column)
+        .map_err(|_| crate::error::Error::InvalidArgument {
+            message: format!(
+                "order column '{}' must be among the selected columns",
+                column
+            ),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        })
+}
+
+fn sort_desc(batch: &RecordBatch, column: &str, fetch: Option<usize>) -> Result<RecordBatch> {
+    let values = batch.column(order_column_index(&batch.schema(), column)?);
+    let indices = sort_to_indices(values, Some(DESC_NULLS_LAST), fetch)?;
+    Ok(take_record_batch(batch, &indices)?)
+}
+
+/// Keep the rows of `stream` whose `_distance` lies in `[lower, upper)`
+fn filter_distance_range(
+    stream: BoxStream<'static, lancedb::Result<RecordBatch>>,
+    lower: Option<f32>,
+    upper: Option<f32>,
+) -> BoxStream<'static, lancedb::Result<RecordBatch>> {
+    stream
+        .map(move |batch| {
+            rows_in_distance_range(&batch?, lower, upper)
+                .map_err(|source| lancedb::Error::Arrow { source })
+        })
+        .boxed()
+}
+
+/// Skip the first `offset` rows of `stream` and keep at most `limit` of the
+/// rest, all of them when `limit` is `None`
+fn page_stream(
+    stream: impl futures::Stream<Item = lancedb::Result<RecordBatch>> + Send + 'static,
+    offset: usize,
+    limit: Option<usize>,
+) -> BoxStream<'static, lancedb::Result<RecordBatch>> {
+    let (mut skip, mut remaining) = (offset, limit.unwrap_or(usize::MAX));
+    stream
+        .map(move |batch| {
+            let batch = batch?;
+            let start = skip.min(batch.num_rows());
+            skip -= start;
+            let len = (batch.num_rows() - start).min(remaining);
+            remaining -= len;
+            Ok(batch.slice(start, len))
+        })
+        .boxed()
+}
+
+fn zero_vector_limit() -> crate::error::Error {
+    crate::error::Error::InvalidArgument {
+        message: "a vector search needs a limit of at least 1".to_string(),
+        location: snafu::Location::new(file!(), line!(), column!()),
+    }
+}
+
+fn rows_in_distance_range(
+    batch: &RecordBatch,
+    lower: Option<f32>,
+    upper: Option<f32>,
+) -> std::result::Result<RecordBatch, ArrowError> {
+    let distances = batch
+        .column_by_name("_distance")
+        .and_then(|c| c.as_any().downcast_ref::<Float32Array>())
+        .ok_or_else(|| {
+            ArrowError::SchemaError(
+                "distance_range requires the _distance column in the results".to_string(),
+            )
+        })?;
+    let in_range: BooleanArray = distances
+        .iter()
+        .map(|d| {
+            Some(d.is_some_and(|d| {
+                lower.map_or(true, |lower| d >= lower) && upper.map_or(true, |upper| d < upper)
+            }))
+        })
+        .collect();
+    filter_record_batch(batch, &in_range)
+}
+
+/// Adapts a query's batch stream to Arrow's synchronous `RecordBatchReader`
+/// by driving the stream on the runtime one batch at a time
+pub struct BlockingBatchReader {
+    schema: SchemaRef,
+    stream: BoxStream<'static, lancedb::Result<RecordBatch>>,
+}
+
+impl Iterator for BlockingBatchReader {
+    type Item = std::result::Result<RecordBatch, ArrowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        RT.block_on(self.stream.next())
+            .map(|batch| batch.map_err(|e| ArrowError::ExternalError(Box::new(e))))
+    }
+}
+
+impl RecordBatchReader for BlockingBatchReader {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
+
+pub struct QueryStreamHandle {
+    stream: BoxStream<'static, lancedb::Result<RecordBatch>>,
+    /// Set by lancedb_stream_cancel, possibly from another thread
+    cancel: StreamCancel,
+}
+
+/// Returned by lancedb_stream_next once the stream has been cancelled
+pub const STREAM_CANCELLED: c_int = -2;
+
+/// Cancellation signal shared between lancedb_stream_cancel and a
+/// lancedb_stream_next that may be blocked waiting for a batch
+#[derive(Default)]
+struct StreamCancel {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl StreamCancel {
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        // notify_one stores a permit when nobody is waiting yet, so a
+        // stream_next that starts waiting after this still wakes up
+        self.notify.notify_one();
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+// C API for queries
+
+/// Create a new query for a table.
+/// Returns a pointer to QueryHandle on success, null on failure.
+#[no_mangle]
+pub extern "C" fn lancedb_query_new(table: *const super::table::TableHandle) -> *mut QueryHandle {
+    if table.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return std::ptr::null_mut();
+    }
+
+    let table = unsafe { &*table };
+    let mut handle = QueryHandle::new(table.inner.clone());
+    handle.set_embedding_columns(table.embedding_columns());
+    Box::into_raw(Box::new(handle))
+}
+
+/// Clear everything set on a query (search, filter, selection, limit, and
+/// other options), leaving a fresh query of the same table.
+/// Returns 0 on success, -1 on failure.
+///
+/// Resetting is only needed to build a different query: a query can be
+/// executed repeatedly as it is. A query keeps its table open, so it stays
+/// usable after the table handle it was created from is closed.
+#[no_mangle]
+pub extern "C" fn lancedb_query_reset(handle: *mut QueryHandle) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &mut *handle };
+    query.reset();
+    0
+}
+
+/// Copy a query with everything set on it so far. Returns a pointer to the
+/// new QueryHandle on success, null on failure.
+///
+/// A query handle must not be modified while another thread is using it.
+/// Cloning only reads the source, so threads sharing a query can each take
+/// a clone and then refine and execute it independently, e.g. keeping a
+/// template's filter while searching a different vector per thread. The
+/// clone must be released with lancedb_query_close, before or after the
+/// source.
+#[no_mangle]
+pub extern "C" fn lancedb_query_clone(handle: *const QueryHandle) -> *mut QueryHandle {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return std::ptr::null_mut();
+    }
+
+    let query = unsafe { &*handle };
+    Box::into_raw(Box::new(query.clone()))
+}
+
+/// Close a query and free resources.
+#[no_mangle]
+pub extern "C" fn lancedb_query_close(handle: *mut QueryHandle) {
+    if !handle.is_null() {
+        unsafe {
+            let _ = Box::from_raw(handle);
+        }
+    }
+}
+
+/// Set the vector to search for nearest neighbors.
+/// Returns 0 on success, -1 on failure.
+#[no_mangle]
+pub extern "C" fn lancedb_query_nearest_to(
+    handle: *mut QueryHandle,
+    vector: *const c_float,
+    vector_len: c_int,
+) -> c_int {
+    if handle.is_null() || vector.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle and vector cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+    if vector_len <= 0 {
+        crate::set_last_error(&crate::error::Error::InvalidArgument {
+            message: "vector_len must be positive".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &mut *handle };
+
+    // Convert C array to Rust Vec
+    let vector_slice = unsafe { std::slice::from_raw_parts(vector, vector_len as usize) };
+    let vector_vec = vector_slice.to_vec();
+
+    match query.nearest_to(vector_vec) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Set a double-precision vector to search for nearest neighbors.
+/// The target vector column must be a FixedSizeList<Float64>; searching a
+/// Float32 column with a double-precision vector is rejected rather than
+/// silently losing precision.
+/// Returns 0 on success, -1 on failure.
+#[no_mangle]
+pub extern "C" fn lancedb_query_nearest_to_f64(
+    handle: *mut QueryHandle,
+    vector: *const c_double,
+    vector_len: c_int,
+) -> c_int {
+    if handle.is_null() || vector.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle and vector cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+    if vector_len <= 0 {
+        crate::set_last_error(&crate::error::Error::InvalidArgument {
+            message: "vector_len must be positive".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &mut *handle };
+
+    let vector_slice = unsafe { std::slice::from_raw_parts(vector, vector_len as usize) };
+    let vector_vec = vector_slice.to_vec();
+
+    match query.nearest_to_f64(vector_vec) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Set the distance metric for the query.
+/// distance_type: 0 = L2, 1 = Cosine, 2 = Dot
+/// Returns 0 on success, -1 on failure.
+#[no_mangle]
+pub extern "C" fn lancedb_query_distance_type(
+    handle: *mut QueryHandle,
+    distance_type: c_int,
+) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &mut *handle };
+
+    let dist_type = match distance_type {
+        0 => DistanceType::L2,
+        1 => DistanceType::Cosine,
+        2 => DistanceType::Dot,
+        _ => {
+            crate::set_last_error(&crate::error::Error::InvalidArgument {
+                message: "invalid distance type: must be 0 (L2), 1 (Cosine), or 2 (Dot)"
+                    .to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+            return -1;
+        }
+    };
+
+    match query.distance_type(dist_type) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Set the number of IVF partitions to probe during a vector search.
+/// Returns 0 on success, -1 on failure.
+#[no_mangle]
+pub extern "C" fn lancedb_query_nprobes(handle: *mut QueryHandle, nprobes: c_int) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+    if nprobes <= 0 {
+        crate::set_last_error(&crate::error::Error::InvalidArgument {
+            message: "nprobes must be positive".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &mut *handle };
+
+    match query.nprobes(nprobes as usize) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Search for the rows nearest to `text`, embedding it with the function of
+/// the table's embedding column (see lancedb_table_add_embedding_column).
+/// Returns 0 on success, -1 on failure.
+#[no_mangle]
+pub extern "C" fn lancedb_query_nearest_to_text(
+    handle: *mut QueryHandle,
+    text: *const c_char,
+) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &mut *handle };
+    let text = match unsafe { crate::c_str_arg(text, "text") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match query.nearest_to_text(text) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Set the HNSW candidate list size (`ef`) for a vector search.
+/// Returns 0 on success, -1 on failure.
+///
+/// Only affects searches served by an HNSW index (IVF_HNSW_SQ or
+/// IVF_HNSW_PQ); `nprobes` still controls the IVF partitions searched.
+#[no_mangle]
+pub extern "C" fn lancedb_query_ef(handle: *mut QueryHandle, ef: c_int) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+    if ef <= 0 {
+        crate::set_last_error(&crate::error::Error::InvalidArgument {
+            message: "ef must be positive".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &mut *handle };
+
+    match query.ef(ef as usize) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Choose the vector column to search, by name.
+/// Returns 0 on success, -1 on failure.
+///
+/// Required when the table has more than one vector column; a table with a
+/// single vector column searches it by default.
+#[no_mangle]
+pub extern "C" fn lancedb_query_vector_column(
+    handle: *mut QueryHandle,
+    column: *const c_char,
+) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &mut *handle };
+
+    let column = match unsafe { crate::c_str_arg(column, "column") } {
+        Ok(c) => c,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match query.column(column) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Keep only vector search results whose distance lies in `[lower, upper)`.
+/// Returns 0 on success, -1 on failure.
+///
+/// A bound is ignored unless its `has_lower` / `has_upper` flag is set. The
+/// query must have a limit: the range filters the `limit` nearest rows after
+/// `offset`, so a page can hold fewer than `limit` rows, or none when a lower
+/// bound excludes all of them.
+#[no_mangle]
+pub extern "C" fn lancedb_query_distance_range(
+    handle: *mut QueryHandle,
+    lower: c_float,
+    upper: c_float,
+    has_lower: bool,
+    has_upper: bool,
+) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &mut *handle };
+    let lower = has_lower.then_some(lower);
+    let upper = has_upper.then_some(upper);
+
+    match query.distance_range(lower, upper) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Make a vector search compute exact distances against every row instead of
+/// using the vector index. Returns 0 on success, -1 on failure.
+///
+/// Useful for small tables and for measuring an index's recall. nprobes and
+/// ef have no effect on a bypassed search.
+#[no_mangle]
+pub extern "C" fn lancedb_query_bypass_vector_index(handle: *mut QueryHandle) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &mut *handle };
+
+    match query.bypass_vector_index() {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Make a vector search use only the rows covered by the vector index,
+/// skipping the flat scan of unindexed data for lower latency.
+/// Returns 0 on success, -1 on failure.
+///
+/// Results may exclude rows added since the index was last built or
+/// optimized. Fails if the searched column has no vector index.
+#[no_mangle]
+pub extern "C" fn lancedb_query_fast_search(handle: *mut QueryHandle) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &mut *handle };
+
+    match query.fast_search() {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Apply the filter of a vector search after the search instead of before
+/// it. Returns 0 on success, -1 on failure.
+///
+/// Prefiltering is the default. With `enable` set, only the nearest `limit`
+/// rows are filtered, so fewer than `limit` rows may be returned.
+#[no_mangle]
+pub extern "C" fn lancedb_query_postfilter(handle: *mut QueryHandle, enable: bool) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &mut *handle };
+
+    match query.postfilter(enable) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Set the maximum number of results to return.
+/// Returns 0 on success, -1 on failure.
+///
+/// A plain query with a limit of 0 returns no rows. Vector searches reject a
+/// limit of 0 and return 10 rows when no limit is set.
+#[no_mangle]
+pub extern "C" fn lancedb_query_limit(handle: *mut QueryHandle, limit: c_int) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+    if limit < 0 {
+        crate::set_last_error(&crate::error::Error::InvalidArgument {
+            message: "limit must be non-negative".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &mut *handle };
+
+    match query.limit(limit as usize) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Set the offset for query results.
+/// Returns 0 on success, -1 on failure.
+///
+/// A plain query skips `offset` rows of its scan (or of its ordered
+/// results). A vector search finds the `limit + offset` nearest rows and
+/// skips the nearest `offset`, so successive offsets page through the
+/// neighbours in distance order.
+#[no_mangle]
+pub extern "C" fn lancedb_query_offset(handle: *mut QueryHandle, offset: c_int) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+    if offset < 0 {
+        crate::set_last_error(&crate::error::Error::InvalidArgument {
+            message: "offset must be non-negative".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &mut *handle };
+
+    match query.offset(offset as usize) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Fail the query with a TimedOut error (code ERROR_TIMED_OUT) when an
+/// execution takes longer than `millis` milliseconds.
+/// Returns 0 on success, -1 on failure.
+///
+/// The timeout bounds a whole execution rather than each batch: all of
+/// lancedb_query_execute, or the opening of a stream by
+/// lancedb_query_execute_stream. Batches read later with
+/// lancedb_stream_next are not bounded; use lancedb_stream_cancel for them.
+#[no_mangle]
+pub extern "C" fn lancedb_query_timeout(handle: *mut QueryHandle, millis: u64) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+    if millis == 0 {
+        crate::set_last_error(&crate::error::Error::InvalidArgument {
+            message: "millis must be positive".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &mut *handle };
+
+    match query.timeout(millis) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Set the maximum number of rows in each result batch.
+/// Returns 0 on success, -1 on failure.
+///
+/// Applies equally to lancedb_query_execute and the streaming functions.
+/// Smaller batches reduce peak memory, including the exported arrays;
+/// larger batches improve throughput. Results that are sorted in memory
+/// (lancedb_query_order_by_desc) still come back as a single batch.
+#[no_mangle]
+pub extern "C" fn lancedb_query_batch_size(handle: *mut QueryHandle, size: c_int) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+    if size <= 0 {
+        crate::set_last_error(&crate::error::Error::InvalidArgument {
+            message: "size must be positive".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &mut *handle };
+
+    match query.with_batch_size(size as usize) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Sort the query results by a column in descending order, nulls last.
+/// Returns 0 on success, -1 on failure.
+///
+/// Combined with lancedb_query_limit this is a top-N read: rows are scanned
+/// in batches and only the current top `limit + offset` rows are kept, so
+/// memory stays bounded and no full sort is performed. Every row matching
+/// the filter is still scanned; a scalar index on the column speeds up
+/// filters on it but is not used to read rows in order. The column must be
+/// among the selected columns, and vector searches cannot be ordered.
+#[no_mangle]
+pub extern "C" fn lancedb_query_order_by_desc(
+    handle: *mut QueryHandle,
+    column: *const c_char,
+) -> c_int {
+    if handle.is_null() || column.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle and column cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &mut *handle };
+    let column_str = match unsafe { crate::c_str_arg(column, "column") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match query.order_by_desc(column_str) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Search a text column with its full-text (FTS) index.
+/// Returns 0 on success, -1 on failure (including when the column has no FTS index).
+///
+/// Composes with lancedb_query_filter, lancedb_query_select,
+/// lancedb_query_limit, and lancedb_query_offset, but not with a vector search.
+#[no_mangle]
+pub extern "C" fn lancedb_query_full_text_search(
+    handle: *mut QueryHandle,
+    column: *const c_char,
+    query: *const c_char,
+) -> c_int {
+    if handle.is_null() || column.is_null() || query.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle, column, and query cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query_handle = unsafe { &mut *handle };
+    let column_str = match unsafe { crate::c_str_arg(column, "column") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+    let query_str = match unsafe { crate::c_str_arg(query, "query") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match query_handle.full_text_search(column_str, query_str) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Combine a full-text search for `text` and a vector search for `vector`
+/// into one hybrid search, reranking the two result sets together
+/// (reciprocal rank fusion by default).
+/// Returns 0 on success, -1 on failure.
+///
+/// The table must have exactly one FTS index and one vector index. Results
+/// carry `_rowid` and a Float32 `_relevance_score` column, best first; the
+/// filter, selection, limit (10 by default), and offset apply as usual.
+/// Ordering, distance ranges, dropping `_distance`, postfiltering and
+/// batch sizes are rejected, here or when the query runs.
+#[no_mangle]
+pub extern "C" fn lancedb_query_hybrid(
+    handle: *mut QueryHandle,
+    text: *const c_char,
+    vector: *const c_float,
+    vector_len: c_int,
+) -> c_int {
+    if handle.is_null() || vector.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle and vector cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+    if vector_len <= 0 {
+        crate::set_last_error(&crate::error::Error::InvalidArgument {
+            message: "vector_len must be positive".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &mut *handle };
+    let text = match unsafe { crate::c_str_arg(text, "text") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+    let vector = unsafe { std::slice::from_raw_parts(vector, vector_len as usize) }.to_vec();
+
+    match query.hybrid(text, vector) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Choose how a hybrid search combines its two result sets.
+/// Returns 0 on success, -1 on failure.
+///
+/// `kind` is 0 for reciprocal rank fusion, configured by `{"k": 60}`; 1 for
+/// a linear combination of the scaled vector and text scores, configured by
+/// `{"weight": 0.7}`, the vector's share; or 2 for a callback registered
+/// with lancedb_register_reranker, configured by
+/// `{"name": "...", "column": "..."}`. The callback reorders the fused
+/// candidates using the text in `column`, the FTS column by default, which
+/// the query must select. `params_json` may be null to use the defaults
+/// (except for the callback's name).
+#[no_mangle]
+pub extern "C" fn lancedb_query_reranker(
+    handle: *mut QueryHandle,
+    kind: c_int,
+    params_json: *const c_char,
+) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &mut *handle };
+    let params = if params_json.is_null() {
+        None
+    } else {
+        match unsafe { crate::c_str_arg(params_json, "params_json") } {
+            Ok(s) => Some(s),
+            Err(err) => {
+                crate::set_last_error(&err);
+                return -1;
+            }
+        }
+    };
+
+    match Reranker::from_kind(kind, params) {
+        Ok(reranker) => {
+            query.set_reranker(reranker);
+            0
+        }
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Include the `_rowid` column (UInt64) in the query results.
+/// Returns 0 on success, -1 on failure.
+#[no_mangle]
+pub extern "C" fn lancedb_query_with_row_id(handle: *mut QueryHandle) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &mut *handle };
+
+    match query.with_row_id() {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Choose whether a vector search returns the `_distance` column (Float32).
+/// Returns 0 on success, -1 on failure.
+///
+/// `_distance` is returned by default, even when lancedb_query_select does
+/// not list it; pass `include = false` to drop it.
+#[no_mangle]
+pub extern "C" fn lancedb_query_with_distance(handle: *mut QueryHandle, include: bool) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &mut *handle };
+
+    match query.with_distance(include) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Set a filter predicate for the query.
+/// Returns 0 on success, -1 on failure.
+#[no_mangle]
+pub extern "C" fn lancedb_query_filter(handle: *mut QueryHandle, filter: *const c_char) -> c_int {
+    if handle.is_null() || filter.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle and filter cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &mut *handle };
+    let filter_str = match unsafe { crate::c_str_arg(filter, "filter") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match query.filter(filter_str) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Filter on a timestamp column without formatting the bound as a string.
+/// Returns 0 on success, -1 on failure (including when the column is not a
+/// timestamp).
+///
+/// op: 0 (=), 1 (>), 2 (>=), 3 (<), or 4 (<=), comparing the column with
+/// `micros_since_epoch` microseconds since the Unix epoch in UTC. This
+/// replaces any filter set with lancedb_query_filter.
+#[no_mangle]
+pub extern "C" fn lancedb_query_filter_timestamp(
+    handle: *mut QueryHandle,
+    column: *const c_char,
+    op: c_int,
+    micros_since_epoch: i64,
+) -> c_int {
+    if handle.is_null() || column.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle and column cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &mut *handle };
+    let column_str = match unsafe { crate::c_str_arg(column, "column") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match query.filter_timestamp(column_str, op, micros_since_epoch) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Set the columns to select in the query results.
+/// columns is a pointer to an array of C strings.
+/// Returns 0 on success, -1 on failure.
+#[no_mangle]
+pub extern "C" fn lancedb_query_select(
+    handle: *mut QueryHandle,
+    columns: *const *const c_char,
     columns_len: c_int,
 ) -> c_int {
-    if handle.is_null() || columns.is_null() || columns_len <= 0 {
-        let error_msg = "handle, columns cannot be null and columns_len must be positive";
-        let c_error = CString::new(error_msg).unwrap();
-        crate::lancedb_set_last_error(c_error.as_ptr());
+    if handle.is_null() || columns.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle and columns cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+    if columns_len <= 0 {
+        crate::set_last_error(&crate::error::Error::InvalidArgument {
+            message: "columns_len must be positive".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
         return -1;
     }
 
@@ -342,18 +2368,16 @@ pub extern "C" fn lancedb_query_select(
     let mut column_names = Vec::new();
     for &col_ptr in columns_slice {
         if col_ptr.is_null() {
-            let error_msg = "column name cannot be null";
-            let c_error = CString::new(error_msg).unwrap();
-            crate::lancedb_set_last_error(c_error.as_ptr());
+            crate::set_last_error(&crate::error::Error::NullPointer {
+                message: "column name cannot be null".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
             return -1;
         }
-        let c_str = unsafe { CStr::from_ptr(col_ptr) };
-        let col_name = match c_str.to_str() {
+        let col_name = match unsafe { crate::c_str_arg(col_ptr, "column name") } {
             Ok(s) => s.to_string(),
             Err(err) => {
-                let error_msg = format!("invalid UTF-8 in column name: {}", err);
-                let c_error = CString::new(error_msg).unwrap();
-                crate::lancedb_set_last_error(c_error.as_ptr());
+                crate::set_last_error(&err);
                 return -1;
             }
         };
@@ -363,16 +2387,250 @@ pub extern "C" fn lancedb_query_select(
     match query.select(column_names) {
         Ok(_) => 0,
         Err(err) => {
-            let error_msg = format!("{}", err);
-            let c_error = CString::new(error_msg).unwrap();
-            crate::lancedb_set_last_error(c_error.as_ptr());
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Return every table column again after lancedb_query_select or
+/// lancedb_query_select_expr narrowed the results.
+/// Returns 0 on success, -1 on failure.
+///
+/// `_distance` and `_rowid` keep following lancedb_query_with_distance and
+/// lancedb_query_with_row_id.
+#[no_mangle]
+pub extern "C" fn lancedb_query_select_all(handle: *mut QueryHandle) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &mut *handle };
+    query.select_all();
+    0
+}
+
+/// Select computed columns in the query results.
+/// aliases and expressions are parallel arrays of n C strings; each
+/// expression is SQL over the table's columns (e.g. "price * 1.1") and its
+/// result is returned under the matching alias.
+/// Returns 0 on success, -1 on failure (including a malformed expression).
+#[no_mangle]
+pub extern "C" fn lancedb_query_select_expr(
+    handle: *mut QueryHandle,
+    aliases: *const *const c_char,
+    expressions: *const *const c_char,
+    n: c_int,
+) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &mut *handle };
+
+    let aliases = match unsafe { crate::c_str_array_arg(aliases, n, "aliases") } {
+        Ok(aliases) => aliases,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+    let expressions = match unsafe { crate::c_str_array_arg(expressions, n, "expressions") } {
+        Ok(expressions) => expressions,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match query.select_expr(aliases.into_iter().zip(expressions).collect()) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Apply several query options in a single call.
+/// Returns 0 on success, -1 on failure.
+///
+/// `config_json` is a JSON object with any of the keys `vector` (array of
+/// floats), `distance_type` (0 = L2, 1 = Cosine, 2 = Dot), `nprobes`,
+/// `filter`, `select` (array of column names), `limit`, and `offset`.
+/// Unknown keys are rejected. Options are applied in that order, so a
+/// failure part-way through may leave earlier options applied.
+#[no_mangle]
+pub extern "C" fn lancedb_query_configure(
+    handle: *mut QueryHandle,
+    config_json: *const c_char,
+) -> c_int {
+    if handle.is_null() || config_json.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle and config_json cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &mut *handle };
+    let json_str = match unsafe { crate::c_str_arg(config_json, "config_json") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    let config: QueryConfig = match serde_json::from_str(json_str) {
+        Ok(c) => c,
+        Err(err) => {
+            crate::set_last_error_with_context("invalid query config", &err.into());
+            return -1;
+        }
+    };
+
+    match query.configure(config) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Get the planner's estimate of how many rows the query will return.
+/// The query is planned but not executed.
+/// Returns the estimate on success, -1 on failure or when no estimate is available
+/// (a NotSupported last error).
+#[no_mangle]
+pub extern "C" fn lancedb_query_estimated_rows(handle: *const QueryHandle) -> i64 {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &*handle };
+
+    match query.estimated_rows() {
+        Ok(Some(rows)) => rows as i64,
+        Ok(None) => {
+            crate::set_last_error(&crate::error::Error::NotSupported {
+                message: "the query plan does not provide a row count estimate".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+            -1
+        }
+        Err(err) => {
+            crate::set_last_error(&err);
             -1
         }
     }
 }
 
+/// Get the physical plan of the query as text, without executing it.
+/// Columns chosen with lancedb_query_select are pushed down into the scan, so
+/// the plan's scan node lists only those columns in its projection.
+/// Returns 0 on success, -1 on failure.
+/// plan_out must be freed with lancedb_free_string.
+#[no_mangle]
+pub extern "C" fn lancedb_query_explain_plan(
+    handle: *const QueryHandle,
+    verbose: bool,
+    plan_out: *mut *mut c_char,
+) -> c_int {
+    if handle.is_null() || plan_out.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle and plan_out cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &*handle };
+
+    let plan = match query.explain_plan(verbose) {
+        Ok(p) => p,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    let c_string = match CString::new(plan) {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err.into());
+            return -1;
+        }
+    };
+
+    unsafe {
+        *plan_out = c_string.into_raw();
+    }
+
+    0
+}
+
+/// Run a query and get its physical plan annotated with runtime metrics,
+/// such as rows produced and time spent per operator.
+/// Returns 0 on success, -1 on failure.
+///
+/// This executes the query in full and discards the results. If execution
+/// fails, -1 is returned and plan_out is left unchanged.
+/// plan_out must be freed with lancedb_free_string.
+#[no_mangle]
+pub extern "C" fn lancedb_query_analyze_plan(
+    handle: *const QueryHandle,
+    plan_out: *mut *mut c_char,
+) -> c_int {
+    if handle.is_null() || plan_out.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle and plan_out cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &*handle };
+
+    let plan = match query.analyze_plan() {
+        Ok(p) => p,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    let c_string = match CString::new(plan) {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err.into());
+            return -1;
+        }
+    };
+
+    unsafe {
+        *plan_out = c_string.into_raw();
+    }
+
+    0
+}
+
 /// Execute the query and return results as Arrow C Data Interface structures.
 /// Returns 0 on success, -1 on failure.
+/// Free the results with lancedb_free_batch_arrays.
 #[no_mangle]
 pub extern "C" fn lancedb_query_execute(
     handle: *const QueryHandle,
@@ -381,9 +2639,10 @@ pub extern "C" fn lancedb_query_execute(
     count_out: *mut c_int,
 ) -> c_int {
     if handle.is_null() || arrays_out.is_null() || schemas_out.is_null() || count_out.is_null() {
-        let error_msg = "handle, arrays_out, schemas_out, and count_out cannot be null";
-        let c_error = CString::new(error_msg).unwrap();
-        crate::lancedb_set_last_error(c_error.as_ptr());
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle, arrays_out, schemas_out, and count_out cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
         return -1;
     }
 
@@ -393,73 +2652,94 @@ pub extern "C" fn lancedb_query_execute(
     let batches = match query.execute() {
         Ok(b) => b,
         Err(err) => {
-            let error_msg = format!("{}", err);
-            let c_error = CString::new(error_msg).unwrap();
-            crate::lancedb_set_last_error(c_error.as_ptr());
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match unsafe {
+        crate::arrow_ffi::export_batches_to_c(&batches, arrays_out, schemas_out, count_out)
+    } {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Execute the query and write the results to `path` as newline-delimited
+/// JSON, one object per row. Only the selected columns are written; list and
+/// struct columns become JSON arrays and objects, and nulls are written as
+/// `null`. An existing file at `path` is overwritten.
+/// Returns the number of rows written on success, -1 on failure.
+#[no_mangle]
+pub extern "C" fn lancedb_query_execute_ndjson(
+    handle: *const QueryHandle,
+    path: *const c_char,
+) -> i64 {
+    if handle.is_null() || path.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle and path cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let query = unsafe { &*handle };
+    let path_str = match unsafe { crate::c_str_arg(path, "path") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
             return -1;
         }
     };
 
-    let num_batches = batches.len();
-
-    if num_batches == 0 {
-        // No results
-        unsafe {
-            *arrays_out = std::ptr::null_mut();
-            *schemas_out = std::ptr::null_mut();
-            *count_out = 0;
+    match query.execute_ndjson(path_str) {
+        Ok(rows) => rows as i64,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
         }
-        return 0;
     }
+}
 
-    // Allocate arrays for Arrow C structures
-    let arrays_size = num_batches * std::mem::size_of::<FFI_ArrowArray>();
-    let schemas_size = num_batches * std::mem::size_of::<FFI_ArrowSchema>();
-
-    let arrays_ptr = unsafe { libc::malloc(arrays_size) as *mut FFI_ArrowArray };
-    let schemas_ptr = unsafe { libc::malloc(schemas_size) as *mut FFI_ArrowSchema };
-
-    if arrays_ptr.is_null() || schemas_ptr.is_null() {
-        if !arrays_ptr.is_null() {
-            unsafe { libc::free(arrays_ptr as *mut libc::c_void) };
-        }
-        if !schemas_ptr.is_null() {
-            unsafe { libc::free(schemas_ptr as *mut libc::c_void) };
-        }
-        let error_msg = "failed to allocate memory for output arrays";
-        let c_error = CString::new(error_msg).unwrap();
-        crate::lancedb_set_last_error(c_error.as_ptr());
+/// Execute the query and export the results as an Arrow C stream
+/// (ArrowArrayStream). Returns 0 on success, -1 on failure.
+///
+/// Batches are produced lazily: each `get_next` call on the stream pulls
+/// the next batch from the query, so the full result is never buffered.
+/// Ordered queries are the exception: their rows are sorted before the
+/// first batch is returned, holding up to limit + offset rows, or every
+/// matching row when there is no limit.
+/// `stream_out` must point to uninitialized memory for one ArrowArrayStream;
+/// the caller owns the stream and must call its `release` callback. The
+/// stream stays valid after the query handle is freed.
+#[no_mangle]
+pub extern "C" fn lancedb_query_execute_arrow_stream(
+    handle: *const QueryHandle,
+    stream_out: *mut FFI_ArrowArrayStream,
+) -> c_int {
+    if handle.is_null() || stream_out.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle and stream_out cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
         return -1;
     }
 
-    // Export each batch
-    for (i, batch) in batches.iter().enumerate() {
-        let array_ptr = unsafe { arrays_ptr.add(i) };
-        let schema_ptr = unsafe { schemas_ptr.add(i) };
+    let query = unsafe { &*handle };
 
-        if let Err(err) = unsafe { export_record_batch_to_c(batch, array_ptr, schema_ptr) } {
-            // Clean up on error
-            for j in 0..i {
-                unsafe {
-                    crate::arrow_ffi::lancedb_arrow_array_release(arrays_ptr.add(j));
-                    crate::arrow_ffi::lancedb_arrow_schema_release(schemas_ptr.add(j));
-                }
-            }
-            unsafe {
-                libc::free(arrays_ptr as *mut libc::c_void);
-                libc::free(schemas_ptr as *mut libc::c_void);
-            }
-            let error_msg = format!("{}", err);
-            let c_error = CString::new(error_msg).unwrap();
-            crate::lancedb_set_last_error(c_error.as_ptr());
+    let reader = match query.execute_reader() {
+        Ok(r) => r,
+        Err(err) => {
+            crate::set_last_error(&err);
             return -1;
         }
-    }
+    };
 
     unsafe {
-        *arrays_out = arrays_ptr;
-        *schemas_out = schemas_ptr;
-        *count_out = num_batches as c_int;
+        std::ptr::write(stream_out, FFI_ArrowArrayStream::new(Box::new(reader)));
     }
 
     0
@@ -470,9 +2750,10 @@ pub extern "C" fn lancedb_query_execute(
 #[no_mangle]
 pub extern "C" fn lancedb_query_execute_stream(handle: *const QueryHandle) -> *mut QueryStreamHandle {
     if handle.is_null() {
-        let error_msg = "handle cannot be null";
-        let c_error = CString::new(error_msg).unwrap();
-        crate::lancedb_set_last_error(c_error.as_ptr());
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
         return std::ptr::null_mut();
     }
 
@@ -481,19 +2762,21 @@ pub extern "C" fn lancedb_query_execute_stream(handle: *const QueryHandle) -> *m
     let stream = match query.execute_stream() {
         Ok(s) => s,
         Err(err) => {
-            let error_msg = format!("{}", err);
-            let c_error = CString::new(error_msg).unwrap();
-            crate::lancedb_set_last_error(c_error.as_ptr());
+            crate::set_last_error(&err);
             return std::ptr::null_mut();
         }
     };
 
-    let handle = QueryStreamHandle { stream };
+    let handle = QueryStreamHandle {
+        stream,
+        cancel: StreamCancel::default(),
+    };
     Box::into_raw(Box::new(handle))
 }
 
 /// Get the next batch from the stream.
-/// Returns 1 if a batch was returned, 0 if stream ended, -1 on error.
+/// Returns 1 if a batch was returned, 0 if stream ended, -1 on error, and
+/// -2 (STREAM_CANCELLED) once the stream has been cancelled.
 #[no_mangle]
 pub extern "C" fn lancedb_stream_next(
     handle: *mut QueryStreamHandle,
@@ -501,36 +2784,72 @@ pub extern "C" fn lancedb_stream_next(
     schema_out: *mut FFI_ArrowSchema,
 ) -> c_int {
     if handle.is_null() || array_out.is_null() || schema_out.is_null() {
-        let error_msg = "handle, array_out, and schema_out cannot be null";
-        let c_error = CString::new(error_msg).unwrap();
-        crate::lancedb_set_last_error(c_error.as_ptr());
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle, array_out, and schema_out cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
         return -1;
     }
 
-    let stream_handle = unsafe { &mut *handle };
+    // lancedb_stream_cancel may read `cancel` concurrently, so borrow the
+    // fields separately rather than the whole handle mutably
+    let stream = unsafe { &mut (*handle).stream };
+    let cancel = unsafe { &(*handle).cancel };
+
+    if cancel.is_cancelled() {
+        *stream = futures::stream::empty().boxed();
+        return STREAM_CANCELLED;
+    }
 
-    let next_item = RT.block_on(async { stream_handle.stream.next().await });
+    let next_item = RT.block_on(async {
+        let cancelled = std::pin::pin!(cancel.notify.notified());
+        match futures::future::select(stream.next(), cancelled).await {
+            Either::Left((item, _)) => Some(item),
+            Either::Right(_) => None,
+        }
+    });
+    let Some(next_item) = next_item else {
+        *stream = futures::stream::empty().boxed();
+        return STREAM_CANCELLED;
+    };
 
     match next_item {
         Some(Ok(batch)) => {
             if let Err(err) = unsafe { export_record_batch_to_c(&batch, array_out, schema_out) } {
-                let error_msg = format!("{}", err);
-                let c_error = CString::new(error_msg).unwrap();
-                crate::lancedb_set_last_error(c_error.as_ptr());
+                crate::set_last_error(&err);
                 return -1;
             }
             1
         }
         Some(Err(err)) => {
-            let error_msg = format!("{}", err);
-            let c_error = CString::new(error_msg).unwrap();
-            crate::lancedb_set_last_error(c_error.as_ptr());
+            crate::set_last_error(&err.into());
             -1
         }
         None => 0,
     }
 }
 
+/// Cancel a stream. Returns 0 on success, -1 on failure.
+///
+/// May be called from another thread while lancedb_stream_next is blocked:
+/// that call, and every later one, returns -2 (STREAM_CANCELLED) and no
+/// further batches are produced. The stream must still be closed with
+/// lancedb_stream_close.
+#[no_mangle]
+pub extern "C" fn lancedb_stream_cancel(handle: *mut QueryStreamHandle) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "stream handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let cancel = unsafe { &(*handle).cancel };
+    cancel.cancel();
+    0
+}
+
 /// Close the stream and free resources.
 #[no_mangle]
 pub extern "C" fn lancedb_stream_close(handle: *mut QueryStreamHandle) {
@@ -540,3 +2859,1371 @@ pub extern "C" fn lancedb_stream_close(handle: *mut QueryStreamHandle) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::table::TableHandle;
+    use crate::test_util::{temp_connection, vector_batch, vector_schema, vector_table};
+    use arrow_array::{Array, FixedSizeListArray, Float32Array, Int32Array, StringArray};
+    use lancedb::table::AddDataMode;
+
+    /// The line of a plan describing the scan of the table
+    fn scan_line(plan: &str) -> &str {
+        plan.lines()
+            .find(|line| line.contains("LanceScan"))
+            .expect("plan should contain a LanceScan node")
+    }
+
+    #[test]
+    fn test_select_is_pushed_down_into_scan() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "pushdown", 10, 4);
+
+        let mut query = QueryHandle::new(table.inner.clone());
+        let full_plan = query.explain_plan(false).unwrap();
+        assert!(scan_line(&full_plan).contains("vector"));
+
+        query.select(vec!["id".to_string()]).unwrap();
+        let plan = query.explain_plan(false).unwrap();
+        let scan = scan_line(&plan);
+        assert!(
+            scan.contains("projection=[id]"),
+            "unexpected scan: {}",
+            scan
+        );
+        assert!(!scan.contains("vector"), "unexpected scan: {}", scan);
+        assert!(!scan.contains("text"), "unexpected scan: {}", scan);
+    }
+
+    #[test]
+    fn test_select_all_after_select() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "reselect", 10, 2);
+        let column_names = |batches: &[RecordBatch]| -> Vec<String> {
+            let schema = batches[0].schema();
+            schema.fields().iter().map(|f| f.name().clone()).collect()
+        };
+
+        let mut query = QueryHandle::new(table.inner.clone());
+        query.select(vec!["id".to_string()]).unwrap();
+        assert_eq!(column_names(&query.execute().unwrap()), vec!["id"]);
+        assert_eq!(lancedb_query_select_all(&mut query), 0);
+        assert_eq!(
+            column_names(&query.execute().unwrap()),
+            vec!["id", "text", "vector"]
+        );
+
+        // The extra vector search columns still follow their own flags
+        let mut query = QueryHandle::new(table.inner.clone());
+        query.nearest_to(vec![1.0, 1.0]).unwrap();
+        query.with_row_id().unwrap();
+        query.select(vec!["id".to_string()]).unwrap();
+        assert_eq!(lancedb_query_select_all(&mut query), 0);
+        let names = column_names(&query.execute().unwrap());
+        for name in ["id", "text", "vector", "_distance", "_rowid"] {
+            assert!(names.iter().any(|n| n == name), "{:?}", names);
+        }
+
+        query.with_distance(false).unwrap();
+        let names = column_names(&query.execute().unwrap());
+        assert!(!names.iter().any(|n| n == "_distance"), "{:?}", names);
+
+        assert_eq!(lancedb_query_select_all(std::ptr::null_mut()), -1);
+    }
+
+    #[test]
+    fn test_select_expr() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "priced", 10, 2);
+        let mut query = QueryHandle::new(table.inner.clone());
+
+        let aliases = [
+            CString::new("id").unwrap(),
+            CString::new("id_times_ten").unwrap(),
+        ];
+        let exprs = [
+            CString::new("id").unwrap(),
+            CString::new("id * 10").unwrap(),
+        ];
+        let alias_ptrs: Vec<*const c_char> = aliases.iter().map(|s| s.as_ptr()).collect();
+        let expr_ptrs: Vec<*const c_char> = exprs.iter().map(|s| s.as_ptr()).collect();
+        assert_eq!(
+            lancedb_query_select_expr(&mut query, alias_ptrs.as_ptr(), expr_ptrs.as_ptr(), 2),
+            0
+        );
+
+        let batches = query.execute().unwrap();
+        let schema = batches[0].schema();
+        let names: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(names, vec!["id", "id_times_ten"]);
+        let rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+        assert_eq!(rows, 10);
+
+        // A malformed expression is reported and leaves the selection as it was
+        let bad = CString::new("id * * 2").unwrap();
+        assert_eq!(
+            lancedb_query_select_expr(&mut query, alias_ptrs.as_ptr(), [bad.as_ptr()].as_ptr(), 1),
+            -1
+        );
+        assert!(!crate::lancedb_get_last_error().is_null());
+        let batches = query.execute().unwrap();
+        assert_eq!(batches[0].num_columns(), 2);
+    }
+
+    #[test]
+    fn test_batch_size() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "batched", 10, 2);
+        let mut query = QueryHandle::new(table.inner.clone());
+        assert_eq!(lancedb_query_batch_size(&mut query, 0), -1);
+        assert_eq!(lancedb_query_batch_size(&mut query, 2), 0);
+
+        let batches = query.execute().unwrap();
+        assert!(batches.len() > 1);
+        assert!(batches.iter().all(|b| b.num_rows() <= 2));
+        let rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+        assert_eq!(rows, 10);
+    }
+
+    #[test]
+    fn test_timeout() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "timed", 10, 2);
+        let mut query = QueryHandle::new(table.inner.clone());
+        assert_eq!(lancedb_query_timeout(&mut query, 0), -1);
+
+        // A generous timeout lets the query finish
+        assert_eq!(lancedb_query_timeout(&mut query, 60_000), 0);
+        let rows: usize = query.execute().unwrap().iter().map(|b| b.num_rows()).sum();
+        assert_eq!(rows, 10);
+
+        // A deadline that has already passed fails with TimedOut
+        let err = block_on_until(Some(Instant::now()), std::future::pending::<()>()).unwrap_err();
+        assert!(matches!(err, crate::error::Error::TimedOut { .. }));
+        assert_eq!(err.code(), crate::error::ERROR_TIMED_OUT);
+    }
+
+    #[test]
+    fn test_stream_cancel() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "cancelled", 10, 2);
+        let mut query = QueryHandle::new(table.inner.clone());
+        query.with_batch_size(2).unwrap();
+
+        let stream = lancedb_query_execute_stream(&query);
+        assert!(!stream.is_null());
+        let mut array = std::mem::MaybeUninit::<FFI_ArrowArray>::uninit();
+        let mut schema = std::mem::MaybeUninit::<FFI_ArrowSchema>::uninit();
+        assert_eq!(
+            lancedb_stream_next(stream, array.as_mut_ptr(), schema.as_mut_ptr()),
+            1
+        );
+        unsafe {
+            crate::arrow_ffi::lancedb_arrow_array_release(array.as_mut_ptr());
+            crate::arrow_ffi::lancedb_arrow_schema_release(schema.as_mut_ptr());
+        }
+
+        assert_eq!(lancedb_stream_cancel(stream), 0);
+        for _ in 0..2 {
+            assert_eq!(
+                lancedb_stream_next(stream, array.as_mut_ptr(), schema.as_mut_ptr()),
+                STREAM_CANCELLED
+            );
+        }
+        lancedb_stream_close(stream);
+    }
+
+    #[test]
+    fn test_explain_filtered_vector_query() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "explain", 10, 4);
+
+        let mut query = QueryHandle::new(table.inner.clone());
+        query.nearest_to(vec![1.0; 4]).unwrap();
+        query.filter("id > 5").unwrap();
+
+        let mut plan_out: *mut c_char = std::ptr::null_mut();
+        assert_eq!(lancedb_query_explain_plan(&query, true, &mut plan_out), 0);
+        let plan = unsafe { std::ffi::CString::from_raw(plan_out) }
+            .into_string()
+            .unwrap();
+        assert!(!plan.is_empty());
+        // The filter is rendered as `id@0 > 5` by DataFusion and as
+        // `id > Int32(5)` by Lance, depending on where it is applied
+        assert!(
+            plan.contains("> 5") || plan.contains("> Int32(5)"),
+            "unexpected plan: {}",
+            plan
+        );
+    }
+
+    #[test]
+    fn test_analyze_plan_reports_row_counts() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "analyze", 10, 4);
+
+        let mut query = QueryHandle::new(table.inner.clone());
+        query.filter("id > 5").unwrap();
+
+        let mut plan_out: *mut c_char = std::ptr::null_mut();
+        assert_eq!(lancedb_query_analyze_plan(&query, &mut plan_out), 0);
+        let plan = unsafe { std::ffi::CString::from_raw(plan_out) }
+            .into_string()
+            .unwrap();
+        assert!(plan.contains("output_rows=4"), "unexpected plan: {}", plan);
+    }
+
+    /// Cosine distance computed directly from its definition
+    fn reference_cosine_distance(a: &[f32], b: &[f32]) -> f64 {
+        let dot: f64 = a.iter().zip(b).map(|(x, y)| *x as f64 * *y as f64).sum();
+        let norm_a = a.iter().map(|x| (*x as f64).powi(2)).sum::<f64>().sqrt();
+        let norm_b = b.iter().map(|x| (*x as f64).powi(2)).sum::<f64>().sqrt();
+        1.0 - dot / (norm_a * norm_b)
+    }
+
+    #[test]
+    fn test_cosine_on_unnormalized_vectors() {
+        // Magnitudes differ by two orders so that dot-product and cosine
+        // rankings disagree: [10, 0] has the largest dot product with the
+        // query, while [0.1, 0.05] points in exactly the same direction.
+        let vectors: Vec<[f32; 2]> = vec![
+            [10.0, 0.0],
+            [1.0, 1.0],
+            [0.1, 0.05],
+            [0.0, 5.0],
+            [-3.0, 1.0],
+        ];
+        let query_vector = [2.0f32, 1.0];
+
+        let (_dir, conn) = temp_connection();
+        let table = TableHandle::create(&conn, "cosine", vector_schema(2)).unwrap();
+        let ids: Vec<i32> = (0..vectors.len() as i32).collect();
+        let values = Float32Array::from(vectors.iter().flatten().copied().collect::<Vec<_>>());
+        let batch = RecordBatch::try_new(
+            vector_schema(2),
+            vec![
+                Arc::new(Int32Array::from(ids.clone())),
+                Arc::new(StringArray::from(vec![None::<&str>; vectors.len()])),
+                Arc::new(
+                    FixedSizeListArray::try_new(
+                        Arc::new(Field::new("item", DataType::Float32, true)),
+                        2,
+                        Arc::new(values),
+                        None,
+                    )
+                    .unwrap(),
+                ),
+            ],
+        )
+        .unwrap();
+        table.add_data(batch, AddDataMode::Append).unwrap();
+
+        let mut expected: Vec<(i32, f64)> = ids
+            .iter()
+            .map(|id| {
+                let distance = reference_cosine_distance(&vectors[*id as usize], &query_vector);
+                (*id, distance)
+            })
+            .collect();
+        expected.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+
+        let mut query = QueryHandle::new(table.inner.clone());
+        query.nearest_to(query_vector.to_vec()).unwrap();
+        query.distance_type(DistanceType::Cosine).unwrap();
+        query.limit(vectors.len()).unwrap();
+        let batches = query.execute().unwrap();
+
+        let mut actual: Vec<(i32, f64)> = Vec::new();
+        for batch in &batches {
+            let ids = batch
+                .column_by_name("id")
+                .unwrap()
+                .as_any()
+                .downcast_ref::<Int32Array>()
+                .unwrap();
+            let distances = batch
+                .column_by_name("_distance")
+                .unwrap()
+                .as_any()
+                .downcast_ref::<Float32Array>()
+                .unwrap();
+            for i in 0..batch.num_rows() {
+                actual.push((ids.value(i), distances.value(i) as f64));
+            }
+        }
+
+        assert_eq!(actual.len(), expected.len());
+        for ((actual_id, actual_distance), (expected_id, expected_distance)) in
+            actual.iter().zip(&expected)
+        {
+            assert_eq!(actual_id, expected_id, "actual: {:?}", actual);
+            assert!(
+                (actual_distance - expected_distance).abs() < 1e-4,
+                "distance for id {} was {}, expected {}",
+                actual_id,
+                actual_distance,
+                expected_distance
+            );
+        }
+    }
+
+    #[test]
+    fn test_execute_ndjson_honors_select() {
+        let (dir, conn) = temp_connection();
+        let table = vector_table(&conn, "ndjson", 3, 2);
+        let path = dir.path().join("results.ndjson");
+
+        let mut query = QueryHandle::new(table.inner.clone());
+        query
+            .select(vec!["id".to_string(), "vector".to_string()])
+            .unwrap();
+        let rows = query.execute_ndjson(path.to_str().unwrap()).unwrap();
+        assert_eq!(rows, 3);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        for line in &lines {
+            let object = line.as_object().unwrap();
+            assert_eq!(object.len(), 2, "unexpected row: {}", line);
+            let id = object["id"].as_i64().unwrap();
+            assert_eq!(
+                object["vector"],
+                serde_json::json!([id as f64, id as f64]),
+                "unexpected row: {}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn test_order_by_desc_with_limit() {
+        let (_dir, conn) = temp_connection();
+        let table = TableHandle::create(&conn, "recent", vector_schema(2)).unwrap();
+        // Several appends so the top rows are spread across batches
+        for start in [0, 40, 20] {
+            table
+                .add_data(vector_batch(start, 20, 2), AddDataMode::Append)
+                .unwrap();
+        }
+
+        let mut query = QueryHandle::new(table.inner.clone());
+        query.order_by_desc("id").unwrap();
+        query.limit(5).unwrap();
+        query.offset(2).unwrap();
+        let batches = query.execute().unwrap();
+
+        let ids: Vec<i32> = batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column_by_name("id")
+                    .unwrap()
+                    .as_any()
+                    .downcast_ref::<Int32Array>()
+                    .unwrap()
+                    .values()
+                    .to_vec()
+            })
+            .collect();
+        assert_eq!(ids, vec![57, 56, 55, 54, 53]);
+    }
+
+    #[test]
+    fn test_order_by_desc_without_limit() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "unbounded", 30, 2);
+
+        let mut query = QueryHandle::new(table.inner.clone());
+        query.order_by_desc("id").unwrap();
+        query.offset(25).unwrap();
+        assert_eq!(ids(&query.execute().unwrap()), vec![4, 3, 2, 1, 0]);
+
+        // A limit past the end of the table returns every row
+        let mut query = QueryHandle::new(table.inner.clone());
+        query.order_by_desc("id").unwrap();
+        query.limit(100).unwrap();
+        assert_eq!(ids(&query.execute().unwrap()).len(), 30);
+    }
+
+    #[test]
+    fn test_order_by_desc_rejects_vector_search() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "ordered", 10, 2);
+
+        let mut query = QueryHandle::new(table.inner.clone());
+        query.nearest_to(vec![1.0, 1.0]).unwrap();
+        let err = query.order_by_desc("id").unwrap_err();
+        assert!(err.to_string().contains("vector search"));
+    }
+
+    #[test]
+    fn test_filter_timestamp() {
+        let hour: i64 = 3_600_000_000;
+        // 2024-01-01T00:00:00Z plus one hour per row
+        let start: i64 = 1_704_067_200_000_000;
+        let boundary = start + 5 * hour;
+
+        // The bound is an instant, so the rows matched do not depend on the
+        // zone the column is displayed in
+        for timezone in [Some("UTC"), Some("America/New_York"), None] {
+            let (_dir, conn) = temp_connection();
+            let schema = Arc::new(arrow_schema::Schema::new(vec![
+                Field::new("id", DataType::Int32, false),
+                Field::new(
+                    "event`ts",
+                    DataType::Timestamp(
+                        arrow_schema::TimeUnit::Microsecond,
+                        timezone.map(Into::into),
+                    ),
+                    false,
+                ),
+            ]));
+            let times = arrow_array::TimestampMicrosecondArray::from_iter_values(
+                (0..10).map(|i| start + i * hour),
+            )
+            .with_timezone_opt(timezone);
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from_iter_values(0..10)),
+                    Arc::new(times),
+                ],
+            )
+            .unwrap();
+            let table = TableHandle::create(&conn, "events", schema).unwrap();
+            table.add_data(batch, AddDataMode::Append).unwrap();
+
+            let column = CString::new("event`ts").unwrap();
+            for (op, expected) in [
+                (FILTER_OP_EQ, vec![5]),
+                (FILTER_OP_GT, (6..10).collect()),
+                (FILTER_OP_GE, (5..10).collect()),
+                (FILTER_OP_LT, (0..5).collect()),
+                (FILTER_OP_LE, (0..6).collect()),
+            ] {
+                let mut query = QueryHandle::new(table.inner.clone());
+                assert_eq!(
+                    lancedb_query_filter_timestamp(&mut query, column.as_ptr(), op, boundary),
+                    0
+                );
+                let mut found = ids(&query.execute().unwrap());
+                found.sort();
+                assert_eq!(found, expected, "op {} in {:?}", op, timezone);
+            }
+
+            // Sub-second precision is kept
+            let mut query = QueryHandle::new(table.inner.clone());
+            query
+                .filter_timestamp("event`ts", FILTER_OP_GT, boundary - 1)
+                .unwrap();
+            assert_eq!(ids(&query.execute().unwrap()).len(), 5);
+
+            let mut query = QueryHandle::new(table.inner.clone());
+            let err = query
+                .filter_timestamp("id", FILTER_OP_EQ, boundary)
+                .unwrap_err();
+            assert!(err.to_string().contains("not a timestamp"), "{}", err);
+            assert!(query.filter_timestamp("event`ts", 5, boundary).is_err());
+            assert!(query.filter_timestamp("missing", FILTER_OP_EQ, 0).is_err());
+        }
+    }
+
+    #[test]
+    fn test_offset_on_plain_and_vector_queries() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "paging", 20, 2);
+
+        // A plain query pages through the scan
+        let mut plain = QueryHandle::new(table.inner.clone());
+        assert_eq!(lancedb_query_limit(&mut plain, 4), 0);
+        assert_eq!(lancedb_query_offset(&mut plain, 3), 0);
+        assert_eq!(ids(&plain.execute().unwrap()), vec![3, 4, 5, 6]);
+
+        // A vector search pages through the neighbours nearest first
+        let mut vector = QueryHandle::new(table.inner.clone());
+        vector.nearest_to(vec![0.0, 0.0]).unwrap();
+        assert_eq!(lancedb_query_limit(&mut vector, 4), 0);
+        assert_eq!(lancedb_query_offset(&mut vector, 3), 0);
+        assert_eq!(ids(&vector.execute().unwrap()), vec![3, 4, 5, 6]);
+
+        // Without a limit a vector search returns 10 rows past the offset
+        let mut unlimited = QueryHandle::new(table.inner.clone());
+        unlimited.nearest_to(vec![0.0, 0.0]).unwrap();
+        unlimited.offset(5).unwrap();
+        assert_eq!(
+            ids(&unlimited.execute().unwrap()),
+            (5..15).collect::<Vec<i32>>()
+        );
+
+        // A limit of 0 returns nothing from a scan but is an error for a
+        // vector search, whether it is set before or after the vector
+        plain.limit(0).unwrap();
+        assert!(ids(&plain.execute().unwrap()).is_empty());
+        assert_eq!(lancedb_query_limit(&mut vector, 0), -1);
+        let mut early = QueryHandle::new(table.inner.clone());
+        early.limit(0).unwrap();
+        early.nearest_to(vec![0.0, 0.0]).unwrap();
+        assert!(early.execute().is_err());
+    }
+
+    #[test]
+    fn test_nearest_to_dimension_mismatch() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "dims", 3, 4);
+
+        let mut query = QueryHandle::new(table.inner.clone());
+        let err = query.nearest_to(vec![0.0; 3]).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("query vector has 3 dims but column 'vector' has 4"),
+            "unexpected error: {}",
+            err
+        );
+
+        query.nearest_to(vec![0.0; 4]).unwrap();
+    }
+
+    #[test]
+    fn test_ef_on_hnsw_search() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "hnsw", 512, 8);
+        table
+            .create_index(
+                "vector",
+                "IVF_HNSW_SQ",
+                DistanceType::L2,
+                Some(2),
+                None,
+                false,
+                &crate::table::IndexOptions::default(),
+            )
+            .unwrap();
+
+        let mut query = QueryHandle::new(table.inner.clone());
+        assert!(query.ef(50).is_err());
+
+        query.nearest_to(vec![100.0; 8]).unwrap();
+        assert!(query.ef(0).is_err());
+        assert_eq!(lancedb_query_ef(&mut query, 50), 0);
+        query.limit(5).unwrap();
+
+        let batches = query.execute().unwrap();
+        let ids: Vec<i32> = batches
+            .iter()
+            .flat_map(|b| {
+                b.column_by_name("id")
+                    .unwrap()
+                    .as_any()
+                    .downcast_ref::<Int32Array>()
+                    .unwrap()
+                    .values()
+                    .to_vec()
+            })
+            .collect();
+        assert_eq!(ids.len(), 5);
+        assert!(
+            ids.iter().all(|id| (95..=105).contains(id)),
+            "ids: {:?}",
+            ids
+        );
+    }
+
+    fn distances(batches: &[RecordBatch]) -> Vec<f32> {
+        batches
+            .iter()
+            .flat_map(|b| {
+                b.column_by_name("_distance")
+                    .unwrap()
+                    .as_any()
+                    .downcast_ref::<Float32Array>()
+                    .unwrap()
+                    .values()
+                    .to_vec()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_vector_column_on_multi_vector_table() {
+        let (_dir, conn) = temp_connection();
+        let item = Arc::new(Field::new("item", DataType::Float32, true));
+        let vector_type = DataType::FixedSizeList(item.clone(), 2);
+        let schema = Arc::new(arrow_schema::Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("vec_a", vector_type.clone(), true),
+            Field::new("vec_b", vector_type, true),
+        ]));
+        // vec_a grows with the id and vec_b shrinks, so [0, 0] is nearest to
+        // row 0 in vec_a and to row 9 in vec_b
+        let ids: Vec<i32> = (0..10).collect();
+        let vectors = |value: fn(i32) -> f32| {
+            let values: Vec<f32> = ids.iter().flat_map(|&id| [value(id); 2]).collect();
+            FixedSizeListArray::try_new(item.clone(), 2, Arc::new(Float32Array::from(values)), None)
+                .unwrap()
+        };
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(ids.clone())),
+                Arc::new(vectors(|id| id as f32)),
+                Arc::new(vectors(|id| (9 - id) as f32)),
+            ],
+        )
+        .unwrap();
+        let table = TableHandle::create(&conn, "multi", schema).unwrap();
+        table.add_data(batch, AddDataMode::Append).unwrap();
+
+        let top_id = |column: Option<&str>| -> Result<i32> {
+            let mut query = QueryHandle::new(table.inner.clone());
+            query.nearest_to(vec![0.0, 0.0])?;
+            if let Some(column) = column {
+                let column = CString::new(column).unwrap();
+                assert_eq!(lancedb_query_vector_column(&mut query, column.as_ptr()), 0);
+            }
+            query.limit(1)?;
+            let batches = query.execute()?;
+            let id = batches[0]
+                .column_by_name("id")
+                .unwrap()
+                .as_any()
+                .downcast_ref::<Int32Array>()
+                .unwrap()
+                .value(0);
+            Ok(id)
+        };
+        assert_eq!(top_id(Some("vec_a")).unwrap(), 0);
+        assert_eq!(top_id(Some("vec_b")).unwrap(), 9);
+
+        let err = top_id(None).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("several vector columns (vec_a, vec_b)"));
+
+        let mut query = QueryHandle::new(table.inner.clone());
+        query.nearest_to(vec![0.0, 0.0]).unwrap();
+        assert!(query.column("id").is_err());
+    }
+
+    #[test]
+    fn test_float16_vector_column() {
+        let (_dir, conn) = temp_connection();
+        let item = Arc::new(Field::new("item", DataType::Float16, true));
+        let schema = Arc::new(arrow_schema::Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("vector", DataType::FixedSizeList(item.clone(), 2), true),
+        ]));
+        let row_ids: Vec<i32> = (0..10).collect();
+        let values: Vec<f32> = row_ids.iter().flat_map(|&id| [id as f32; 2]).collect();
+        let values = cast(&Float32Array::from(values), &DataType::Float16).unwrap();
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(row_ids)),
+                Arc::new(FixedSizeListArray::try_new(item, 2, values, None).unwrap()),
+            ],
+        )
+        .unwrap();
+        let table = TableHandle::create(&conn, "half", schema).unwrap();
+        table.add_data(batch, AddDataMode::Append).unwrap();
+
+        let mut query = QueryHandle::new(table.inner.clone());
+        query.nearest_to(vec![3.0, 3.0]).unwrap();
+        query.limit(3).unwrap();
+        let found = ids(&query.execute().unwrap());
+        assert_eq!(found[0], 3);
+        assert_eq!(found.len(), 3);
+
+        let mut query = QueryHandle::new(table.inner.clone());
+        assert!(query.nearest_to(vec![3.0, 3.0, 3.0]).is_err());
+    }
+
+    #[test]
+    fn test_float64_vector_index() {
+        let (_dir, conn) = temp_connection();
+        let item = Arc::new(Field::new("item", DataType::Float64, true));
+        let schema = Arc::new(arrow_schema::Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("vector", DataType::FixedSizeList(item.clone(), 4), true),
+        ]));
+        let row_ids: Vec<i32> = (0..256).collect();
+        let values: Vec<f64> = row_ids.iter().flat_map(|&id| [id as f64; 4]).collect();
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(row_ids)),
+                Arc::new(
+                    FixedSizeListArray::try_new(
+                        item,
+                        4,
+                        Arc::new(Float64Array::from(values)),
+                        None,
+                    )
+                    .unwrap(),
+                ),
+            ],
+        )
+        .unwrap();
+        let table = TableHandle::create(&conn, "double", schema).unwrap();
+        table.add_data(batch, AddDataMode::Append).unwrap();
+        table
+            .create_index(
+                "vector",
+                "IVF_PQ",
+                DistanceType::L2,
+                Some(2),
+                Some(2),
+                false,
+                &crate::table::IndexOptions::default(),
+            )
+            .unwrap();
+        assert!(table.has_vector_index("vector").unwrap());
+
+        let mut query = QueryHandle::new(table.inner.clone());
+        query.nearest_to_f64(vec![100.0; 4]).unwrap();
+        query.limit(5).unwrap();
+        let found = ids(&query.execute().unwrap());
+        assert_eq!(found.len(), 5);
+        assert!(found.contains(&100), "{:?}", found);
+
+        // A single-precision query vector is rejected for a Float64 column
+        let mut query = QueryHandle::new(table.inner.clone());
+        assert!(query.nearest_to(vec![100.0; 4]).is_err());
+    }
+
+    #[test]
+    fn test_variable_length_vector_column() {
+        let (_dir, conn) = temp_connection();
+        let item = Arc::new(Field::new("item", DataType::Float32, true));
+        let schema = Arc::new(arrow_schema::Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("embedding", DataType::List(item), true),
+        ]));
+        let table = TableHandle::create(&conn, "ragged", schema).unwrap();
+
+        let mut query = QueryHandle::new(table.inner.clone());
+        let err = query.nearest_to(vec![1.0, 2.0]).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("column 'embedding' is a variable-length list"));
+    }
+
+    #[test]
+    fn test_distance_range() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "range", 50, 2);
+
+        let mut query = QueryHandle::new(table.inner.clone());
+        assert!(query.distance_range(None, Some(20.0)).is_err());
+        query.nearest_to(vec![10.0, 10.0]).unwrap();
+        assert_eq!(
+            lancedb_query_distance_range(&mut query, 0.0, 20.0, false, true),
+            0
+        );
+        // The range filters a page of nearest rows, so a limit is required
+        assert!(query.execute().is_err());
+
+        query.limit(10).unwrap();
+        let found = distances(&query.execute().unwrap());
+        assert!(
+            found.len() > 1 && found.len() < 10,
+            "distances: {:?}",
+            found
+        );
+        assert!(found.iter().all(|d| *d < 20.0), "distances: {:?}", found);
+
+        // A lower bound drops the nearest rows of the page; paging further
+        // reaches the rows beyond them
+        query.distance_range(Some(2.0), Some(200.0)).unwrap();
+        query.limit(4).unwrap();
+        let found = distances(&query.execute().unwrap());
+        assert!(found.len() < 4, "distances: {:?}", found);
+        assert!(
+            found.iter().all(|d| (2.0..200.0).contains(d)),
+            "distances: {:?}",
+            found
+        );
+        query.offset(4).unwrap();
+        let found = distances(&query.execute().unwrap());
+        assert_eq!(found.len(), 4);
+        assert!(
+            found.iter().all(|d| (2.0..200.0).contains(d)),
+            "distances: {:?}",
+            found
+        );
+
+        assert!(query.distance_range(Some(5.0), Some(5.0)).is_err());
+    }
+
+    #[test]
+    fn test_postfilter_can_return_fewer_rows() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "filtered", 50, 2);
+
+        let mut query = QueryHandle::new(table.inner.clone());
+        assert!(query.postfilter(true).is_err());
+        query.nearest_to(vec![0.0, 0.0]).unwrap();
+        query.filter("id >= 40").unwrap();
+        query.limit(5).unwrap();
+
+        // Prefilter searches only the matching rows
+        let rows: usize = query.execute().unwrap().iter().map(|b| b.num_rows()).sum();
+        assert_eq!(rows, 5);
+
+        // Postfilter searches first; none of the 5 nearest rows match
+        assert_eq!(lancedb_query_postfilter(&mut query, true), 0);
+        let rows: usize = query.execute().unwrap().iter().map(|b| b.num_rows()).sum();
+        assert_eq!(rows, 0);
+
+        query.postfilter(false).unwrap();
+        let rows: usize = query.execute().unwrap().iter().map(|b| b.num_rows()).sum();
+        assert_eq!(rows, 5);
+    }
+
+    #[test]
+    fn test_clone_query_across_threads() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "shared", 100, 2);
+        let mut base = QueryHandle::new(table.inner.clone());
+        base.filter("id < 50").unwrap();
+        let base = &base;
+
+        std::thread::scope(|scope| {
+            for t in 0..8 {
+                scope.spawn(move || {
+                    for _ in 0..10 {
+                        let handle = lancedb_query_clone(base);
+                        assert!(!handle.is_null());
+                        let query = unsafe { &mut *handle };
+                        query.limit(t + 1).unwrap();
+                        let rows: usize =
+                            query.execute().unwrap().iter().map(|b| b.num_rows()).sum();
+                        assert_eq!(rows, t + 1);
+                        lancedb_query_close(handle);
+
+                        // The shared query itself can be executed concurrently
+                        let rows: usize =
+                            base.execute().unwrap().iter().map(|b| b.num_rows()).sum();
+                        assert_eq!(rows, 50);
+                    }
+                });
+            }
+        });
+
+        assert!(lancedb_query_clone(std::ptr::null()).is_null());
+    }
+
+    #[test]
+    fn test_clone_is_independent() {
+        let (_dir, conn) = temp_connection();
+        let table = Box::into_raw(Box::new(vector_table(&conn, "templated", 20, 2)));
+        let base = lancedb_query_new(table);
+        unsafe { &mut *base }.filter("id >= 10").unwrap();
+        unsafe { &mut *base }.limit(1).unwrap();
+
+        // Vary the vector of each clone while the template keeps its filter
+        let near_low = lancedb_query_clone(base);
+        let near_high = lancedb_query_clone(base);
+        unsafe { &mut *near_low }
+            .nearest_to(vec![0.0, 0.0])
+            .unwrap();
+        unsafe { &mut *near_high }
+            .nearest_to(vec![19.0, 19.0])
+            .unwrap();
+
+        assert_eq!(ids(&unsafe { &*base }.execute().unwrap()).len(), 1);
+        lancedb_query_close(base);
+        crate::table::lancedb_table_close(table);
+
+        assert_eq!(ids(&unsafe { &*near_low }.execute().unwrap()), vec![10]);
+        assert_eq!(ids(&unsafe { &*near_high }.execute().unwrap()), vec![19]);
+        lancedb_query_close(near_low);
+        lancedb_query_close(near_high);
+    }
+
+    #[test]
+    fn test_reuse_and_reset_query() {
+        let (_dir, conn) = temp_connection();
+        let table = Box::into_raw(Box::new(vector_table(&conn, "reused", 10, 2)));
+        let handle = lancedb_query_new(table);
+        assert!(!handle.is_null());
+        let query = unsafe { &mut *handle };
+        query.filter("id < 5").unwrap();
+        query.nearest_to(vec![0.0, 0.0]).unwrap();
+
+        let first = ids(&query.execute().unwrap());
+        assert_eq!(first, vec![0, 1, 2, 3, 4]);
+        assert_eq!(ids(&query.execute().unwrap()), first);
+
+        // The query holds its own reference to the table
+        crate::table::lancedb_table_close(table);
+        assert_eq!(ids(&query.execute().unwrap()), first);
+
+        assert_eq!(lancedb_query_reset(handle), 0);
+        let mut all = ids(&query.execute().unwrap());
+        all.sort();
+        assert_eq!(all, (0..10).collect::<Vec<i32>>());
+
+        lancedb_query_close(handle);
+    }
+
+    #[test]
+    fn test_fast_search() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "fast", 256, 4);
+
+        // Without an index there is nothing a fast search could return
+        let mut query = QueryHandle::new(table.inner.clone());
+        query.nearest_to(vec![10.0; 4]).unwrap();
+        assert_eq!(lancedb_query_fast_search(&mut query), -1);
+
+        table
+            .create_index(
+                "vector",
+                "IVF_HNSW_SQ",
+                DistanceType::L2,
+                Some(1),
+                None,
+                false,
+                &crate::table::IndexOptions::default(),
+            )
+            .unwrap();
+        // Rows appended after the build are not indexed, so a fast search
+        // leaves them out
+        table
+            .add_data(vector_batch(256, 4, 4), AddDataMode::Append)
+            .unwrap();
+
+        let mut query = QueryHandle::new(table.inner.clone());
+        query.nearest_to(vec![300.0; 4]).unwrap();
+        assert_eq!(lancedb_query_fast_search(&mut query), 0);
+        query.limit(3).unwrap();
+        let found = ids(&query.execute().unwrap());
+        assert_eq!(found.len(), 3);
+        assert!(found.iter().all(|id| *id < 256));
+
+        let mut plain = QueryHandle::new(table.inner.clone());
+        assert!(plain.fast_search().is_err());
+    }
+
+    #[test]
+    fn test_bypass_vector_index_matches_indexed_top1() {
+        let (_dir, conn) = temp_connection();
+        // With 256 rows whose elements are 0..=255, 8-bit scalar quantization
+        // is exact, so the index and the flat scan must agree
+        let table = vector_table(&conn, "exact", 256, 8);
+        table
+            .create_index(
+                "vector",
+                "IVF_HNSW_SQ",
+                DistanceType::L2,
+                Some(1),
+                None,
+                false,
+                &crate::table::IndexOptions::default(),
+            )
+            .unwrap();
+
+        let top1 = |bypass: bool| {
+            let mut query = QueryHandle::new(table.inner.clone());
+            query.nearest_to(vec![100.0; 8]).unwrap();
+            query.ef(64).unwrap();
+            if bypass {
+                assert_eq!(lancedb_query_bypass_vector_index(&mut query), 0);
+            }
+            query.limit(1).unwrap();
+            let batches = query.execute().unwrap();
+            let id = batches[0]
+                .column_by_name("id")
+                .unwrap()
+                .as_any()
+                .downcast_ref::<Int32Array>()
+                .unwrap()
+                .value(0);
+            (id, distances(&batches)[0])
+        };
+
+        let exact = top1(true);
+        assert_eq!(exact, (100, 0.0));
+        assert_eq!(top1(false).0, exact.0);
+
+        let mut plain = QueryHandle::new(table.inner.clone());
+        assert!(plain.bypass_vector_index().is_err());
+    }
+
+    /// Table of 256 rows whose vectors all equal the id, with a text
+    /// column in which row 200 mentions "apple" most and row 3 once, and
+    /// both a vector and an FTS index
+    fn hybrid_table(conn: &crate::connection::ConnectionHandle) -> TableHandle {
+        let batch = vector_batch(0, 256, 4);
+        let texts: Vec<&str> = (0..256)
+            .map(|id| match id {
+                200 => "apple apple apple",
+                3 => "apple pear pear",
+                _ => "pear pear pear",
+            })
+            .collect();
+        let mut columns = batch.columns().to_vec();
+        columns[1] = Arc::new(StringArray::from(texts));
+        let batch = RecordBatch::try_new(batch.schema(), columns).unwrap();
+
+        let table = TableHandle::create(conn, "hybrid", vector_schema(4)).unwrap();
+        table.add_data(batch, AddDataMode::Append).unwrap();
+        for (column, index_type) in [("vector", "IVF_HNSW_SQ"), ("text", "FTS")] {
+            table
+                .create_index(
+                    column,
+                    index_type,
+                    DistanceType::L2,
+                    Some(1),
+                    None,
+                    false,
+                    &crate::table::IndexOptions::default(),
+                )
+                .unwrap();
+        }
+        table
+    }
+
+    fn ids(batches: &[RecordBatch]) -> Vec<i32> {
+        batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column_by_name("id")
+                    .unwrap()
+                    .as_any()
+                    .downcast_ref::<Int32Array>()
+                    .unwrap()
+                    .values()
+                    .to_vec()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_hybrid_search() {
+        let (_dir, conn) = temp_connection();
+        let table = hybrid_table(&conn);
+
+        let mut vector_only = QueryHandle::new(table.inner.clone());
+        vector_only.nearest_to(vec![5.0; 4]).unwrap();
+        vector_only.limit(5).unwrap();
+        assert_eq!(ids(&vector_only.execute().unwrap())[0], 5);
+
+        let mut fts_only = QueryHandle::new(table.inner.clone());
+        fts_only.full_text_search("text", "apple").unwrap();
+        assert_eq!(ids(&fts_only.execute().unwrap())[0], 200);
+
+        // Row 3 is near the query vector and mentions apple, so fusion ranks
+        // it above the best match of either search alone
+        let mut hybrid = QueryHandle::new(table.inner.clone());
+        let text = CString::new("apple").unwrap();
+        let vector = [5.0f32; 4];
+        assert_eq!(
+            lancedb_query_hybrid(&mut hybrid, text.as_ptr(), vector.as_ptr(), 4),
+            0
+        );
+        hybrid.limit(5).unwrap();
+        let batches = hybrid.execute().unwrap();
+        let found = ids(&batches);
+        assert_eq!(found[0], 3);
+        assert_eq!(found.len(), 5);
+        let scores = batches[0]
+            .column_by_name(crate::rerank::RELEVANCE_SCORE_COLUMN)
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Float32Array>()
+            .unwrap();
+        assert!(scores.values().windows(2).all(|w| w[0] >= w[1]));
+
+        // Options the hybrid path would ignore are rejected, whether they
+        // are set before or after the search
+        let mut ordered = QueryHandle::new(table.inner.clone());
+        ordered.order_by_desc("id").unwrap();
+        assert!(ordered.hybrid("apple", vec![5.0; 4]).is_err());
+        let mut batched = QueryHandle::new(table.inner.clone());
+        batched.hybrid("apple", vec![5.0; 4]).unwrap();
+        batched.with_batch_size(2).unwrap();
+        let err = batched.execute().unwrap_err();
+        assert!(err.to_string().contains("batch size"), "{}", err);
+
+        // Both indices are required
+        let plain = vector_table(&conn, "plain", 10, 4);
+        let mut query = QueryHandle::new(plain.inner.clone());
+        assert!(query.hybrid("apple", vec![5.0; 4]).is_err());
+    }
+
+    /// Reverses the fused order of the candidates
+    extern "C" fn reverse_candidates(
+        _query: *const c_char,
+        _texts: *const *const c_char,
+        _scores: *const c_float,
+        n: c_int,
+        order_out: *mut c_int,
+        _user_data: *mut std::os::raw::c_void,
+    ) -> c_int {
+        let order = unsafe { std::slice::from_raw_parts_mut(order_out, n as usize) };
+        for (position, i) in order.iter_mut().enumerate() {
+            *i = n - 1 - position as c_int;
+        }
+        0
+    }
+
+    #[test]
+    fn test_reranker() {
+        let (_dir, conn) = temp_connection();
+        let table = hybrid_table(&conn);
+        let search = |kind: c_int, params: Option<&str>| {
+            let mut query = QueryHandle::new(table.inner.clone());
+            query.hybrid("apple", vec![5.0; 4]).unwrap();
+            query.limit(5).unwrap();
+            let params = params.map(|p| CString::new(p).unwrap());
+            let params_ptr = params.as_ref().map_or(std::ptr::null(), |p| p.as_ptr());
+            assert_eq!(lancedb_query_reranker(&mut query, kind, params_ptr), 0);
+            query
+        };
+
+        // The same candidates: row 3 wins on rank fusion, while a linear
+        // combination leaning on the text score favours row 200
+        let rrf = ids(&search(crate::rerank::RERANKER_RRF, None).execute().unwrap());
+        assert_eq!(rrf[0], 3);
+        let linear = ids(
+            &search(crate::rerank::RERANKER_LINEAR, Some(r#"{"weight": 0.3}"#))
+                .execute()
+                .unwrap(),
+        );
+        assert_eq!(linear[0], 200);
+        assert_ne!(rrf, linear);
+
+        let name = CString::new("reverse_candidates").unwrap();
+        assert_eq!(
+            crate::rerank::lancedb_register_reranker(
+                name.as_ptr(),
+                Some(reverse_candidates),
+                std::ptr::null_mut()
+            ),
+            0
+        );
+        let params = r#"{"name": "reverse_candidates"}"#;
+        let mut reversed = search(crate::rerank::RERANKER_CALLBACK, Some(params));
+        let mut expected = rrf.clone();
+        expected.reverse();
+        assert_eq!(ids(&reversed.execute().unwrap()), expected);
+
+        // The callback reads the text column, which the selection leaves out
+        reversed.select(vec!["id".to_string()]).unwrap();
+        let err = reversed.execute().unwrap_err().to_string();
+        assert!(err.contains("'text', which is not selected"), "{}", err);
+
+        let mut query = QueryHandle::new(table.inner.clone());
+        let bad = CString::new(r#"{"weight": 2}"#).unwrap();
+        assert_eq!(
+            lancedb_query_reranker(&mut query, crate::rerank::RERANKER_LINEAR, bad.as_ptr()),
+            -1
+        );
+        assert_eq!(lancedb_query_reranker(&mut query, 7, std::ptr::null()), -1);
+        assert_eq!(crate::rerank::lancedb_unregister_reranker(name.as_ptr()), 0);
+    }
+
+    #[test]
+    fn test_full_text_search() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "fts", 20, 2);
+
+        let mut unindexed = QueryHandle::new(table.inner.clone());
+        assert!(unindexed.full_text_search("text", "7").is_err());
+
+        table
+            .create_index(
+                "text",
+                "FTS",
+                DistanceType::L2,
+                None,
+                None,
+                false,
+                &crate::table::IndexOptions::default(),
+            )
+            .unwrap();
+
+        let mut query = QueryHandle::new(table.inner.clone());
+        query.full_text_search("text", "7").unwrap();
+        query
+            .select(vec!["id".to_string(), "text".to_string()])
+            .unwrap();
+        let batches = query.execute().unwrap();
+
+        let ids: Vec<i32> = batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column_by_name("id")
+                    .unwrap()
+                    .as_any()
+                    .downcast_ref::<Int32Array>()
+                    .unwrap()
+                    .values()
+                    .to_vec()
+            })
+            .collect();
+        assert_eq!(ids, vec![7]);
+    }
+
+    #[test]
+    fn test_with_row_id_survives_export() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "rowids", 5, 2);
+
+        let mut query = QueryHandle::new(table.inner.clone());
+        query.with_row_id().unwrap();
+        let batches = query.execute().unwrap();
+
+        let mut array = FFI_ArrowArray::empty();
+        let mut schema = FFI_ArrowSchema::empty();
+        unsafe { export_record_batch_to_c(&batches[0], &mut array, &mut schema) }.unwrap();
+        let imported =
+            unsafe { crate::arrow_ffi::import_record_batch_from_c(&mut array, &mut schema) }
+                .unwrap();
+
+        let field = imported.schema().field_with_name("_rowid").unwrap().clone();
+        assert_eq!(field.data_type(), &DataType::UInt64);
+        assert_eq!(imported.num_rows(), batches[0].num_rows());
+    }
+
+    #[test]
+    fn test_distance_column() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "scores", 20, 2);
+
+        let mut query = QueryHandle::new(table.inner.clone());
+        assert!(query.with_distance(false).is_err());
+        query.nearest_to(vec![7.0, 7.0]).unwrap();
+        query.select(vec!["id".to_string()]).unwrap();
+        query.limit(10).unwrap();
+        let batches = query.execute().unwrap();
+
+        let mut array = FFI_ArrowArray::empty();
+        let mut schema = FFI_ArrowSchema::empty();
+        unsafe { export_record_batch_to_c(&batches[0], &mut array, &mut schema) }.unwrap();
+        let imported =
+            unsafe { crate::arrow_ffi::import_record_batch_from_c(&mut array, &mut schema) }
+                .unwrap();
+        let field = imported
+            .schema()
+            .field_with_name("_distance")
+            .unwrap()
+            .clone();
+        assert_eq!(field.data_type(), &DataType::Float32);
+
+        let found = distances(&[imported]);
+        assert_eq!(found.len(), 10);
+        assert!(
+            found.windows(2).all(|w| w[0] <= w[1]),
+            "distances: {:?}",
+            found
+        );
+
+        assert_eq!(lancedb_query_with_distance(&mut query, false), 0);
+        let batches = query.execute().unwrap();
+        assert!(batches[0].column_by_name("_distance").is_none());
+        assert!(batches[0].column_by_name("id").is_some());
+    }
+
+    #[test]
+    fn test_execute_arrow_stream() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "arrow_stream", 25, 2);
+        let query = QueryHandle::new(table.inner.clone());
+
+        let mut stream = FFI_ArrowArrayStream::empty();
+        assert_eq!(lancedb_query_execute_arrow_stream(&query, &mut stream), 0);
+        drop(query);
+
+        let reader = arrow::ffi_stream::ArrowArrayStreamReader::try_new(stream).unwrap();
+        assert!(reader.schema().field_with_name("vector").is_ok());
+        let rows: usize = reader.map(|batch| batch.unwrap().num_rows()).sum();
+        assert_eq!(rows, 25);
+    }
+
+    #[test]
+    fn test_configure() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "configured", 20, 2);
+
+        let mut query = QueryHandle::new(table.inner.clone());
+        let config = CString::new(
+            r#"{"vector": [9.0, 9.0], "distance_type": 0, "nprobes": 4,
+                "filter": "id < 5", "select": ["id"], "limit": 3}"#,
+        )
+        .unwrap();
+        assert_eq!(lancedb_query_configure(&mut query, config.as_ptr()), 0);
+        let batches = query.execute().unwrap();
+        assert_eq!(ids(&batches), vec![4, 3, 2]);
+        assert!(batches[0].column_by_name("vector").is_none());
+
+        // Unknown keys and options that need a vector search are rejected
+        let mut query = QueryHandle::new(table.inner.clone());
+        let unknown = CString::new(r#"{"limit": 3, "nprobe": 4}"#).unwrap();
+        assert_eq!(lancedb_query_configure(&mut query, unknown.as_ptr()), -1);
+        let plain = CString::new(r#"{"nprobes": 4}"#).unwrap();
+        assert_eq!(lancedb_query_configure(&mut query, plain.as_ptr()), -1);
+    }
+
+    #[test]
+    fn test_nprobes() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "probed", 10, 2);
+
+        let mut query = QueryHandle::new(table.inner.clone());
+        assert_eq!(lancedb_query_nprobes(&mut query, 4), -1);
+        query.nearest_to(vec![3.0, 3.0]).unwrap();
+        assert_eq!(lancedb_query_nprobes(&mut query, 0), -1);
+        assert_eq!(lancedb_query_nprobes(&mut query, 4), 0);
+        query.limit(1).unwrap();
+        assert_eq!(ids(&query.execute().unwrap()), vec![3]);
+    }
+
+    #[test]
+    fn test_estimated_rows() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "estimated", 20, 2);
+        let last_error = || {
+            unsafe { std::ffi::CStr::from_ptr(crate::lancedb_get_last_error()) }
+                .to_string_lossy()
+                .into_owned()
+        };
+
+        // Whether a plan carries an estimate is up to LanceDB; when it does
+        // the estimate is bounded by the rows the query can return
+        let mut query = QueryHandle::new(table.inner.clone());
+        query.filter("id < 10").unwrap();
+        query.limit(5).unwrap();
+        match lancedb_query_estimated_rows(&query) {
+            -1 => {
+                assert!(last_error().contains("does not provide a row count estimate"));
+                assert_eq!(
+                    crate::lancedb_get_last_error_code(),
+                    crate::error::ERROR_NOT_SUPPORTED
+                );
+            }
+            estimate => assert!((0..=20).contains(&estimate), "{}", estimate),
+        }
+
+        // A query that cannot be planned fails with the planning error
+        let mut query = QueryHandle::new(table.inner.clone());
+        query.nearest_to(vec![1.0, 1.0]).unwrap();
+        query.distance_range(None, Some(4.0)).unwrap();
+        assert_eq!(lancedb_query_estimated_rows(&query), -1);
+        assert!(
+            last_error().contains("requires a limit"),
+            "{}",
+            last_error()
+        );
+
+        assert_eq!(lancedb_query_estimated_rows(std::ptr::null()), -1);
+    }
+}
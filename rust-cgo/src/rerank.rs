@@ -0,0 +1,567 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The LanceDB Authors
+
+//! Fusion of vector and full-text search results for hybrid queries
+//!
+//! A hybrid query runs a vector search and a full-text search separately and
+//! hands both result sets to a [`Reranker`], which merges the rows (matched
+//! by `_rowid`) and orders them by a combined `_relevance_score`. Besides
+//! the built-in fusion methods, a caller can register a callback, such as a
+//! cross-encoder, that reorders the fused candidates.
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::CString;
+use std::os::raw::{c_char, c_float, c_int, c_void};
+use std::sync::{Arc, RwLock};
+
+use arrow::compute::{cast, concat_batches, take_record_batch};
+use arrow_array::{Array, Float32Array, RecordBatch, StringArray, UInt32Array, UInt64Array};
+use arrow_schema::{DataType, Field, Schema};
+use lazy_static::lazy_static;
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+
+/// Column holding the combined score of each hybrid search result
+pub const RELEVANCE_SCORE_COLUMN: &str = "_relevance_score";
+
+/// The `k` of reciprocal rank fusion, as in LanceDB's RRF reranker
+pub const DEFAULT_RRF_K: f32 = 60.0;
+
+/// The weight of the vector search in a linear combination, as in
+/// LanceDB's linear combination reranker
+pub const DEFAULT_LINEAR_WEIGHT: f32 = 0.7;
+
+// Reranker kinds accepted by lancedb_query_reranker
+pub const RERANKER_RRF: c_int = 0;
+pub const RERANKER_LINEAR: c_int = 1;
+pub const RERANKER_CALLBACK: c_int = 2;
+
+/// Callback that reorders `n` hybrid search candidates for the NUL-terminated
+/// `query`. `texts` holds each candidate's text (null for a null value) and
+/// `scores` its reciprocal rank fusion score, best first. The callback writes
+/// a permutation of `0..n` to `order_out`, best first, and returns 0 on
+/// success and any other value on failure.
+pub type RerankCallback = Option<
+    extern "C" fn(
+        query: *const c_char,
+        texts: *const *const c_char,
+        scores: *const c_float,
+        n: c_int,
+        order_out: *mut c_int,
+        user_data: *mut c_void,
+    ) -> c_int,
+>;
+
+/// A registered reranking callback
+pub struct RerankFunction {
+    name: String,
+    callback: extern "C" fn(
+        *const c_char,
+        *const *const c_char,
+        *const c_float,
+        c_int,
+        *mut c_int,
+        *mut c_void,
+    ) -> c_int,
+    user_data: *mut c_void,
+}
+
+// The caller promises, when registering, that the callback may be invoked
+// from any thread and that `user_data` stays valid until it is unregistered.
+unsafe impl Send for RerankFunction {}
+unsafe impl Sync for RerankFunction {}
+
+impl std::fmt::Debug for RerankFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RerankFunction")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RerankFunction {
+    /// The order, best first, in which the callback puts the candidates
+    /// described by `texts` and `scores`
+    fn reorder(&self, query: &str, texts: &[Option<&str>], scores: &[f32]) -> Result<Vec<usize>> {
+        let c_query = CString::new(query)?;
+        let c_texts = texts
+            .iter()
+            .map(|text| text.map(CString::new).transpose())
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let text_ptrs: Vec<*const c_char> = c_texts
+            .iter()
+            .map(|text| text.as_ref().map_or(std::ptr::null(), |t| t.as_ptr()))
+            .collect();
+        let mut order: Vec<c_int> = vec![-1; texts.len()];
+        let status = (self.callback)(
+            c_query.as_ptr(),
+            text_ptrs.as_ptr(),
+            scores.as_ptr(),
+            texts.len() as c_int,
+            order.as_mut_ptr(),
+            self.user_data,
+        );
+        if status != 0 {
+            return Err(Error::InvalidArgument {
+                message: format!("reranker '{}' failed with status {}", self.name, status),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+
+        let mut seen = vec![false; texts.len()];
+        for &i in &order {
+            if i < 0 || i as usize >= texts.len() || std::mem::replace(&mut seen[i as usize], true)
+            {
+                return Err(Error::InvalidArgument {
+                    message: format!(
+                        "reranker '{}' must return each index in 0..{} exactly once",
+                        self.name,
+                        texts.len()
+                    ),
+                    location: snafu::Location::new(file!(), line!(), column!()),
+                });
+            }
+        }
+        Ok(order.into_iter().map(|i| i as usize).collect())
+    }
+}
+
+lazy_static! {
+    static ref RERANK_FUNCTIONS: RwLock<HashMap<String, Arc<RerankFunction>>> =
+        RwLock::new(HashMap::new());
+}
+
+/// Register `function` under its name, replacing any function of that name
+pub fn register(function: RerankFunction) -> Result<()> {
+    RERANK_FUNCTIONS
+        .write()?
+        .insert(function.name.clone(), Arc::new(function));
+    Ok(())
+}
+
+/// Remove the function registered as `name`. Returns whether one existed.
+pub fn unregister(name: &str) -> Result<bool> {
+    Ok(RERANK_FUNCTIONS.write()?.remove(name).is_some())
+}
+
+/// Look up the function registered as `name`
+pub fn get(name: &str) -> Result<Arc<RerankFunction>> {
+    RERANK_FUNCTIONS
+        .read()?
+        .get(name)
+        .cloned()
+        .ok_or_else(|| Error::InvalidArgument {
+            message: format!("no reranker is registered as '{}'", name),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        })
+}
+
+/// Parameters of the reciprocal rank fusion reranker
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RrfParams {
+    k: Option<f32>,
+}
+
+/// Parameters of the linear combination reranker
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LinearParams {
+    weight: Option<f32>,
+}
+
+/// Parameters of a callback reranker
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CallbackParams {
+    name: String,
+    column: Option<String>,
+}
+
+/// How the results of the two searches are combined
+#[derive(Debug, Clone)]
+pub enum Reranker {
+    /// Reciprocal rank fusion: each row scores `1 / (rank + k)` in every
+    /// result set it appears in, with ranks starting at 0
+    Rrf { k: f32 },
+    /// Linear combination: vector distances and full-text scores are each
+    /// scaled to `[0, 1]`, with the nearest vector scoring 1, and a row
+    /// scores `weight * vector + (1 - weight) * text`, missing scores
+    /// counting as 0
+    Linear { weight: f32 },
+    /// A registered callback reorders the candidates as ranked by
+    /// reciprocal rank fusion, reading their text from `column` (the FTS
+    /// column when `None`). A row scores `1 / (position + 1)`.
+    Callback {
+        function: Arc<RerankFunction>,
+        column: Option<String>,
+    },
+}
+
+impl Default for Reranker {
+    fn default() -> Self {
+        Self::Rrf { k: DEFAULT_RRF_K }
+    }
+}
+
+impl Reranker {
+    /// The reranker of the given kind (one of the RERANKER_* constants),
+    /// configured by a JSON object of parameters: `{"k": ...}` for RRF,
+    /// `{"weight": ...}` for linear combination, and
+    /// `{"name": ..., "column": ...}` for a registered callback. Absent
+    /// parameters take their defaults.
+    pub fn from_kind(kind: c_int, params: Option<&str>) -> Result<Self> {
+        fn parse<'a, T: Deserialize<'a> + Default>(params: Option<&'a str>) -> Result<T> {
+            Ok(params
+                .map(serde_json::from_str)
+                .transpose()?
+                .unwrap_or_default())
+        }
+
+        match kind {
+            RERANKER_RRF => {
+                let params: RrfParams = parse(params)?;
+                let k = params.k.unwrap_or(DEFAULT_RRF_K);
+                if k.is_nan() || k <= 0.0 {
+                    return Err(Error::InvalidArgument {
+                        message: "k must be positive".to_string(),
+                        location: snafu::Location::new(file!(), line!(), column!()),
+                    });
+                }
+                Ok(Self::Rrf { k })
+            }
+            RERANKER_LINEAR => {
+                let params: LinearParams = parse(params)?;
+                let weight = params.weight.unwrap_or(DEFAULT_LINEAR_WEIGHT);
+                if !(0.0..=1.0).contains(&weight) {
+                    return Err(Error::InvalidArgument {
+                        message: "weight must be between 0 and 1".to_string(),
+                        location: snafu::Location::new(file!(), line!(), column!()),
+                    });
+                }
+                Ok(Self::Linear { weight })
+            }
+            RERANKER_CALLBACK => {
+                let params: CallbackParams = serde_json::from_str(params.unwrap_or("{}"))?;
+                Ok(Self::Callback {
+                    function: get(&params.name)?,
+                    column: params.column,
+                })
+            }
+            _ => Err(Error::InvalidArgument {
+                message: format!(
+                    "unknown reranker kind {}: expected 0 (RRF), 1 (linear), or 2 (callback)",
+                    kind
+                ),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            }),
+        }
+    }
+
+    /// Merge vector search results, which carry `_distance`, and full-text
+    /// search results, which carry `_score`, into one batch ordered by
+    /// `_relevance_score`, best first. Both must include `_rowid`.
+    ///
+    /// The output has the columns of `vector_results` without `_distance`
+    /// or `_score`, followed by `_relevance_score`. A row found by both
+    /// searches appears once. `query` and `text_column`, the searched text
+    /// and the column it was searched in, are only read by callbacks.
+    pub fn rerank(
+        &self,
+        query: &str,
+        text_column: &str,
+        vector_results: &RecordBatch,
+        fts_results: &RecordBatch,
+    ) -> Result<RecordBatch> {
+        let merged = merge_results(vector_results, fts_results)?;
+        let texts = match self {
+            Self::Callback { function, column } => {
+                let column = column.as_deref().unwrap_or(text_column);
+                let texts =
+                    merged
+                        .column_by_name(column)
+                        .ok_or_else(|| Error::InvalidArgument {
+                            message: format!(
+                        "reranker '{}' reads column '{}', which is not selected by the query",
+                        function.name, column
+                    ),
+                            location: snafu::Location::new(file!(), line!(), column!()),
+                        })?;
+                let texts = cast(texts, &DataType::Utf8).map_err(|_| Error::InvalidArgument {
+                    message: format!(
+                        "reranker '{}' reads column '{}', which must hold strings",
+                        function.name, column
+                    ),
+                    location: snafu::Location::new(file!(), line!(), column!()),
+                })?;
+                Some(texts)
+            }
+            _ => None,
+        };
+
+        let scores: HashMap<u64, f32> = match self {
+            Self::Rrf { k } => rrf_scores(vector_results, fts_results, *k)?,
+            Self::Linear { weight } => {
+                let mut scores: HashMap<u64, f32> = HashMap::new();
+                let vector_scores = normalized_scores(vector_results, "_distance", true)?;
+                let fts_scores = normalized_scores(fts_results, "_score", false)?;
+                for (row_id, score) in vector_scores {
+                    *scores.entry(row_id).or_insert(0.0) += weight * score;
+                }
+                for (row_id, score) in fts_scores {
+                    *scores.entry(row_id).or_insert(0.0) += (1.0 - weight) * score;
+                }
+                scores
+            }
+            Self::Callback { .. } => rrf_scores(vector_results, fts_results, DEFAULT_RRF_K)?,
+        };
+
+        let row_ids = row_ids(&merged)?;
+
+        // Keep the first occurrence of each row, then order by score. The
+        // sort is stable, so ties keep vector results first.
+        let mut seen = HashSet::new();
+        let mut rows: Vec<(u32, f32)> = Vec::new();
+        for (i, row_id) in row_ids.values().iter().enumerate() {
+            if seen.insert(*row_id) {
+                rows.push((i as u32, scores[row_id]));
+            }
+        }
+        rows.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        if let (Self::Callback { function, .. }, Some(texts)) = (self, &texts) {
+            let texts = texts.as_any().downcast_ref::<StringArray>().unwrap();
+            let candidate_texts: Vec<Option<&str>> = rows
+                .iter()
+                .map(|(i, _)| {
+                    texts
+                        .is_valid(*i as usize)
+                        .then(|| texts.value(*i as usize))
+                })
+                .collect();
+            let candidate_scores: Vec<f32> = rows.iter().map(|(_, s)| *s).collect();
+            let order = function.reorder(query, &candidate_texts, &candidate_scores)?;
+            rows = order
+                .into_iter()
+                .enumerate()
+                .map(|(position, i)| (rows[i].0, 1.0 / (position as f32 + 1.0)))
+                .collect();
+        }
+
+        let indices = UInt32Array::from_iter_values(rows.iter().map(|(i, _)| *i));
+        let reranked = take_record_batch(&merged, &indices)?;
+        let relevance = Float32Array::from_iter_values(rows.iter().map(|(_, s)| *s));
+
+        let mut fields = reranked.schema().fields().to_vec();
+        fields.push(Arc::new(Field::new(
+            RELEVANCE_SCORE_COLUMN,
+            DataType::Float32,
+            false,
+        )));
+        let mut columns = reranked.columns().to_vec();
+        columns.push(Arc::new(relevance));
+        Ok(RecordBatch::try_new(
+            Arc::new(Schema::new(fields)),
+            columns,
+        )?)
+    }
+}
+
+fn row_ids(batch: &RecordBatch) -> Result<&UInt64Array> {
+    batch
+        .column_by_name("_rowid")
+        .and_then(|c| c.as_any().downcast_ref::<UInt64Array>())
+        .ok_or_else(|| Error::InvalidArgument {
+            message: "hybrid search results are missing the _rowid column".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        })
+}
+
+/// Reciprocal rank fusion scores of every row in either result set
+fn rrf_scores(
+    vector_results: &RecordBatch,
+    fts_results: &RecordBatch,
+    k: f32,
+) -> Result<HashMap<u64, f32>> {
+    let mut scores = HashMap::new();
+    for ranking in [
+        ranking(vector_results, "_distance", true)?,
+        ranking(fts_results, "_score", false)?,
+    ] {
+        for (rank, row_id) in ranking.iter().enumerate() {
+            *scores.entry(*row_id).or_insert(0.0) += 1.0 / (rank as f32 + k);
+        }
+    }
+    Ok(scores)
+}
+
+/// The scores in `score_column` scaled to `[0, 1]`, best 1, keyed by row id.
+/// Lower raw scores are better when `ascending`. If every row has the same
+/// score, all score 1.
+fn normalized_scores(
+    batch: &RecordBatch,
+    score_column: &str,
+    ascending: bool,
+) -> Result<Vec<(u64, f32)>> {
+    let scores = score_values(batch, score_column)?;
+    let row_ids = row_ids(batch)?;
+    let (min, max) = scores
+        .values()
+        .iter()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), s| {
+            (min.min(*s), max.max(*s))
+        });
+    let range = max - min;
+    Ok(scores
+        .values()
+        .iter()
+        .zip(row_ids.values().iter())
+        .map(|(score, row_id)| {
+            let scaled = if range > 0.0 {
+                (score - min) / range
+            } else {
+                1.0
+            };
+            (*row_id, if ascending { 1.0 - scaled } else { scaled })
+        })
+        .collect())
+}
+
+/// The values of `score_column` as floats
+fn score_values(batch: &RecordBatch, score_column: &str) -> Result<Float32Array> {
+    let scores = batch
+        .column_by_name(score_column)
+        .ok_or_else(|| Error::InvalidArgument {
+            message: format!(
+                "hybrid search results are missing the {} column",
+                score_column
+            ),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        })?;
+    let scores = cast(scores, &DataType::Float32)?;
+    Ok(scores
+        .as_any()
+        .downcast_ref::<Float32Array>()
+        .unwrap()
+        .clone())
+}
+
+/// The row ids of `batch` ordered best first by `score_column`, lowest first
+/// when `ascending`
+fn ranking(batch: &RecordBatch, score_column: &str, ascending: bool) -> Result<Vec<u64>> {
+    let scores = score_values(batch, score_column)?;
+    let row_ids = row_ids(batch)?;
+
+    let mut order: Vec<usize> = (0..batch.num_rows()).collect();
+    order.sort_by(|&a, &b| {
+        let ordering = scores.value(a).total_cmp(&scores.value(b));
+        if ascending {
+            ordering
+        } else {
+            ordering.reverse()
+        }
+    });
+    Ok(order.into_iter().map(|i| row_ids.value(i)).collect())
+}
+
+/// Stack both result sets on the columns they share, the columns of
+/// `vector_results` other than the search scores
+fn merge_results(vector_results: &RecordBatch, fts_results: &RecordBatch) -> Result<RecordBatch> {
+    let schema = vector_results.schema();
+    let indices: Vec<usize> = (0..schema.fields().len())
+        .filter(|i| !matches!(schema.field(*i).name().as_str(), "_distance" | "_score"))
+        .collect();
+    let vector_results = vector_results.project(&indices)?;
+
+    let fts_indices = vector_results
+        .schema()
+        .fields()
+        .iter()
+        .map(|field| {
+            fts_results
+                .schema()
+                .index_of(field.name())
+                .map_err(Error::from)
+        })
+        .collect::<Result<Vec<usize>>>()?;
+    let fts_results = fts_results.project(&fts_indices)?;
+
+    Ok(concat_batches(
+        &vector_results.schema(),
+        &[vector_results.clone(), fts_results],
+    )?)
+}
+
+// C API for reranking callbacks
+
+/// Register a reranking callback under `name`, replacing any existing
+/// callback of that name, for use with lancedb_query_reranker.
+/// Returns 0 on success, -1 on failure.
+///
+/// The callback is called on the thread that executes the query, possibly
+/// from several threads at once, so it must be thread-safe. `user_data` is
+/// passed through unchanged and must stay valid until the callback is
+/// unregistered.
+#[no_mangle]
+pub extern "C" fn lancedb_register_reranker(
+    name: *const c_char,
+    callback: RerankCallback,
+    user_data: *mut c_void,
+) -> c_int {
+    let Some(callback) = callback else {
+        crate::set_last_error(&Error::NullPointer {
+            message: "callback cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    };
+
+    let name = match unsafe { crate::c_str_arg(name, "name") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match register(RerankFunction {
+        name: name.to_string(),
+        callback,
+        user_data,
+    }) {
+        Ok(()) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Unregister the reranking callback named `name`. Queries already using it
+/// keep it. Returns 0 on success, -1 if no callback has that name.
+#[no_mangle]
+pub extern "C" fn lancedb_unregister_reranker(name: *const c_char) -> c_int {
+    let name = match unsafe { crate::c_str_arg(name, "name") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match unregister(name) {
+        Ok(true) => 0,
+        Ok(false) => {
+            crate::set_last_error(&Error::InvalidArgument {
+                message: format!("no reranker is registered as '{}'", name),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+            -1
+        }
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
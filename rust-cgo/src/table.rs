@@ -1,33 +1,217 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-FileCopyrightText: Copyright The LanceDB Authors
 
-use std::ffi::{CStr, CString};
-use std::os::raw::{c_char, c_int};
-use std::sync::Arc;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::CString;
+use std::os::raw::{c_char, c_double, c_float, c_int, c_void};
+use std::sync::atomic::{AtomicI32, AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
 
 use arrow::ffi::FFI_ArrowArray;
 use arrow::ffi::FFI_ArrowSchema;
-use arrow_array::{RecordBatch, RecordBatchIterator};
-use arrow_schema::{DataType, Field, Schema};
+use arrow::ffi_stream::{ArrowArrayStreamReader, FFI_ArrowArrayStream};
+use arrow_array::{
+    Array, RecordBatch, RecordBatchIterator, RecordBatchReader, UInt32Array, UInt64Array,
+};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
 
 use crate::arrow_ffi::import_record_batch_from_c;
+use crate::embedding::EmbeddingColumn;
 use crate::error::Result;
 use crate::{c_result, RT};
-use lancedb::index::vector::IvfPqIndexBuilder;
-use lancedb::index::{Index, IndexConfig};
-use lancedb::query::{ExecutableQuery, QueryBase};
-use lancedb::table::{AddDataMode, Table};
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use lance::dataset::builder::DatasetBuilder;
+use lance::dataset::transaction::Operation;
+use lance::dataset::{WriteMode, WriteParams};
+use lance::io::{ObjectStore, ObjectStoreParams, ObjectStoreRegistry};
+use lance::table::format::Fragment;
+use lance_encoding::version::LanceFileVersion;
+use lance_index::DatasetIndexExt;
+use lancedb::arrow::RecordBatchStream;
+use lancedb::connection::CreateTableMode;
+use lancedb::index::scalar::{BTreeIndexBuilder, FtsIndexBuilder};
+use lancedb::index::vector::{IvfHnswPqIndexBuilder, IvfHnswSqIndexBuilder, IvfPqIndexBuilder};
+use lancedb::index::{Index, IndexConfig, IndexType};
+use lancedb::query::{ExecutableQuery, QueryBase, VectorQuery};
+use lancedb::table::{AddDataMode, ColumnAlteration, Table, WriteOptions};
 use lancedb::DistanceType;
+use serde::{Deserialize, Serialize};
 
-/// Opaque handle to a LanceDB table
+/// Default number of times an append is retried after a commit conflict
+pub const DEFAULT_COMMIT_RETRIES: u32 = 5;
+
+/// Column tagging each batch search result with the position of its query
+pub const QUERY_INDEX_COLUMN: &str = "_query_index";
+
+/// How many searches of a batch search run at once
+const BATCH_SEARCH_CONCURRENCY: usize = 16;
+
+/// How often wait_for_index checks whether the indices are built
+const INDEX_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Returned by lancedb_table_wait_for_index when the timeout elapses first
+pub const WAIT_TIMED_OUT: c_int = -2;
+
+/// How many fragments count_rows_approx evaluates a filter on
+const APPROX_COUNT_SAMPLE_FRAGMENTS: usize = 8;
+
+/// Opaque handle to a LanceDB table.
+///
+/// Every operation takes the handle by shared reference and its settings are
+/// synchronized, so one handle may be used from several threads at once.
 pub struct TableHandle {
     pub inner: Table,
+    /// How many times add_data retries after losing a commit race
+    commit_retries: AtomicU32,
+    /// Vector columns filled by embedding a text column on write
+    embeddings: Mutex<Vec<EmbeddingColumn>>,
+    /// Object store options of the connection the table was opened through
+    storage_options: HashMap<String, String>,
 }
 
 impl TableHandle {
+    pub fn new(inner: Table) -> Self {
+        Self {
+            inner,
+            commit_retries: AtomicU32::new(DEFAULT_COMMIT_RETRIES),
+            embeddings: Mutex::new(Vec::new()),
+            storage_options: HashMap::new(),
+        }
+    }
+
+    /// Wrap a table opened or created through `connection`, keeping the
+    /// connection's object store options for direct dataset access
+    fn opened(connection: &super::connection::ConnectionHandle, inner: Table) -> Self {
+        Self {
+            storage_options: connection.storage_options.clone(),
+            ..Self::new(inner)
+        }
+    }
+
+    /// Fill `dest_column` by embedding `source_column` with the registered
+    /// function `function_name` whenever a written batch lacks it.
+    ///
+    /// The setting belongs to this handle and is not stored with the table.
+    pub fn add_embedding_column(
+        &self,
+        source_column: &str,
+        dest_column: &str,
+        function_name: &str,
+    ) -> Result<()> {
+        let function = crate::embedding::get(function_name)?;
+        let schema = self.schema()?;
+        let source = schema.field_with_name(source_column)?;
+        if source.data_type() != &DataType::Utf8 {
+            return Err(crate::error::Error::InvalidArgument {
+                message: format!(
+                    "embedding source column '{}' must be Utf8, but is {}",
+                    source_column,
+                    source.data_type()
+                ),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+        let (dim, _) = self.vector_dimensions(dest_column)?;
+        if dim != function.dim() {
+            return Err(crate::error::Error::InvalidArgument {
+                message: format!(
+                    "column '{}' has {} dims but embedding function '{}' produces {}",
+                    dest_column,
+                    dim,
+                    function_name,
+                    function.dim()
+                ),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+
+        // A plain list that stays consistent even if a holder panicked
+        let mut embeddings = self
+            .embeddings
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        embeddings.retain(|e| e.dest_column != dest_column);
+        embeddings.push(EmbeddingColumn {
+            source_column: source_column.to_string(),
+            dest_column: dest_column.to_string(),
+            function_name: function_name.to_string(),
+        });
+        Ok(())
+    }
+
+    pub fn embedding_columns(&self) -> Vec<EmbeddingColumn> {
+        self.embeddings
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Add every configured embedding column that `batch` lacks
+    fn embed_batch(&self, batch: RecordBatch) -> Result<RecordBatch> {
+        let embeddings = self.embedding_columns();
+        if embeddings.is_empty() {
+            return Ok(batch);
+        }
+        let schema = self.schema()?;
+        embeddings
+            .iter()
+            .try_fold(batch, |batch, embedding| embedding.apply(batch, &schema))
+    }
+
+    /// The column a vector index on `column` is built on: the embedded
+    /// vector column when `column` is the text source of one
+    fn index_column(&self, column: &str, index_type: &str) -> String {
+        if index_type.to_uppercase().starts_with("IVF") {
+            if let Some(embedding) = self
+                .embedding_columns()
+                .into_iter()
+                .find(|e| e.source_column == column)
+            {
+                return embedding.dest_column;
+            }
+        }
+        column.to_string()
+    }
+
     pub fn open(connection: &super::connection::ConnectionHandle, name: &str) -> Result<Self> {
         let table = RT.block_on(connection.inner.open_table(name).execute())?;
-        Ok(Self { inner: table })
+        Ok(Self::opened(connection, table))
+    }
+
+    /// Open a table pinned to `version`, or at the latest version when 0.
+    ///
+    /// Equivalent to open followed by checkout, so the handle stays on that
+    /// version until `checkout_latest` is called.
+    pub fn open_version(
+        connection: &super::connection::ConnectionHandle,
+        name: &str,
+        version: u64,
+    ) -> Result<Self> {
+        let handle = Self::open(connection, name)?;
+        if version > 0 {
+            handle.checkout(version)?;
+        }
+        Ok(handle)
+    }
+
+    /// Open a table whose index cache holds up to `index_cache_size` index
+    /// entries, or the LanceDB default when 0.
+    ///
+    /// The cache belongs to the opened table, so handles to the same table
+    /// opened with different sizes each keep their own.
+    pub fn open_with_index_cache_size(
+        connection: &super::connection::ConnectionHandle,
+        name: &str,
+        index_cache_size: u32,
+    ) -> Result<Self> {
+        let mut builder = connection.inner.open_table(name);
+        if index_cache_size > 0 {
+            builder = builder.index_cache_size(index_cache_size);
+        }
+        let table = RT.block_on(builder.execute())?;
+        Ok(Self::opened(connection, table))
     }
 
     #[allow(dead_code)] // Used by C API in future phases
@@ -36,32 +220,389 @@ impl TableHandle {
         name: &str,
         schema: Arc<Schema>,
     ) -> Result<Self> {
-        let table = RT.block_on(connection.inner.create_empty_table(name, schema).execute())?;
-        Ok(Self { inner: table })
+        Self::create_with_mode(connection, name, schema, CreateTableMode::Create)
+    }
+
+    /// Create an empty table, with `mode` deciding what happens when a table
+    /// of that name already exists
+    pub fn create_with_mode(
+        connection: &super::connection::ConnectionHandle,
+        name: &str,
+        schema: Arc<Schema>,
+        mode: CreateTableMode,
+    ) -> Result<Self> {
+        Self::create_with_storage_version(connection, name, schema, mode, None)
+    }
+
+    /// Create an empty table like [`Self::create_with_mode`], writing its data
+    /// files in the given Lance file format version (LanceDB's default when
+    /// `None`). Pinning an older version keeps the table readable by older
+    /// readers.
+    pub fn create_with_storage_version(
+        connection: &super::connection::ConnectionHandle,
+        name: &str,
+        schema: Arc<Schema>,
+        mode: CreateTableMode,
+        storage_version: Option<LanceFileVersion>,
+    ) -> Result<Self> {
+        let mut builder = connection.inner.create_empty_table(name, schema).mode(mode);
+        if let Some(version) = storage_version {
+            builder = builder.data_storage_version(version);
+        }
+        let table = RT.block_on(builder.execute())?;
+        Ok(Self::opened(connection, table))
+    }
+
+    /// Create a table holding the rows of `batch`, with the batch's schema.
+    /// An empty batch creates an empty table with that schema.
+    pub fn create_from_batch(
+        connection: &super::connection::ConnectionHandle,
+        name: &str,
+        batch: RecordBatch,
+        mode: CreateTableMode,
+    ) -> Result<Self> {
+        let schema = batch.schema();
+        if batch.num_rows() == 0 {
+            return Self::create_with_mode(connection, name, schema, mode);
+        }
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let table = RT.block_on(
+            connection
+                .inner
+                .create_table(name, Box::new(reader))
+                .mode(mode)
+                .execute(),
+        )?;
+        Ok(Self::opened(connection, table))
     }
 
     pub fn count_rows(&self) -> Result<i64> {
-        let count = RT.block_on(self.inner.count_rows(None))?;
+        self.count_rows_filtered(None)
+    }
+
+    /// Count the rows matching the SQL predicate `filter`, or all rows if
+    /// it is `None`
+    pub fn count_rows_filtered(&self, filter: Option<&str>) -> Result<i64> {
+        let count = RT.block_on(self.inner.count_rows(filter.map(|f| f.to_string())))?;
         Ok(count as i64)
     }
 
-    pub fn add_data(&self, batch: RecordBatch, mode: AddDataMode) -> Result<()> {
+    /// Estimate the rows matching `filter`, or all rows if it is `None`,
+    /// from fragment metadata instead of reading the table. The estimate is
+    /// for the version the handle reads.
+    ///
+    /// Without a filter this sums the live row counts recorded in the
+    /// manifest. A fragment whose deletion file does not record how many rows
+    /// it removes (as with older writers) is counted with its deleted rows.
+    /// With a filter it is evaluated exactly on up to
+    /// APPROX_COUNT_SAMPLE_FRAGMENTS evenly spaced fragments and the share of
+    /// their live rows that match is scaled to the whole table, so the
+    /// estimate drifts when matching rows are unevenly spread across
+    /// fragments. A table with at most that many fragments is counted
+    /// exactly.
+    pub fn count_rows_approx(&self, filter: Option<&str>) -> Result<i64> {
+        let dataset = self.dataset()?;
+        let fragments = dataset.get_fragments();
+        let mut live_rows = Vec::with_capacity(fragments.len());
+        for fragment in &fragments {
+            let metadata = fragment.metadata();
+            let rows = match (metadata.num_rows(), metadata.physical_rows) {
+                (Some(rows), _) | (None, Some(rows)) => rows,
+                // Manifests from older writers may not record the count
+                (None, None) => RT.block_on(fragment.physical_rows())?,
+            };
+            live_rows.push(rows as u64);
+        }
+        let total: u64 = live_rows.iter().sum();
+        let Some(filter) = filter else {
+            return Ok(total as i64);
+        };
+
+        let step = fragments
+            .len()
+            .div_ceil(APPROX_COUNT_SAMPLE_FRAGMENTS)
+            .max(1);
+        let sample: Vec<usize> = (0..fragments.len()).step_by(step).collect();
+        let sampled_rows: u64 = sample.iter().map(|&i| live_rows[i]).sum();
+        if sampled_rows == 0 {
+            return Ok(0);
+        }
+
+        let mut scanner = dataset.scan();
+        scanner
+            .with_fragments(
+                sample
+                    .iter()
+                    .map(|&i| fragments[i].metadata().clone())
+                    .collect(),
+            )
+            .filter(filter)?;
+        let matched = RT.block_on(scanner.count_rows())?;
+        if sample.len() == fragments.len() {
+            return Ok(matched as i64);
+        }
+        Ok((matched as f64 * total as f64 / sampled_rows as f64).round() as i64)
+    }
+
+    /// Write a batch to the table and commit it.
+    ///
+    /// Writes are not buffered: the data files and the new manifest are both
+    /// written before this returns, so a successful return means the rows are
+    /// durable and visible as a new table version.
+    ///
+    /// If another writer commits first, the table is refreshed to the latest
+    /// version and the write is retried, up to the configured retry count.
+    ///
+    /// Returns the number of rows written, which after an overwrite is also
+    /// the new size of the table.
+    pub fn add_data(&self, batch: RecordBatch, mode: AddDataMode) -> Result<usize> {
+        self.add_data_with_options(batch, mode, None, None)
+    }
+
+    /// Write a batch like [`Self::add_data`], sizing the written files.
+    ///
+    /// `max_rows_per_file` caps the rows of each data file, and so of each
+    /// new fragment; `max_rows_per_group` caps the rows of each row group
+    /// within a file. `None` keeps Lance's defaults. Larger files mean fewer
+    /// fragments for compaction to merge later.
+    pub fn add_data_with_options(
+        &self,
+        batch: RecordBatch,
+        mode: AddDataMode,
+        max_rows_per_file: Option<usize>,
+        max_rows_per_group: Option<usize>,
+    ) -> Result<usize> {
+        let batch = self.embed_batch(batch)?;
+        let num_rows = batch.num_rows();
         let schema = batch.schema();
-        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
-        RT.block_on(self.inner.add(Box::new(reader)).mode(mode).execute())?;
+        // LanceDB takes the write mode from the write params when they are
+        // given, so set it there as well
+        let write_params =
+            (max_rows_per_file.is_some() || max_rows_per_group.is_some()).then(|| {
+                let defaults = WriteParams::default();
+                WriteParams {
+                    mode: match mode {
+                        AddDataMode::Append => WriteMode::Append,
+                        AddDataMode::Overwrite => WriteMode::Overwrite,
+                    },
+                    max_rows_per_file: max_rows_per_file.unwrap_or(defaults.max_rows_per_file),
+                    max_rows_per_group: max_rows_per_group.unwrap_or(defaults.max_rows_per_group),
+                    ..defaults
+                }
+            });
+        let max_retries = self.commit_retries.load(Ordering::Relaxed);
+        let commit = || {
+            let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+            let mut add = self.inner.add(Box::new(reader)).mode(mode.clone());
+            if let Some(params) = &write_params {
+                add = add.write_options(WriteOptions {
+                    lance_write_params: Some(params.clone()),
+                });
+            }
+            add.execute()
+        };
         // The table reference remains valid - LanceDB uses internal versioning
+        RT.block_on(retry_on_conflict(max_retries, commit, || {
+            self.inner.checkout_latest()
+        }))?;
+        Ok(num_rows)
+    }
+
+    /// Write every batch from `reader` to the table in a single commit,
+    /// without collecting the batches in memory first.
+    ///
+    /// An empty stream is a no-op. Every batch must have the same fields as
+    /// the first one. Unlike [`Self::add_data`], a conflicting commit is not
+    /// retried because the stream cannot be replayed.
+    pub fn add_stream(
+        &self,
+        mut reader: Box<dyn RecordBatchReader + Send>,
+        mode: AddDataMode,
+    ) -> Result<()> {
+        let first = match reader.next() {
+            Some(batch) => self.embed_batch(batch?)?,
+            None => return Ok(()),
+        };
+        let schema = first.schema();
+        let expected = schema.clone();
+        let embeddings = self.embedding_columns();
+        let table_schema = self.schema()?;
+        let rest = reader.enumerate().map(move |(i, batch)| {
+            let batch = embeddings.iter().try_fold(batch?, |batch, embedding| {
+                embedding
+                    .apply(batch, &table_schema)
+                    .map_err(|e| ArrowError::ExternalError(Box::new(e)))
+            })?;
+            if batch.schema().fields() != expected.fields() {
+                let reason = schema_mismatch(&expected, &batch.schema())
+                    .unwrap_or_else(|| "fields are in a different order".to_string());
+                return Err(ArrowError::SchemaError(format!(
+                    "batch {} does not match the schema of the first batch: {}",
+                    i + 1,
+                    reason
+                )));
+            }
+            Ok(batch)
+        });
+        let batches = RecordBatchIterator::new(std::iter::once(Ok(first)).chain(rest), schema);
+        RT.block_on(self.inner.add(Box::new(batches)).mode(mode).execute())?;
+        Ok(())
+    }
+
+    /// Append a batch and fold the new rows into the table's existing indices.
+    ///
+    /// The append and the index update are separate commits. If the index
+    /// update fails the rows are still appended, and queries still find them
+    /// through a flat search of the unindexed data.
+    pub fn add_and_index(&self, batch: RecordBatch) -> Result<()> {
+        use lancedb::table::{OptimizeAction, OptimizeOptions};
+        self.add_data(batch, AddDataMode::Append)?;
+        RT.block_on(
+            self.inner
+                .optimize(OptimizeAction::Index(OptimizeOptions::default())),
+        )?;
+        Ok(())
+    }
+
+    /// List every version of the table as `(version, commit time)` pairs,
+    /// oldest first
+    pub fn list_versions(&self) -> Result<Vec<(u64, chrono::DateTime<chrono::Utc>)>> {
+        let versions = RT.block_on(self.inner.list_versions())?;
+        Ok(versions
+            .into_iter()
+            .map(|v| (v.version, v.timestamp))
+            .collect())
+    }
+
+    /// Pin the handle to `version`. Reads see the table as of that version
+    /// until `checkout_latest` is called.
+    pub fn checkout(&self, version: u64) -> Result<()> {
+        RT.block_on(self.inner.checkout(version))?;
+        Ok(())
+    }
+
+    /// Return the handle to the latest version of the table
+    pub fn checkout_latest(&self) -> Result<()> {
+        RT.block_on(self.inner.checkout_latest())?;
+        Ok(())
+    }
+
+    /// Make `version` the new latest version of the table.
+    ///
+    /// This commits a new version whose data matches `version`; the versions
+    /// in between are kept in the history. The handle ends up on the new tip.
+    pub fn restore(&self, version: u64) -> Result<()> {
+        self.checkout_latest()?;
+        let latest = self.version()?;
+        if version > latest {
+            return Err(crate::error::Error::InvalidArgument {
+                message: format!(
+                    "cannot restore version {}: the latest version is {}",
+                    version, latest
+                ),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+        self.checkout(version)?;
+        RT.block_on(self.inner.restore())?;
         Ok(())
     }
 
+    /// The version the handle currently reads
+    pub fn version(&self) -> Result<u64> {
+        Ok(RT.block_on(self.inner.version())?)
+    }
+
+    /// Set how many times add_data retries after a commit conflict
+    pub fn set_commit_retries(&self, retries: u32) {
+        self.commit_retries.store(retries, Ordering::Relaxed);
+    }
+
     pub fn schema(&self) -> Result<Arc<Schema>> {
         let schema = RT.block_on(self.inner.schema())?;
         Ok(schema)
     }
 
-    pub fn to_arrow(&self, limit: Option<i64>) -> Result<Vec<RecordBatch>> {
+    /// Check whether batches with `schema` can be added to the table.
+    ///
+    /// Returns SCHEMA_COMPATIBLE when every field has the table's type, or
+    /// SCHEMA_CASTABLE when some fields need a lossless widening cast (e.g.
+    /// Int16 to Int32) first; add_data does not cast, so such batches must be
+    /// cast by the caller. Fields are matched by name. Every table column
+    /// must be present except embedding columns this handle fills, and a
+    /// nullable field cannot go into a non-nullable column. Any other
+    /// difference is an error naming the field.
+    pub fn check_schema(&self, schema: &Schema) -> Result<c_int> {
+        let table_schema = self.schema()?;
+        let embedded: HashSet<String> = self
+            .embedding_columns()
+            .into_iter()
+            .map(|e| e.dest_column)
+            .collect();
+        let incompatible = |message: String| crate::error::Error::InvalidArgument {
+            message: format!("schema is incompatible with the table: {}", message),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        };
+
+        let mut result = SCHEMA_COMPATIBLE;
+        for field in schema.fields() {
+            let column = table_schema.field_with_name(field.name()).map_err(|_| {
+                incompatible(format!("field '{}' is not in the table", field.name()))
+            })?;
+            if field.is_nullable() && !column.is_nullable() {
+                return Err(incompatible(format!(
+                    "field '{}' is nullable but the table column is not",
+                    field.name()
+                )));
+            }
+            if field.data_type() == column.data_type() {
+                continue;
+            }
+            if !is_widening_cast(field.data_type(), column.data_type()) {
+                return Err(incompatible(format!(
+                    "field '{}' has type {} but the table column has type {}",
+                    field.name(),
+                    field.data_type(),
+                    column.data_type()
+                )));
+            }
+            result = SCHEMA_CASTABLE;
+        }
+        for column in table_schema.fields() {
+            if schema.field_with_name(column.name()).is_err() && !embedded.contains(column.name()) {
+                return Err(incompatible(format!(
+                    "table column '{}' is missing",
+                    column.name()
+                )));
+            }
+        }
+        Ok(result)
+    }
+
+    /// Read the table's rows, skipping the first `offset` rows and then
+    /// reading at most `limit` rows when given
+    pub fn to_arrow(&self, limit: Option<i64>, offset: usize) -> Result<Vec<RecordBatch>> {
+        self.to_arrow_with_progress(limit, offset, |_| {})
+    }
+
+    /// Like `to_arrow`, but calls `on_progress` with the total number of rows
+    /// read so far after each batch arrives
+    pub fn to_arrow_with_progress(
+        &self,
+        limit: Option<i64>,
+        offset: usize,
+        mut on_progress: impl FnMut(usize),
+    ) -> Result<Vec<RecordBatch>> {
         // Create a query to read all data
         let query = self.inner.query();
 
+        // Skip to the offset before applying the limit
+        let query = if offset > 0 {
+            query.offset(offset)
+        } else {
+            query
+        };
+
         // Apply limit if specified
         let query = if let Some(lim) = limit {
             query.limit(lim as usize)
@@ -70,11 +611,15 @@ impl TableHandle {
         };
 
         // Execute the query and collect results
-        let stream = RT.block_on(query.execute())?;
-        let batches: Vec<RecordBatch> = RT.block_on(async {
-            use futures::TryStreamExt;
-            stream.try_collect::<Vec<_>>().await
-        })?;
+        let mut stream = RT.block_on(query.execute())?;
+        let mut batches: Vec<RecordBatch> = Vec::new();
+        let mut rows = 0;
+        while let Some(batch) = RT.block_on(stream.next()) {
+            let batch = batch?;
+            rows += batch.num_rows();
+            batches.push(batch);
+            on_progress(rows);
+        }
 
         Ok(batches)
     }
@@ -88,27 +633,17 @@ impl TableHandle {
         num_partitions: Option<u32>,
         num_sub_vectors: Option<u32>,
         replace: bool,
+        options: &IndexOptions,
     ) -> Result<()> {
-        // Build the index based on type
-        let index = match index_type.to_uppercase().as_str() {
-            "IVF_PQ" => {
-                let mut builder = IvfPqIndexBuilder::default().distance_type(metric);
-                if let Some(partitions) = num_partitions {
-                    builder = builder.num_partitions(partitions);
-                }
-                if let Some(sub_vectors) = num_sub_vectors {
-                    builder = builder.num_sub_vectors(sub_vectors);
-                }
-                Index::IvfPq(builder)
-            }
-            "AUTO" => Index::Auto,
-            _ => {
-                return Err(crate::error::Error::InvalidArgument {
-                    message: format!("Unsupported index type: {}", index_type),
-                    location: snafu::Location::new(file!(), line!(), column!()),
-                });
-            }
-        };
+        let column = &self.index_column(column, index_type);
+        let index = self.prepare_index(
+            column,
+            index_type,
+            metric,
+            num_partitions,
+            num_sub_vectors,
+            options,
+        )?;
 
         // Create the index
         RT.block_on(
@@ -120,543 +655,6768 @@ impl TableHandle {
         Ok(())
     }
 
+    /// Resolve defaults that depend on the table's data and build the index
+    /// definition
+    fn prepare_index(
+        &self,
+        column: &str,
+        index_type: &str,
+        metric: DistanceType,
+        num_partitions: Option<u32>,
+        num_sub_vectors: Option<u32>,
+        options: &IndexOptions,
+    ) -> Result<Index> {
+        self.check_index_column(column, index_type)?;
+        let num_partitions = self.resolve_num_partitions(index_type, num_partitions)?;
+        let sample_rate = match options.memory_limit {
+            Some(memory_limit) => {
+                if !index_type.to_uppercase().starts_with("IVF") {
+                    return Err(crate::error::Error::InvalidArgument {
+                        message: format!(
+                            "memory_limit is only supported for IVF indices, not {}",
+                            index_type
+                        ),
+                        location: snafu::Location::new(file!(), line!(), column!()),
+                    });
+                }
+                let (dim, element_size) = self.vector_dimensions(column)?;
+                Some(training_sample_rate(
+                    memory_limit,
+                    num_partitions.unwrap_or(1),
+                    dim,
+                    element_size,
+                )?)
+            }
+            None => None,
+        };
+        build_index(
+            index_type,
+            metric,
+            num_partitions,
+            num_sub_vectors,
+            sample_rate,
+            options,
+        )
+    }
+
+    /// Check that `column` has a type the index type can be built on
+    fn check_index_column(&self, column: &str, index_type: &str) -> Result<()> {
+        let schema = self.schema()?;
+        let field = schema.field_with_name(column)?;
+        if index_type.eq_ignore_ascii_case("FTS")
+            && !matches!(field.data_type(), DataType::Utf8 | DataType::LargeUtf8)
+        {
+            return Err(crate::error::Error::InvalidArgument {
+                message: format!(
+                    "FTS indices require a string column, but '{}' is {}",
+                    column,
+                    field.data_type()
+                ),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+        if index_type.eq_ignore_ascii_case("BTREE") && field.data_type().is_nested() {
+            return Err(crate::error::Error::InvalidArgument {
+                message: format!(
+                    "BTREE indices require a scalar column, but '{}' is {}",
+                    column,
+                    field.data_type()
+                ),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+        if index_type.to_uppercase().starts_with("IVF_HNSW")
+            && !matches!(field.data_type(), DataType::FixedSizeList(..))
+        {
+            return Err(crate::error::Error::InvalidArgument {
+                message: format!(
+                    "{} indices require a vector column, but '{}' is {}",
+                    index_type.to_uppercase(),
+                    column,
+                    field.data_type()
+                ),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+        Ok(())
+    }
+
+    /// Dimension and element byte width of a fixed-size-list vector column
+    fn vector_dimensions(&self, column: &str) -> Result<(usize, usize)> {
+        let schema = self.schema()?;
+        let field = schema.field_with_name(column)?;
+        match field.data_type() {
+            DataType::FixedSizeList(item, dim) => {
+                let element_size = item.data_type().primitive_width().unwrap_or(4);
+                Ok((*dim as usize, element_size))
+            }
+            other => Err(crate::error::Error::InvalidArgument {
+                message: format!(
+                    "column '{}' is not a vector column (found {})",
+                    column, other
+                ),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            }),
+        }
+    }
+
+    /// Pick the IVF partition count for an index build.
+    ///
+    /// An explicit count is used as-is. When none is given for an IVF index,
+    /// the count is derived from the current row count with
+    /// [`auto_num_partitions`].
+    fn resolve_num_partitions(
+        &self,
+        index_type: &str,
+        num_partitions: Option<u32>,
+    ) -> Result<Option<u32>> {
+        if num_partitions.is_some() || !index_type.to_uppercase().starts_with("IVF") {
+            return Ok(num_partitions);
+        }
+        let num_rows = RT.block_on(self.inner.count_rows(None))?;
+        Ok(Some(auto_num_partitions(num_rows)))
+    }
+
+    /// Start building an index on the runtime without blocking the caller.
+    /// Progress is reported through the returned job handle.
+    pub fn create_index_async(
+        &self,
+        column: &str,
+        index_type: &str,
+        metric: DistanceType,
+        num_partitions: Option<u32>,
+        num_sub_vectors: Option<u32>,
+        replace: bool,
+        options: &IndexOptions,
+    ) -> Result<IndexJobHandle> {
+        let column = &self.index_column(column, index_type);
+        let index = self.prepare_index(
+            column,
+            index_type,
+            metric,
+            num_partitions,
+            num_sub_vectors,
+            options,
+        )?;
+
+        let job = IndexJobHandle {
+            status: Arc::new(AtomicI32::new(INDEX_JOB_PENDING)),
+            error: Arc::new(Mutex::new(None)),
+        };
+
+        let table = self.inner.clone();
+        let column = column.to_string();
+        let status = job.status.clone();
+        let error = job.error.clone();
+        RT.spawn(async move {
+            status.store(INDEX_JOB_RUNNING, Ordering::SeqCst);
+            let result = table
+                .create_index(&[column.as_str()], index)
+                .replace(replace)
+                .execute()
+                .await;
+            match result {
+                Ok(_) => status.store(INDEX_JOB_DONE, Ordering::SeqCst),
+                Err(err) => {
+                    *error.lock().unwrap_or_else(PoisonError::into_inner) = Some(err.into());
+                    status.store(INDEX_JOB_ERROR, Ordering::SeqCst);
+                }
+            }
+        });
+
+        Ok(job)
+    }
+
     /// List all indices on the table
     pub fn list_indices(&self) -> Result<Vec<IndexConfig>> {
         let indices = RT.block_on(self.inner.list_indices())?;
         Ok(indices)
     }
 
+    /// Whether `column` is covered by an ANN (vector) index
+    pub fn has_vector_index(&self, column: &str) -> Result<bool> {
+        let indices = self.list_indices()?;
+        Ok(indices.iter().any(|idx| {
+            idx.columns.iter().any(|c| c == column)
+                && matches!(
+                    idx.index_type,
+                    IndexType::IvfPq | IndexType::IvfHnswPq | IndexType::IvfHnswSq
+                )
+        }))
+    }
+
+    /// Rebuild the index named `name` with new parameters.
+    ///
+    /// The replacement is built as a new index on the same column and
+    /// committed with `replace`, which swaps it for the old index in a single
+    /// table version. Readers see either the old index or the new one, never
+    /// an unindexed column.
+    ///
+    /// The replacement gets the default name `<column>_idx`, so only an index
+    /// with that name can be rebuilt; replacing one named otherwise would
+    /// leave the old index in place next to the new one.
+    pub fn rebuild_index(&self, name: &str, spec: &IndexSpec) -> Result<()> {
+        let existing = self
+            .list_indices()?
+            .into_iter()
+            .find(|idx| idx.name == name)
+            .ok_or_else(|| crate::error::Error::InvalidArgument {
+                message: format!("index '{}' not found", name),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            })?;
+        let column = match existing.columns.as_slice() {
+            [column] => column,
+            columns => {
+                return Err(crate::error::Error::InvalidArgument {
+                    message: format!(
+                        "index '{}' covers {} columns; only single-column indices can be rebuilt",
+                        name,
+                        columns.len()
+                    ),
+                    location: snafu::Location::new(file!(), line!(), column!()),
+                })
+            }
+        };
+        let default_name = format!("{}_idx", column);
+        if name != default_name {
+            return Err(crate::error::Error::InvalidArgument {
+                message: format!(
+                    "index '{}' has a custom name; only an index named '{}' can be rebuilt",
+                    name, default_name
+                ),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+
+        self.create_index(
+            column,
+            &spec.index_type,
+            spec.distance_type()?,
+            spec.num_partitions,
+            spec.num_sub_vectors,
+            true,
+            &spec.options,
+        )
+    }
+
     /// Delete rows matching a predicate
     pub fn delete_rows(&self, predicate: &str) -> Result<()> {
         RT.block_on(self.inner.delete(predicate))?;
         Ok(())
     }
 
-    /// Optimize the table to reclaim space after deletions
-    pub fn compact(&self) -> Result<()> {
-        use lancedb::table::{OptimizeAction, CompactionOptions};
-        let options = CompactionOptions::default();
-        RT.block_on(self.inner.optimize(OptimizeAction::Compact { 
-            options,
-            remap_options: None,
-        }))?;
+    /// Update rows matching `predicate` (or every row when it is `None`),
+    /// setting each column in `updates` to the result of its SQL expression
+    pub fn update(&self, predicate: Option<&str>, updates: &[(String, String)]) -> Result<()> {
+        if updates.is_empty() {
+            return Err(crate::error::Error::InvalidArgument {
+                message: "update requires at least one column".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+
+        let mut builder = self.inner.update();
+        if let Some(predicate) = predicate {
+            builder = builder.only_if(predicate);
+        }
+        for (column, expression) in updates {
+            builder = builder.column(column, expression);
+        }
+        RT.block_on(builder.execute())?;
         Ok(())
     }
-}
 
-// C API for tables
+    /// Add columns computed from SQL expressions over existing columns, e.g.
+    /// `("doubled", "value * 2")`. Each new column's type is inferred from
+    /// its expression.
+    pub fn add_columns(&self, definitions: &[(String, String)]) -> Result<()> {
+        use lancedb::table::NewColumnTransform;
+        if definitions.is_empty() {
+            return Err(crate::error::Error::InvalidArgument {
+                message: "at least one column definition is required".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+        let schema = self.schema()?;
+        for (name, _) in definitions {
+            if schema.field_with_name(name).is_ok() {
+                return Err(crate::error::Error::InvalidArgument {
+                    message: format!("column '{}' already exists", name),
+                    location: snafu::Location::new(file!(), line!(), column!()),
+                });
+            }
+        }
 
-/// Open an existing table.
-/// Returns a pointer to TableHandle on success, null on failure.
-#[no_mangle]
-pub extern "C" fn lancedb_table_open(
-    connection: *const super::connection::ConnectionHandle,
-    name: *const c_char,
-) -> *mut TableHandle {
-    if connection.is_null() || name.is_null() {
-        let error_msg = "connection and name cannot be null";
-        let c_error = CString::new(error_msg).unwrap();
-        crate::lancedb_set_last_error(c_error.as_ptr());
-        return std::ptr::null_mut();
+        let transform = NewColumnTransform::SqlExpressions(definitions.to_vec());
+        RT.block_on(self.inner.add_columns(transform, None))?;
+        Ok(())
     }
 
-    let connection = unsafe { &*connection };
-    let c_str = unsafe { CStr::from_ptr(name) };
-    let table_name = c_result!(c_str.to_str());
-
-    let handle = c_result!(TableHandle::open(connection, table_name));
-    Box::into_raw(Box::new(handle))
-}
+    /// Remove columns from the table. Every column must exist, and at least
+    /// one column must remain.
+    pub fn drop_columns(&self, columns: &[String]) -> Result<()> {
+        let schema = self.schema()?;
+        for column in columns {
+            schema.field_with_name(column)?;
+        }
+        let remaining = schema
+            .fields()
+            .iter()
+            .filter(|f| !columns.contains(f.name()))
+            .count();
+        if remaining == 0 {
+            return Err(crate::error::Error::InvalidArgument {
+                message: "cannot drop every column of a table".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
 
-/// Create a new table. For now, creates an empty table.
-/// Returns a pointer to TableHandle on success, null on failure.
-#[no_mangle]
-pub extern "C" fn lancedb_table_create(
-    connection: *const super::connection::ConnectionHandle,
-    name: *const c_char,
-) -> *mut TableHandle {
-    if connection.is_null() || name.is_null() {
-        let error_msg = "connection and name cannot be null";
-        let c_error = CString::new(error_msg).unwrap();
-        crate::lancedb_set_last_error(c_error.as_ptr());
-        return std::ptr::null_mut();
+        let cols: Vec<&str> = columns.iter().map(|c| c.as_str()).collect();
+        RT.block_on(self.inner.drop_columns(&cols))?;
+        Ok(())
     }
 
-    let connection = unsafe { &*connection };
-    let c_str = unsafe { CStr::from_ptr(name) };
-    let table_name = c_result!(c_str.to_str());
-
-    // Create a simple schema for demonstration
-    let schema = Arc::new(Schema::new(vec![
-        Field::new("id", DataType::Int32, false),
-        Field::new("text", DataType::Utf8, true),
-    ]));
+    /// Apply `alterations` to the table in a single commit. Each one can
+    /// rename a column, change its nullability, and/or cast it to a new type.
+    ///
+    /// Casts are checked against the stored data first: the alterations fail
+    /// if any value cannot be converted or would not survive a round trip
+    /// back to the original type (e.g. truncating 1.5 to an integer), so no
+    /// information is silently lost. The data is checked a batch at a time
+    /// as it is scanned.
+    pub fn alter_columns(&self, alterations: &[ColumnAlteration]) -> Result<()> {
+        let schema = self.schema()?;
+        let mut casts = Vec::new();
+        let mut altered = Vec::with_capacity(alterations.len());
+        for alteration in alterations {
+            let field = schema.field_with_name(&alteration.path)?;
+            // A cast to the column's own type is dropped rather than rewriting
+            // the column
+            let mut kept = ColumnAlteration::new(alteration.path.clone());
+            if let Some(new_name) = &alteration.rename {
+                kept = kept.rename(new_name.clone());
+            }
+            if let Some(nullable) = alteration.nullable {
+                kept = kept.set_nullable(nullable);
+            }
+            if let Some(to) = &alteration.data_type {
+                if to != field.data_type() {
+                    casts.push((field.name().as_str(), field.data_type(), to));
+                    kept = kept.cast_to(to.clone());
+                }
+            }
+            altered.push(kept);
+        }
+        self.check_lossless_casts(&casts)?;
 
-    let table = c_result!(RT.block_on(
-        connection
+        RT.block_on(self.inner.alter_columns(&altered))?;
+        Ok(())
+    }
+
+    /// Rename a column, change its nullability, and/or cast it to a new
+    /// type, as a single [`Self::alter_columns`] alteration
+    pub fn alter_column(
+        &self,
+        column: &str,
+        new_name: Option<&str>,
+        new_type: Option<DataType>,
+        nullable: Option<bool>,
+    ) -> Result<()> {
+        let mut alteration = ColumnAlteration::new(column.to_string());
+        if let Some(new_name) = new_name {
+            alteration = alteration.rename(new_name.to_string());
+        }
+        if let Some(nullable) = nullable {
+            alteration = alteration.set_nullable(nullable);
+        }
+        if let Some(new_type) = new_type {
+            alteration = alteration.cast_to(new_type);
+        }
+        self.alter_columns(&[alteration])
+    }
+
+    /// Verify every stored value of each `(column, from, to)` converts to
+    /// `to` and back to `from` unchanged, scanning the columns together
+    fn check_lossless_casts(&self, casts: &[(&str, &DataType, &DataType)]) -> Result<()> {
+        use arrow::compute::{cast_with_options, CastOptions};
+        if casts.is_empty() {
+            return Ok(());
+        }
+        let options = CastOptions {
+            safe: false,
+            ..Default::default()
+        };
+        let columns: Vec<&str> = casts.iter().map(|(column, _, _)| *column).collect();
+        let query = self
             .inner
-            .create_empty_table(table_name, schema)
-            .execute()
-    ));
-    let handle = TableHandle { inner: table };
-    Box::into_raw(Box::new(handle))
+            .query()
+            .select(lancedb::query::Select::columns(&columns));
+        let mut stream = RT.block_on(query.execute())?;
+        while let Some(batch) = RT.block_on(stream.next()) {
+            let batch = batch?;
+            for (column, from, to) in casts {
+                let values = batch.column_by_name(column).ok_or_else(|| {
+                    crate::error::Error::InvalidArgument {
+                        message: format!("column '{}' is missing from the scan", column),
+                        location: snafu::Location::new(file!(), line!(), column!()),
+                    }
+                })?;
+                let cast = cast_with_options(values, to, &options)?;
+                let round_trip = cast_with_options(&cast, from, &options)?;
+                if round_trip.as_ref() != values.as_ref() {
+                    return Err(crate::error::Error::InvalidArgument {
+                        message: format!(
+                            "casting column '{}' from {} to {} would lose information",
+                            column, from, to
+                        ),
+                        location: snafu::Location::new(file!(), line!(), column!()),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy rows matching `filter` from this table into `destination`.
+    /// The scan and the write both happen inside the runtime, so the data
+    /// never crosses the FFI boundary, and rows are written as they are read,
+    /// so the source is never held in memory as a whole. Both tables must
+    /// have the same fields, with the same types and nullability. Returns the
+    /// number of rows copied.
+    pub fn copy_into(
+        &self,
+        destination: &TableHandle,
+        filter: Option<&str>,
+        mode: AddDataMode,
+    ) -> Result<usize> {
+        if let Some(reason) = schema_mismatch(&self.schema()?, &destination.schema()?) {
+            return Err(crate::error::Error::InvalidArgument {
+                message: format!("source and destination schemas differ: {}", reason),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+
+        let query = self.inner.query();
+        let query = if let Some(filter) = filter {
+            query.only_if(filter)
+        } else {
+            query
+        };
+        let mut stream = RT.block_on(query.execute())?;
+        let schema = stream.schema();
+        let Some(first) = RT.block_on(stream.next()).transpose()? else {
+            // Nothing to append, but an overwrite still empties the destination
+            if matches!(mode, AddDataMode::Overwrite) {
+                let reader = RecordBatchIterator::new(std::iter::empty(), schema);
+                RT.block_on(destination.inner.add(Box::new(reader)).mode(mode).execute())?;
+            }
+            return Ok(0);
+        };
+
+        let rows = Arc::new(AtomicUsize::new(0));
+        let reader = BlockingStreamReader {
+            schema,
+            stream: futures::stream::iter([Ok(first)]).chain(stream).boxed(),
+            rows: rows.clone(),
+        };
+        destination.add_stream(Box::new(reader), mode)?;
+        Ok(rows.load(Ordering::SeqCst))
+    }
+
+    /// Look up the single row whose `key_column` equals `key_value`.
+    /// Scalar indices on the key column are used automatically by the scan.
+    pub fn get(
+        &self,
+        key_column: &str,
+        key_value: &serde_json::Value,
+    ) -> Result<Option<RecordBatch>> {
+        let filter = format!(
+            "{} = {}",
+            quote_identifier(key_column),
+            sql_literal(key_value)?
+        );
+        let query = self.inner.query().only_if(filter).limit(1);
+
+        let stream = RT.block_on(query.execute())?;
+        let batches: Vec<RecordBatch> = RT.block_on(async {
+            use futures::TryStreamExt;
+            stream.try_collect::<Vec<_>>().await
+        })?;
+
+        Ok(batches.into_iter().find(|b| b.num_rows() > 0))
+    }
+
+    /// Like [`Self::get`], but a missing key yields an empty batch with the
+    /// table's schema instead of `None`
+    pub fn get_by_key(
+        &self,
+        key_column: &str,
+        key_value: &serde_json::Value,
+    ) -> Result<RecordBatch> {
+        match self.get(key_column, key_value)? {
+            Some(batch) => Ok(batch),
+            None => Ok(RecordBatch::new_empty(self.schema()?)),
+        }
+    }
+
+    /// Fetch the rows with the given `_rowid`s, e.g. from a search run with
+    /// row ids, reading only `columns` when given.
+    ///
+    /// Rows come back in the order their ids are requested, with a `_rowid`
+    /// column. Ids of deleted or unknown rows are skipped rather than treated
+    /// as an error, and a repeated id yields its row once.
+    pub fn take(&self, row_ids: &[u64], columns: Option<&[String]>) -> Result<RecordBatch> {
+        if row_ids.is_empty() {
+            return Err(crate::error::Error::InvalidArgument {
+                message: "at least one row id is required".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+
+        let dataset = self.dataset()?;
+        let live = RT.block_on(live_row_ids(&dataset, row_ids))?;
+        let projection = match columns {
+            Some(columns) => dataset.schema().project(columns)?,
+            None => dataset.schema().clone(),
+        };
+        let batch = if live.is_empty() {
+            RecordBatch::new_empty(Arc::new(Schema::from(&projection)))
+        } else {
+            RT.block_on(dataset.take_rows(&live, projection))?
+        };
+
+        // take_rows returns rows in the order requested; add their ids
+        let mut fields = batch.schema().fields().to_vec();
+        fields.push(Arc::new(Field::new("_rowid", DataType::UInt64, true)));
+        let mut columns = batch.columns().to_vec();
+        columns.push(Arc::new(UInt64Array::from(live)));
+        Ok(RecordBatch::try_new(
+            Arc::new(Schema::new(fields)),
+            columns,
+        )?)
+    }
+
+    /// Measure mean recall@k of the ANN index on `column` against exact search.
+    ///
+    /// `query_vectors` holds the query vectors back to back, each `dim` floats
+    /// long. Every query is run twice, once through the index and once with
+    /// the index bypassed, and the overlap of the returned row ids is averaged.
+    pub fn evaluate_recall(
+        &self,
+        column: &str,
+        query_vectors: &[f32],
+        dim: usize,
+        k: usize,
+    ) -> Result<f64> {
+        if dim == 0 || k == 0 || query_vectors.is_empty() || query_vectors.len() % dim != 0 {
+            return Err(crate::error::Error::InvalidArgument {
+                message: "query_vectors must hold whole vectors and dim, k must be positive"
+                    .to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+
+        let mut total_recall = 0.0;
+        let mut num_queries = 0;
+        for vector in query_vectors.chunks(dim) {
+            let base = self
+                .inner
+                .query()
+                .nearest_to(vector.to_vec())?
+                .column(column)
+                .limit(k)
+                .with_row_id();
+            let approximate = collect_row_ids(base.clone())?;
+            let exact = collect_row_ids(base.bypass_vector_index())?;
+
+            let recall = if exact.is_empty() {
+                1.0
+            } else {
+                approximate.intersection(&exact).count() as f64 / exact.len() as f64
+            };
+            total_recall += recall;
+            num_queries += 1;
+        }
+
+        Ok(total_recall / num_queries as f64)
+    }
+
+    /// Find the `k` nearest neighbours of each query vector in the table's
+    /// vector column, running the searches concurrently.
+    ///
+    /// `query_vectors` holds the query vectors back to back, each `dim` floats
+    /// long, and `dim` must match the column. Every result row carries a
+    /// UInt32 `_query_index` column, the position of its query; results
+    /// come back grouped by query, in query order.
+    pub fn batch_search(
+        &self,
+        query_vectors: &[f32],
+        dim: usize,
+        k: usize,
+    ) -> Result<Vec<RecordBatch>> {
+        if dim == 0 || k == 0 || query_vectors.is_empty() || query_vectors.len() % dim != 0 {
+            return Err(crate::error::Error::InvalidArgument {
+                message: "query_vectors must hold whole vectors and dim, k must be positive"
+                    .to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+
+        let schema = self.schema()?;
+        let vector_columns: Vec<(&String, i32)> = schema
+            .fields()
+            .iter()
+            .filter_map(|field| match field.data_type() {
+                DataType::FixedSizeList(_, size) => Some((field.name(), *size)),
+                _ => None,
+            })
+            .collect();
+        let [(column, column_dim)] = vector_columns.as_slice() else {
+            return Err(crate::error::Error::InvalidArgument {
+                message: format!(
+                    "batch search requires exactly one vector column, found {}",
+                    vector_columns.len()
+                ),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        };
+        if *column_dim as usize != dim {
+            return Err(crate::error::Error::InvalidArgument {
+                message: format!(
+                    "query vectors have dimension {} but column '{}' has dimension {}",
+                    dim, column, column_dim
+                ),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+
+        let searches = query_vectors
+            .chunks(dim)
+            .enumerate()
+            .map(|(i, vector)| {
+                let query = self
+                    .inner
+                    .query()
+                    .nearest_to(vector.to_vec())?
+                    .column(column.as_str())
+                    .limit(k);
+                Ok(async move {
+                    use futures::TryStreamExt;
+                    let batches: Vec<RecordBatch> = query.execute().await?.try_collect().await?;
+                    batches
+                        .iter()
+                        .map(|batch| tag_query_index(batch, i as u32))
+                        .collect::<Result<Vec<_>>>()
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let results: Vec<Vec<RecordBatch>> = RT.block_on(async {
+            use futures::TryStreamExt;
+            futures::stream::iter(searches)
+                .buffered(BATCH_SEARCH_CONCURRENCY)
+                .try_collect()
+                .await
+        })?;
+        Ok(results.into_iter().flatten().collect())
+    }
+
+    /// Read every row still present in storage, including rows that were
+    /// deleted but not yet removed by compaction. A trailing `_deleted`
+    /// boolean column marks the tombstoned rows.
+    pub fn scan_with_deleted(&self) -> Result<Vec<RecordBatch>> {
+        let dataset = self.dataset()?;
+        let mut scanner = dataset.scan();
+        // Deleted rows come back with a null row id
+        scanner.with_row_id().include_deleted_rows();
+
+        let stream = RT.block_on(scanner.try_into_stream())?;
+        let batches: Vec<RecordBatch> = RT.block_on(async {
+            use futures::TryStreamExt;
+            stream.try_collect::<Vec<_>>().await
+        })?;
+
+        batches.iter().map(mark_deleted_rows).collect()
+    }
+
+    /// Update rows whose key columns match a row in `batch` and insert the
+    /// rest. Any number of key columns may be used, e.g. `(tenant_id, doc_id)`.
+    pub fn upsert(&self, batch: RecordBatch, key_columns: &[String]) -> Result<()> {
+        self.merge_insert(batch, key_columns, true, true)
+    }
+
+    /// Merge `batch` into the table, matching rows on `on_columns`.
+    /// Matched rows are replaced when `update_matched` is set, and unmatched
+    /// rows are inserted when `insert_not_matched` is set.
+    pub fn merge_insert(
+        &self,
+        batch: RecordBatch,
+        on_columns: &[String],
+        update_matched: bool,
+        insert_not_matched: bool,
+    ) -> Result<()> {
+        if !update_matched && !insert_not_matched {
+            return Err(crate::error::Error::InvalidArgument {
+                message: "merge_insert needs at least one of update_matched or insert_not_matched"
+                    .to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+        let batch = self.embed_batch(batch)?;
+        let table_schema = self.schema()?;
+        if let Some(reason) = schema_mismatch(&batch.schema(), &table_schema) {
+            return Err(crate::error::Error::InvalidArgument {
+                message: format!("batch schema differs from table schema: {}", reason),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+
+        let keys: Vec<&str> = on_columns.iter().map(|c| c.as_str()).collect();
+        let mut builder = self.inner.merge_insert(&keys);
+        if update_matched {
+            builder.when_matched_update_all(None);
+        }
+        if insert_not_matched {
+            builder.when_not_matched_insert_all();
+        }
+
+        let schema = batch.schema();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        RT.block_on(builder.execute(Box::new(reader)))?;
+        Ok(())
+    }
+
+    /// Collect storage statistics for the version of the table the handle
+    /// reads
+    pub fn stats(&self) -> Result<TableStats> {
+        let dataset = self.dataset()?;
+        let indices = RT.block_on(dataset.load_indices())?;
+        let mut stats = TableStats::default();
+        for fragment in dataset.get_fragments() {
+            let rows = RT.block_on(fragment.count_rows())?;
+            stats.num_fragments += 1;
+            stats.num_rows += rows;
+            stats.num_deleted_rows += RT.block_on(fragment.count_deletions())?;
+            let indexed = indices.iter().any(|idx| {
+                idx.fragment_bitmap
+                    .as_ref()
+                    .is_some_and(|bitmap| bitmap.contains(fragment.id() as u32))
+            });
+            if indexed {
+                stats.num_indexed_rows += rows;
+            }
+        }
+
+        let (store, base) = RT.block_on(ObjectStore::from_uri_and_params(
+            Arc::new(ObjectStoreRegistry::default()),
+            self.inner.dataset_uri(),
+            &self.object_store_params(),
+        ))?;
+        stats.total_bytes = RT.block_on(async {
+            let mut files = store.read_dir_all(&base, None).await?;
+            let mut total = 0u64;
+            while let Some(file) = files.next().await {
+                total += file?.size as u64;
+            }
+            Ok::<_, lance::Error>(total)
+        })?;
+        Ok(stats)
+    }
+
+    /// Report how much of the table the index named `index_name` covers and
+    /// how it was trained. Rows appended since the index was built count as
+    /// unindexed until it is optimized or rebuilt.
+    pub fn index_stats(&self, index_name: &str) -> Result<IndexStats> {
+        let config = self
+            .list_indices()?
+            .into_iter()
+            .find(|idx| idx.name == index_name)
+            .ok_or_else(|| crate::error::Error::InvalidArgument {
+                message: format!("no index is named '{}'", index_name),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            })?;
+
+        let dataset = self.dataset()?;
+        let indices = RT.block_on(dataset.load_indices())?;
+        let mut stats = IndexStats {
+            index_type: index_type_name(&config.index_type).to_string(),
+            ..Default::default()
+        };
+        for fragment in dataset.get_fragments() {
+            let rows = RT.block_on(fragment.count_rows())?;
+            let indexed = indices.iter().any(|idx| {
+                idx.name == index_name
+                    && idx
+                        .fragment_bitmap
+                        .as_ref()
+                        .is_some_and(|bitmap| bitmap.contains(fragment.id() as u32))
+            });
+            if indexed {
+                stats.num_indexed_rows += rows;
+            } else {
+                stats.num_unindexed_rows += rows;
+            }
+        }
+
+        let parameters = index_parameters(&dataset, index_name)?;
+        stats.distance_type = parameters.distance_type;
+        stats.num_partitions = parameters.num_partitions;
+        Ok(stats)
+    }
+
+    /// List the table's indices with the parameters vector indices were
+    /// trained with, as written by lancedb_table_list_indices.
+    ///
+    /// The parameters come from Lance's own statistics and are best effort:
+    /// when they cannot be read they are left as `None` rather than failing
+    /// the listing, which LanceDB answers on its own.
+    pub fn describe_indices(&self) -> Result<Vec<IndexDescription>> {
+        let indices = self.list_indices()?;
+        let is_vector = |config: &IndexConfig| {
+            matches!(
+                config.index_type,
+                IndexType::IvfPq | IndexType::IvfHnswPq | IndexType::IvfHnswSq
+            )
+        };
+        let dataset = if indices.iter().any(is_vector) {
+            self.dataset().ok()
+        } else {
+            None
+        };
+
+        let mut descriptions = Vec::with_capacity(indices.len());
+        for config in indices {
+            let parameters = match &dataset {
+                Some(dataset) if is_vector(&config) => {
+                    index_parameters(dataset, &config.name).unwrap_or_default()
+                }
+                _ => IndexParameters::default(),
+            };
+            descriptions.push(IndexDescription {
+                index_type: index_type_name(&config.index_type),
+                name: config.name,
+                columns: config.columns,
+                parameters,
+            });
+        }
+        Ok(descriptions)
+    }
+
+    /// Block until every index in `names` exists and covers all live rows,
+    /// failing with a TimedOut error once `timeout` has elapsed.
+    ///
+    /// An index that does not exist yet counts as not ready, so this also
+    /// waits for builds started with [`Self::create_index_async`]. Any other
+    /// failure to read an index's statistics is returned immediately.
+    pub fn wait_for_index(&self, names: &[String], timeout: Duration) -> Result<()> {
+        // A timeout too long to represent never elapses
+        let deadline = Instant::now().checked_add(timeout);
+        loop {
+            let existing = self.list_indices()?;
+            let mut pending = Vec::new();
+            for name in names {
+                let ready = existing.iter().any(|idx| &idx.name == name)
+                    && self.index_stats(name)?.num_unindexed_rows == 0;
+                if !ready {
+                    pending.push(name.as_str());
+                }
+            }
+            if pending.is_empty() {
+                return Ok(());
+            }
+
+            let now = Instant::now();
+            if deadline.is_some_and(|deadline| now >= deadline) {
+                return Err(crate::error::Error::TimedOut {
+                    message: format!(
+                        "indices not fully built after {} ms: {}",
+                        timeout.as_millis(),
+                        pending.join(", ")
+                    ),
+                    location: snafu::Location::new(file!(), line!(), column!()),
+                });
+            }
+            let remaining = deadline.map_or(INDEX_POLL_INTERVAL, |deadline| deadline - now);
+            std::thread::sleep(INDEX_POLL_INTERVAL.min(remaining));
+        }
+    }
+
+    /// Open the Lance dataset backing this table at the version the handle
+    /// reads, so a handle pinned with `checkout` sees its pinned version
+    fn dataset(&self) -> Result<lance::Dataset> {
+        self.open_dataset(Some(self.version()?))
+    }
+
+    /// Open the Lance dataset at its latest version, to commit against
+    fn latest_dataset(&self) -> Result<lance::Dataset> {
+        self.open_dataset(None)
+    }
+
+    fn open_dataset(&self, version: Option<u64>) -> Result<lance::Dataset> {
+        let mut builder = DatasetBuilder::from_uri(self.inner.dataset_uri())
+            .with_storage_options(self.storage_options.clone());
+        if let Some(version) = version {
+            builder = builder.with_version(version);
+        }
+        Ok(RT.block_on(builder.load())?)
+    }
+
+    /// Object store parameters carrying the connection's storage options
+    fn object_store_params(&self) -> ObjectStoreParams {
+        ObjectStoreParams {
+            storage_options: Some(self.storage_options.clone()),
+            ..Default::default()
+        }
+    }
+
+    /// Commit fragments that were written externally (e.g. by distributed
+    /// workers) into the next version of the table.
+    pub fn append_fragments(&self, fragments: Vec<Fragment>) -> Result<()> {
+        if fragments.is_empty() {
+            return Err(crate::error::Error::InvalidArgument {
+                message: "at least one fragment is required".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+
+        let dataset = self.latest_dataset()?;
+        let operation = Operation::Append { fragments };
+        RT.block_on(lance::Dataset::commit(
+            self.inner.dataset_uri(),
+            operation,
+            Some(dataset.version().version),
+            Some(self.object_store_params()),
+            None,
+            Arc::new(ObjectStoreRegistry::default()),
+        ))?;
+        // Move the handle to the version we just committed
+        RT.block_on(self.inner.checkout_latest())?;
+        Ok(())
+    }
+
+    /// Drop the index named `name`, as reported by `list_indices`.
+    /// The index files are left for cleanup; only the manifest entry is removed.
+    pub fn drop_index(&self, name: &str) -> Result<()> {
+        let dataset = self.latest_dataset()?;
+        let indices = RT.block_on(dataset.load_indices())?;
+        let removed: Vec<_> = indices
+            .iter()
+            .filter(|idx| idx.name == name)
+            .cloned()
+            .collect();
+        if removed.is_empty() {
+            return Err(crate::error::Error::InvalidArgument {
+                message: format!("index '{}' not found", name),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+
+        let operation = Operation::CreateIndex {
+            new_indices: vec![],
+            removed_indices: removed,
+        };
+        RT.block_on(lance::Dataset::commit(
+            self.inner.dataset_uri(),
+            operation,
+            Some(dataset.version().version),
+            Some(self.object_store_params()),
+            None,
+            Arc::new(ObjectStoreRegistry::default()),
+        ))?;
+        RT.block_on(self.inner.checkout_latest())?;
+        Ok(())
+    }
+
+    /// Read the custom key/value metadata stored in the table's manifest
+    pub fn get_metadata(&self) -> Result<BTreeMap<String, String>> {
+        let dataset = self.dataset()?;
+        Ok(dataset
+            .manifest()
+            .config
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect())
+    }
+
+    /// Set a metadata key on the table. This commits a new table version.
+    pub fn set_metadata(&self, key: &str, value: &str) -> Result<()> {
+        let mut dataset = self.latest_dataset()?;
+        RT.block_on(dataset.update_config([(key.to_string(), value.to_string())]))?;
+        RT.block_on(self.inner.checkout_latest())?;
+        Ok(())
+    }
+
+    /// Optimize the table to reclaim space after deletions
+    pub fn compact(&self) -> Result<()> {
+        use lancedb::table::{OptimizeAction, CompactionOptions};
+        let options = CompactionOptions::default();
+        RT.block_on(self.inner.optimize(OptimizeAction::Compact { 
+            options,
+            remap_options: None,
+        }))?;
+        Ok(())
+    }
+
+    /// Compact small fragments and/or fold unindexed rows into the table's
+    /// indices. Blocks until all requested work has been committed.
+    pub fn optimize(&self, mode: OptimizeMode) -> Result<OptimizeSummary> {
+        use lancedb::table::{CompactionOptions, OptimizeAction, OptimizeOptions};
+        let mut summary = OptimizeSummary::default();
+        if matches!(mode, OptimizeMode::All | OptimizeMode::Compact) {
+            let stats = RT.block_on(self.inner.optimize(OptimizeAction::Compact {
+                options: CompactionOptions::default(),
+                remap_options: None,
+            }))?;
+            if let Some(metrics) = stats.compaction {
+                summary.fragments_removed = metrics.fragments_removed;
+                summary.fragments_added = metrics.fragments_added;
+                summary.files_removed = metrics.files_removed;
+                summary.files_added = metrics.files_added;
+            }
+        }
+        if matches!(mode, OptimizeMode::All | OptimizeMode::Index) {
+            RT.block_on(
+                self.inner
+                    .optimize(OptimizeAction::Index(OptimizeOptions::default())),
+            )?;
+        }
+        Ok(summary)
+    }
 }
 
-/// Close a table and free resources.
-#[no_mangle]
-pub extern "C" fn lancedb_table_close(handle: *mut TableHandle) {
-    if !handle.is_null() {
-        let _ = unsafe { Box::from_raw(handle) };
+/// Work performed by [`TableHandle::optimize`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptimizeMode {
+    /// Compaction followed by index optimization
+    All,
+    /// Merge small fragments into larger ones
+    Compact,
+    /// Add rows appended since the last index build to the indices
+    Index,
+}
+
+/// Compaction statistics reported by lancedb_table_optimize
+#[derive(Debug, Default, Serialize)]
+pub struct OptimizeSummary {
+    pub fragments_removed: usize,
+    pub fragments_added: usize,
+    pub files_removed: usize,
+    pub files_added: usize,
+}
+
+/// Storage statistics reported by lancedb_table_stats
+#[derive(Debug, Default, Serialize)]
+pub struct TableStats {
+    pub num_rows: usize,
+    pub num_fragments: usize,
+    /// Rows deleted but still stored until compaction removes them
+    pub num_deleted_rows: usize,
+    /// Live rows in fragments covered by at least one index
+    pub num_indexed_rows: usize,
+    /// Size of every file under the table's directory: data, deletion
+    /// files, indices, and manifests of all retained versions
+    pub total_bytes: u64,
+}
+
+/// Coverage and training parameters of one index, reported by
+/// lancedb_table_index_stats
+#[derive(Debug, Default, Serialize)]
+pub struct IndexStats {
+    /// The index type as listed by lancedb_table_list_indices, e.g. "IVF_PQ"
+    pub index_type: String,
+    /// Live rows in fragments the index covers
+    pub num_indexed_rows: usize,
+    /// Live rows added since the index was last built or optimized
+    pub num_unindexed_rows: usize,
+    /// Distance the index was trained with; null for scalar indices
+    pub distance_type: Option<String>,
+    /// IVF partitions of a vector index; null for scalar indices
+    pub num_partitions: Option<usize>,
+}
+
+/// Parse a type name such as "float32", "int64", or "string" into an Arrow
+/// type. Names in Arrow's own display format (e.g. "Float32") also work.
+pub(crate) fn parse_data_type(name: &str) -> Result<DataType> {
+    let data_type = match name.to_lowercase().as_str() {
+        "bool" | "boolean" => DataType::Boolean,
+        "int8" => DataType::Int8,
+        "int16" => DataType::Int16,
+        "int32" => DataType::Int32,
+        "int64" => DataType::Int64,
+        "uint8" => DataType::UInt8,
+        "uint16" => DataType::UInt16,
+        "uint32" => DataType::UInt32,
+        "uint64" => DataType::UInt64,
+        "float16" => DataType::Float16,
+        "float32" | "float" => DataType::Float32,
+        "float64" | "double" => DataType::Float64,
+        "utf8" | "string" => DataType::Utf8,
+        "large_utf8" | "large_string" => DataType::LargeUtf8,
+        "binary" => DataType::Binary,
+        "large_binary" => DataType::LargeBinary,
+        "date32" => DataType::Date32,
+        "date64" => DataType::Date64,
+        _ => name
+            .parse::<DataType>()
+            .map_err(|_| crate::error::Error::InvalidArgument {
+                message: format!("unknown data type '{}'", name),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            })?,
+    };
+    Ok(data_type)
+}
+
+/// Quote a column name for use in a filter predicate, doubling any
+/// backticks it contains
+pub(crate) fn quote_identifier(name: &str) -> String {
+    format!("`{}`", name.replace('`', "``"))
+}
+
+/// Render a JSON scalar as a SQL literal for use in a filter predicate
+pub(crate) fn sql_literal(value: &serde_json::Value) -> Result<String> {
+    match value {
+        serde_json::Value::Number(n) => Ok(n.to_string()),
+        serde_json::Value::Bool(b) => Ok(b.to_string()),
+        serde_json::Value::String(s) => Ok(format!("'{}'", s.replace('\'', "''"))),
+        _ => Err(crate::error::Error::InvalidArgument {
+            message: format!(
+                "key value must be a number, string, or boolean, got {}",
+                value
+            ),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        }),
     }
 }
 
-/// Get the number of rows in a table.
-/// Returns the count on success, -1 on failure.
-#[no_mangle]
-pub extern "C" fn lancedb_table_count_rows(handle: *const TableHandle) -> i64 {
-    if handle.is_null() {
-        let error_msg = "table handle cannot be null";
-        let c_error = CString::new(error_msg).unwrap();
-        crate::lancedb_set_last_error(c_error.as_ptr());
-        return -1;
+/// Reads an async query stream as the blocking reader LanceDB writes from,
+/// counting the rows it hands out
+struct BlockingStreamReader {
+    schema: SchemaRef,
+    stream: BoxStream<'static, lancedb::Result<RecordBatch>>,
+    rows: Arc<AtomicUsize>,
+}
+
+impl Iterator for BlockingStreamReader {
+    type Item = std::result::Result<RecordBatch, ArrowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // The writer pulls batches from inside the runtime, so leave it
+        // before blocking on the scan
+        let batch = tokio::task::block_in_place(|| RT.handle().block_on(self.stream.next()))?;
+        Some(
+            batch
+                .inspect(|batch| {
+                    self.rows.fetch_add(batch.num_rows(), Ordering::SeqCst);
+                })
+                .map_err(|e| ArrowError::ExternalError(Box::new(e))),
+        )
     }
+}
 
-    let table = unsafe { &*handle };
-    match table.count_rows() {
-        Ok(count) => count,
-        Err(err) => {
-            let error_msg = format!("{}", err);
-            let c_error = CString::new(error_msg).unwrap();
-            crate::lancedb_set_last_error(c_error.as_ptr());
-            -1
+impl RecordBatchReader for BlockingStreamReader {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
+
+/// The distinct ids of `row_ids`, in order, that address a live row of
+/// `dataset`. Row ids are the fragment id in the upper 32 bits and the row's
+/// offset in the fragment in the lower 32.
+async fn live_row_ids(dataset: &lance::Dataset, row_ids: &[u64]) -> lance::Result<Vec<u64>> {
+    let fragments: HashMap<u64, _> = dataset
+        .get_fragments()
+        .into_iter()
+        .map(|fragment| (fragment.id() as u64, fragment))
+        .collect();
+    let mut checked = HashMap::new();
+    let mut seen = HashSet::new();
+    let mut live = Vec::new();
+    for &row_id in row_ids {
+        let (fragment_id, offset) = (row_id >> 32, row_id as u32);
+        let Some(fragment) = fragments.get(&fragment_id) else {
+            continue;
+        };
+        if !seen.insert(row_id) {
+            continue;
+        }
+        if !checked.contains_key(&fragment_id) {
+            let rows = fragment.physical_rows().await?;
+            let deletions = fragment.get_deletion_vector().await?;
+            checked.insert(fragment_id, (rows, deletions));
+        }
+        let (rows, deletions) = &checked[&fragment_id];
+        let deleted = deletions
+            .as_ref()
+            .is_some_and(|deletions| deletions.contains(offset));
+        if (offset as usize) < *rows && !deleted {
+            live.push(row_id);
         }
     }
+    Ok(live)
 }
 
-/// Add data to a table from Arrow C Data Interface structures.
-/// Returns 0 on success, -1 on failure.
-/// mode: 0 = Append, 1 = Overwrite
-#[no_mangle]
-pub extern "C" fn lancedb_table_add(
-    handle: *const TableHandle,
-    array: *mut FFI_ArrowArray,
-    schema: *mut FFI_ArrowSchema,
-    mode: c_int,
-) -> c_int {
-    if handle.is_null() || array.is_null() || schema.is_null() {
-        let error_msg = "table handle, array, and schema cannot be null";
-        let c_error = CString::new(error_msg).unwrap();
-        crate::lancedb_set_last_error(c_error.as_ptr());
-        return -1;
+/// Append a `_query_index` column holding `index` to a batch search result
+fn tag_query_index(batch: &RecordBatch, index: u32) -> Result<RecordBatch> {
+    let mut fields = batch.schema().fields().to_vec();
+    fields.push(Arc::new(Field::new(
+        QUERY_INDEX_COLUMN,
+        DataType::UInt32,
+        false,
+    )));
+    let mut columns = batch.columns().to_vec();
+    columns.push(Arc::new(UInt32Array::from(vec![index; batch.num_rows()])));
+    Ok(RecordBatch::try_new(
+        Arc::new(Schema::new(fields)),
+        columns,
+    )?)
+}
+
+/// Run a vector query and collect the `_rowid` of every result
+fn collect_row_ids(query: VectorQuery) -> Result<HashSet<u64>> {
+    let stream = RT.block_on(query.execute())?;
+    let batches: Vec<RecordBatch> = RT.block_on(async {
+        use futures::TryStreamExt;
+        stream.try_collect::<Vec<_>>().await
+    })?;
+
+    let mut row_ids = HashSet::new();
+    for batch in &batches {
+        let column = batch
+            .column_by_name("_rowid")
+            .and_then(|c| c.as_any().downcast_ref::<UInt64Array>())
+            .ok_or_else(|| crate::error::Error::Arrow {
+                message: "query results are missing the _rowid column".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            })?;
+        row_ids.extend(column.values().iter().copied());
     }
+    Ok(row_ids)
+}
 
-    let table = unsafe { &*handle };
+/// Replace the `_rowid` column of a scan that included deleted rows with a
+/// `_deleted` flag, which is set for rows whose row id is null
+fn mark_deleted_rows(batch: &RecordBatch) -> Result<RecordBatch> {
+    let schema = batch.schema();
+    let row_id_index = schema.index_of("_rowid")?;
+    let deleted = arrow::compute::is_null(batch.column(row_id_index))?;
 
-    // Import the record batch from C
-    let batch = match unsafe { import_record_batch_from_c(array, schema) } {
-        Ok(b) => b,
-        Err(err) => {
-            let error_msg = format!("{}", err);
-            let c_error = CString::new(error_msg).unwrap();
-            crate::lancedb_set_last_error(c_error.as_ptr());
-            return -1;
+    let mut fields: Vec<Arc<Field>> = Vec::with_capacity(batch.num_columns());
+    let mut columns = Vec::with_capacity(batch.num_columns());
+    for (i, field) in schema.fields().iter().enumerate() {
+        if i != row_id_index {
+            fields.push(field.clone());
+            columns.push(batch.column(i).clone());
         }
-    };
+    }
+    fields.push(Arc::new(Field::new("_deleted", DataType::Boolean, false)));
+    columns.push(Arc::new(deleted));
+
+    let schema = Schema::new_with_metadata(fields, schema.metadata().clone());
+    Ok(RecordBatch::try_new(Arc::new(schema), columns)?)
+}
+
+/// Whether an error means another writer committed a conflicting version first
+fn is_commit_conflict(err: &lancedb::Error) -> bool {
+    matches!(
+        err,
+        lancedb::Error::Lance {
+            source: lance::Error::CommitConflict { .. }
+        }
+    )
+}
+
+/// Run `commit`, and while it fails with a commit conflict, `refresh` the
+/// table and try again, up to `max_retries` more times. The backoff between
+/// attempts waits on the runtime's timer rather than blocking a thread.
+async fn retry_on_conflict<C, R>(
+    max_retries: u32,
+    mut commit: impl FnMut() -> C,
+    mut refresh: impl FnMut() -> R,
+) -> lancedb::Result<()>
+where
+    C: std::future::Future<Output = lancedb::Result<()>>,
+    R: std::future::Future<Output = lancedb::Result<()>>,
+{
+    let mut attempt = 0;
+    loop {
+        match commit().await {
+            Err(err) if attempt < max_retries && is_commit_conflict(&err) => {
+                attempt += 1;
+                tokio::time::sleep(Duration::from_millis(10 * attempt as u64)).await;
+                refresh().await?;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Upper bound for automatically chosen IVF partition counts
+const MAX_AUTO_PARTITIONS: u32 = 4096;
+
+/// Heuristic IVF partition count for a table with `num_rows` rows.
+///
+/// Uses `sqrt(num_rows)`, which keeps both the number of centroids and the
+/// number of vectors per partition growing sub-linearly with the data. The
+/// result is clamped to `[1, MAX_AUTO_PARTITIONS]` so tiny tables still get a
+/// valid index and huge tables don't spend forever training k-means.
+pub(crate) fn auto_num_partitions(num_rows: usize) -> u32 {
+    let partitions = (num_rows as f64).sqrt().round() as u32;
+    partitions.clamp(1, MAX_AUTO_PARTITIONS)
+}
+
+/// Additional index build options, passed as JSON to lancedb_table_create_index_ex
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IndexOptions {
+    /// Upper bound in bytes for the vectors sampled to train the index
+    pub memory_limit: Option<u64>,
+    /// FTS only: language used for stemming. LanceDB 0.10 cannot configure
+    /// its tokenizer, so only "English" is accepted.
+    pub language: Option<String>,
+    /// FTS only: whether tokens are lowercased; only `true` is accepted
+    pub lower_case: Option<bool>,
+    /// FTS only: whether tokens are reduced to their stem; only `true` is
+    /// accepted
+    pub stem: Option<bool>,
+    /// HNSW only: number of neighbors each node links to in the graph
+    pub m: Option<u32>,
+    /// HNSW only: size of the candidate list used while building the graph
+    pub ef_construction: Option<u32>,
+}
+
+impl IndexOptions {
+    fn has_tokenizer_options(&self) -> bool {
+        self.language.is_some() || self.lower_case.is_some() || self.stem.is_some()
+    }
+
+    fn has_hnsw_options(&self) -> bool {
+        self.m.is_some() || self.ef_construction.is_some()
+    }
+}
+
+/// One entry of the JSON array written by lancedb_table_list_indices
+#[derive(Debug, Serialize)]
+pub struct IndexDescription {
+    pub name: String,
+    /// The name the index type is created with, e.g. "IVF_PQ"
+    #[serde(rename = "type")]
+    pub index_type: &'static str,
+    pub columns: Vec<String>,
+    #[serde(flatten)]
+    pub parameters: IndexParameters,
+}
+
+/// Parameters a vector index was trained with; every field is null for
+/// scalar and full-text indices
+#[derive(Debug, Default, Serialize)]
+pub struct IndexParameters {
+    /// Distance the index was trained with, e.g. "l2" or "cosine"
+    pub distance_type: Option<String>,
+    pub num_partitions: Option<usize>,
+    /// PQ sub-vectors; null for indices that are not product quantized
+    pub num_sub_vectors: Option<usize>,
+}
+
+/// Read how the index `index_name` was trained from Lance's statistics,
+/// which report them per index delta; the first delta's are used
+fn index_parameters(dataset: &lance::Dataset, index_name: &str) -> Result<IndexParameters> {
+    let details: serde_json::Value =
+        serde_json::from_str(&RT.block_on(dataset.index_statistics(index_name))?)?;
+    let Some(first) = details["indices"].get(0) else {
+        return Ok(IndexParameters::default());
+    };
+    let as_usize = |value: &serde_json::Value| value.as_u64().map(|n| n as usize);
+    Ok(IndexParameters {
+        distance_type: first["metric_type"]
+            .as_str()
+            .or_else(|| first["distance_type"].as_str())
+            .map(|d| d.to_lowercase()),
+        num_partitions: as_usize(&first["num_partitions"])
+            .or_else(|| first["partitions"].as_array().map(|p| p.len())),
+        num_sub_vectors: as_usize(&first["num_sub_vectors"])
+            .or_else(|| as_usize(&first["sub_index"]["num_sub_vectors"])),
+    })
+}
+
+/// The name an index type is created with, e.g. "IVF_PQ"
+fn index_type_name(index_type: &IndexType) -> &'static str {
+    match index_type {
+        IndexType::IvfPq => "IVF_PQ",
+        IndexType::IvfHnswPq => "IVF_HNSW_PQ",
+        IndexType::IvfHnswSq => "IVF_HNSW_SQ",
+        IndexType::BTree => "BTREE",
+        IndexType::Bitmap => "BITMAP",
+        IndexType::LabelList => "LABEL_LIST",
+        IndexType::FTS => "FTS",
+    }
+}
+
+/// Full description of an index, passed as JSON to lancedb_table_rebuild_index
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IndexSpec {
+    pub index_type: String,
+    /// Distance metric (0=L2, 1=Cosine, 2=Dot); defaults to L2
+    #[serde(default)]
+    pub metric: i32,
+    pub num_partitions: Option<u32>,
+    pub num_sub_vectors: Option<u32>,
+    #[serde(default)]
+    pub options: IndexOptions,
+}
+
+impl IndexSpec {
+    fn distance_type(&self) -> Result<DistanceType> {
+        match self.metric {
+            0 => Ok(DistanceType::L2),
+            1 => Ok(DistanceType::Cosine),
+            2 => Ok(DistanceType::Dot),
+            _ => Err(crate::error::Error::InvalidArgument {
+                message: "invalid metric: must be 0 (L2), 1 (Cosine), or 2 (Dot)".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            }),
+        }
+    }
+}
+
+/// Default number of training vectors sampled per centroid
+const DEFAULT_SAMPLE_RATE: u64 = 256;
+/// Number of centroids trained per PQ sub-vector (8-bit codes)
+const PQ_CENTROIDS: u64 = 256;
+
+/// Largest training sample rate whose sampled vectors fit in `memory_limit`.
+///
+/// Both IVF and PQ training run k-means over `sample_rate` vectors per
+/// centroid, so the sample holds `sample_rate * max(num_partitions, 256)`
+/// vectors. The rate is capped at the default of 256, and an error is
+/// returned if not even one vector per centroid fits.
+pub(crate) fn training_sample_rate(
+    memory_limit: u64,
+    num_partitions: u32,
+    dim: usize,
+    element_size: usize,
+) -> Result<u32> {
+    let centroids = (num_partitions as u64).max(PQ_CENTROIDS);
+    let bytes_per_vector = (dim * element_size) as u64;
+    let sample_rate = memory_limit / (centroids * bytes_per_vector);
+    if sample_rate == 0 {
+        return Err(crate::error::Error::InvalidArgument {
+            message: format!(
+                "memory_limit of {} bytes is too small to train an index with {} centroids; at least {} bytes are needed",
+                memory_limit,
+                centroids,
+                centroids * bytes_per_vector
+            ),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+    }
+    Ok(sample_rate.min(DEFAULT_SAMPLE_RATE) as u32)
+}
+
+/// Build the index definition for the given index type and parameters
+fn build_index(
+    index_type: &str,
+    metric: DistanceType,
+    num_partitions: Option<u32>,
+    num_sub_vectors: Option<u32>,
+    sample_rate: Option<u32>,
+    options: &IndexOptions,
+) -> Result<Index> {
+    let is_fts = index_type.eq_ignore_ascii_case("FTS");
+    if options.has_tokenizer_options() && !is_fts {
+        return Err(crate::error::Error::InvalidArgument {
+            message: format!(
+                "tokenizer options are only supported for FTS indices, not {}",
+                index_type
+            ),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+    }
+    if options.has_hnsw_options() && !index_type.to_uppercase().starts_with("IVF_HNSW") {
+        return Err(crate::error::Error::InvalidArgument {
+            message: format!(
+                "m and ef_construction are only supported for HNSW indices, not {}",
+                index_type
+            ),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+    }
+
+    let index = match index_type.to_uppercase().as_str() {
+        "IVF_PQ" => {
+            let mut builder = IvfPqIndexBuilder::default().distance_type(metric);
+            if let Some(partitions) = num_partitions {
+                builder = builder.num_partitions(partitions);
+            }
+            if let Some(sub_vectors) = num_sub_vectors {
+                builder = builder.num_sub_vectors(sub_vectors);
+            }
+            if let Some(sample_rate) = sample_rate {
+                builder = builder.sample_rate(sample_rate);
+            }
+            Index::IvfPq(builder)
+        }
+        "IVF_HNSW_SQ" => {
+            let mut builder = IvfHnswSqIndexBuilder::default().distance_type(metric);
+            if let Some(partitions) = num_partitions {
+                builder = builder.num_partitions(partitions);
+            }
+            if let Some(sample_rate) = sample_rate {
+                builder = builder.sample_rate(sample_rate);
+            }
+            if let Some(m) = options.m {
+                builder = builder.num_edges(m);
+            }
+            if let Some(ef_construction) = options.ef_construction {
+                builder = builder.ef_construction(ef_construction);
+            }
+            Index::IvfHnswSq(builder)
+        }
+        "IVF_HNSW_PQ" => {
+            let mut builder = IvfHnswPqIndexBuilder::default().distance_type(metric);
+            if let Some(partitions) = num_partitions {
+                builder = builder.num_partitions(partitions);
+            }
+            if let Some(sub_vectors) = num_sub_vectors {
+                builder = builder.num_sub_vectors(sub_vectors);
+            }
+            if let Some(sample_rate) = sample_rate {
+                builder = builder.sample_rate(sample_rate);
+            }
+            if let Some(m) = options.m {
+                builder = builder.num_edges(m);
+            }
+            if let Some(ef_construction) = options.ef_construction {
+                builder = builder.ef_construction(ef_construction);
+            }
+            Index::IvfHnswPq(builder)
+        }
+        "AUTO" => Index::Auto,
+        "BTREE" => Index::BTree(BTreeIndexBuilder::default()),
+        "FTS" => {
+            check_tokenizer_options(options)?;
+            Index::FTS(FtsIndexBuilder::default())
+        }
+        _ => {
+            return Err(crate::error::Error::InvalidArgument {
+                message: format!("Unsupported index type: {}", index_type),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+        }
+    };
+    Ok(index)
+}
+
+/// Check FTS tokenizer options against the tokenizer LanceDB uses.
+///
+/// LanceDB 0.10 has no way to configure the FTS tokenizer: its index
+/// builder takes no settings and always lowercases and stems English.
+/// Choosing a language or turning off lowercasing or stemming needs a newer
+/// LanceDB. Until then options that agree with the fixed tokenizer are
+/// accepted so callers can state their expectations, and anything else is
+/// an error rather than being silently ignored.
+fn check_tokenizer_options(options: &IndexOptions) -> Result<()> {
+    let unsupported = |setting: String| crate::error::Error::InvalidArgument {
+        message: format!(
+            "FTS tokenizer does not support {}; LanceDB 0.10 always lowercases and stems English",
+            setting
+        ),
+        location: snafu::Location::new(file!(), line!(), column!()),
+    };
+    if let Some(language) = &options.language {
+        if !language.eq_ignore_ascii_case("english") {
+            return Err(unsupported(format!("language '{}'", language)));
+        }
+    }
+    if options.lower_case == Some(false) {
+        return Err(unsupported("lower_case = false".to_string()));
+    }
+    if options.stem == Some(false) {
+        return Err(unsupported("stem = false".to_string()));
+    }
+    Ok(())
+}
+
+/// Index job status: the build has been queued but not started
+pub const INDEX_JOB_PENDING: c_int = 0;
+/// Index job status: the index is being built
+pub const INDEX_JOB_RUNNING: c_int = 1;
+/// Index job status: the index was built successfully
+pub const INDEX_JOB_DONE: c_int = 2;
+/// Index job status: the build failed
+pub const INDEX_JOB_ERROR: c_int = 3;
+
+/// Schema check result: batches with the schema can be added as they are
+pub const SCHEMA_COMPATIBLE: c_int = 0;
+/// Schema check result: some fields must first be cast to the table's types
+pub const SCHEMA_CASTABLE: c_int = 1;
+
+/// Whether every value of type `from` converts to `to` without loss, so a
+/// batch can be cast to the table's type before it is added
+fn is_widening_cast(from: &DataType, to: &DataType) -> bool {
+    use DataType::*;
+    match (from, to) {
+        (Int8, Int16 | Int32 | Int64)
+        | (Int16, Int32 | Int64)
+        | (Int32, Int64)
+        | (UInt8, UInt16 | UInt32 | UInt64 | Int16 | Int32 | Int64)
+        | (UInt16, UInt32 | UInt64 | Int32 | Int64)
+        | (UInt32, UInt64 | Int64)
+        | (Float16, Float32 | Float64)
+        | (Float32, Float64)
+        | (Int8 | Int16 | UInt8 | UInt16, Float32 | Float64)
+        | (Int32 | UInt32, Float64)
+        | (Utf8, LargeUtf8)
+        | (Binary, LargeBinary) => true,
+        (FixedSizeList(from_item, from_size), FixedSizeList(to_item, to_size)) => {
+            from_size == to_size
+                && (from_item.data_type() == to_item.data_type()
+                    || is_widening_cast(from_item.data_type(), to_item.data_type()))
+        }
+        _ => false,
+    }
+}
+
+/// Opaque handle to an index build running in the background
+pub struct IndexJobHandle {
+    status: Arc<AtomicI32>,
+    error: Arc<Mutex<Option<crate::error::Error>>>,
+}
+
+impl IndexJobHandle {
+    pub fn status(&self) -> c_int {
+        self.status.load(Ordering::SeqCst)
+    }
+
+    /// Run `f` on the error the build failed with, if any
+    pub fn with_error<T>(&self, f: impl FnOnce(&crate::error::Error) -> T) -> Option<T> {
+        // Only ever written once, so a panicked writer leaves it usable
+        self.error
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+            .map(f)
+    }
+}
+
+/// Describe the first difference between two schemas, or `None` if they match.
+///
+/// Fields are matched by name; both schemas must contain the same fields with
+/// identical data types and nullability. Field order is not significant.
+pub(crate) fn schema_mismatch(left: &Schema, right: &Schema) -> Option<String> {
+    let nullability = |nullable: bool| if nullable { "nullable" } else { "non-nullable" };
+    for field in left.fields() {
+        let other = match right.field_with_name(field.name()) {
+            Ok(f) => f,
+            Err(_) => {
+                return Some(format!(
+                    "field '{}' is missing from the second schema",
+                    field.name()
+                ))
+            }
+        };
+        if field.data_type() != other.data_type() {
+            return Some(format!(
+                "field '{}' has type {} in the first schema but {} in the second",
+                field.name(),
+                field.data_type(),
+                other.data_type()
+            ));
+        }
+        if field.is_nullable() != other.is_nullable() {
+            return Some(format!(
+                "field '{}' is {} in the first schema but {} in the second",
+                field.name(),
+                nullability(field.is_nullable()),
+                nullability(other.is_nullable())
+            ));
+        }
+    }
+    for field in right.fields() {
+        if left.field_with_name(field.name()).is_err() {
+            return Some(format!(
+                "field '{}' is missing from the first schema",
+                field.name()
+            ));
+        }
+    }
+    None
+}
+
+// C API for tables
+
+/// Open an existing table.
+/// Returns a pointer to TableHandle on success, null on failure.
+#[no_mangle]
+pub extern "C" fn lancedb_table_open(
+    connection: *const super::connection::ConnectionHandle,
+    name: *const c_char,
+) -> *mut TableHandle {
+    if connection.is_null() || name.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "connection and name cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return std::ptr::null_mut();
+    }
+
+    let connection = unsafe { &*connection };
+    let table_name = c_result!(unsafe { crate::c_str_arg(name, "name") });
+
+    let handle = c_result!(TableHandle::open(connection, table_name));
+    Box::into_raw(Box::new(handle))
+}
+
+/// Open an existing table, capping how many index entries it caches in
+/// memory. Returns a pointer to TableHandle on success, null on failure.
+///
+/// index_cache_size: maximum number of index entries (e.g. IVF partitions)
+/// to cache, or 0 for the default. The cache belongs to the returned handle;
+/// opening the same table again with a different size does not affect
+/// handles that are already open.
+#[no_mangle]
+pub extern "C" fn lancedb_table_open_with_options(
+    connection: *const super::connection::ConnectionHandle,
+    name: *const c_char,
+    index_cache_size: u32,
+) -> *mut TableHandle {
+    if connection.is_null() || name.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "connection and name cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return std::ptr::null_mut();
+    }
+
+    let connection = unsafe { &*connection };
+    let table_name = c_result!(unsafe { crate::c_str_arg(name, "name") });
+
+    let handle = c_result!(TableHandle::open_with_index_cache_size(
+        connection,
+        table_name,
+        index_cache_size
+    ));
+    Box::into_raw(Box::new(handle))
+}
+
+/// Open an existing table pinned to a historical version.
+/// Returns a pointer to TableHandle on success, null on failure (e.g. the
+/// version does not exist).
+///
+/// version: the version to read, or 0 for the latest. The handle behaves as
+/// if lancedb_table_checkout had been called on it: reads reflect the table
+/// as of `version` until lancedb_table_checkout_latest, and writes fail.
+#[no_mangle]
+pub extern "C" fn lancedb_table_open_version(
+    connection: *const super::connection::ConnectionHandle,
+    name: *const c_char,
+    version: u64,
+) -> *mut TableHandle {
+    if connection.is_null() || name.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "connection and name cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return std::ptr::null_mut();
+    }
+
+    let connection = unsafe { &*connection };
+    let table_name = c_result!(unsafe { crate::c_str_arg(name, "name") });
+
+    let handle = c_result!(TableHandle::open_version(connection, table_name, version));
+    Box::into_raw(Box::new(handle))
+}
+
+/// Create a new table. For now, creates an empty table.
+/// Returns a pointer to TableHandle on success, null on failure.
+#[no_mangle]
+pub extern "C" fn lancedb_table_create(
+    connection: *const super::connection::ConnectionHandle,
+    name: *const c_char,
+) -> *mut TableHandle {
+    if connection.is_null() || name.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "connection and name cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return std::ptr::null_mut();
+    }
+
+    let connection = unsafe { &*connection };
+    let table_name = c_result!(unsafe { crate::c_str_arg(name, "name") });
+
+    // Create a simple schema for demonstration
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("text", DataType::Utf8, true),
+    ]));
+
+    let table = c_result!(RT.block_on(
+        connection
+            .inner
+            .create_empty_table(table_name, schema)
+            .execute()
+    ));
+    let handle = TableHandle::opened(connection, table);
+    Box::into_raw(Box::new(handle))
+}
+
+/// Close a table and free resources.
+#[no_mangle]
+pub extern "C" fn lancedb_table_close(handle: *mut TableHandle) {
+    if !handle.is_null() {
+        let _ = unsafe { Box::from_raw(handle) };
+    }
+}
+
+/// Get the number of rows in a table.
+/// Returns the count on success, -1 on failure.
+#[no_mangle]
+pub extern "C" fn lancedb_table_count_rows(handle: *const TableHandle) -> i64 {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    match table.count_rows() {
+        Ok(count) => count,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Estimate the number of rows in a table, optionally only those matching a
+/// SQL predicate, without scanning it. A null filter counts every row.
+/// Returns the estimate on success, -1 on failure (including an invalid
+/// filter).
+///
+/// The count is approximate: it comes from the row counts recorded per
+/// fragment, which may include deleted rows for tables written by older
+/// versions, and a filter is evaluated on a sample of fragments and
+/// extrapolated. A handle pinned to a version is counted at that version.
+/// Use lancedb_table_count_rows_filtered when an exact count is needed.
+#[no_mangle]
+pub extern "C" fn lancedb_table_count_rows_approx(
+    handle: *const TableHandle,
+    filter: *const c_char,
+) -> i64 {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    let filter_str = if filter.is_null() {
+        None
+    } else {
+        match unsafe { crate::c_str_arg(filter, "filter") } {
+            Ok(s) => Some(s),
+            Err(err) => {
+                crate::set_last_error(&err);
+                return -1;
+            }
+        }
+    };
+
+    match table.count_rows_approx(filter_str) {
+        Ok(count) => count,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Get the number of rows in a table matching a SQL predicate such as
+/// "value > 10". A null filter counts every row.
+/// Returns the count on success, -1 on failure (including an invalid filter).
+#[no_mangle]
+pub extern "C" fn lancedb_table_count_rows_filtered(
+    handle: *const TableHandle,
+    filter: *const c_char,
+) -> i64 {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    let filter_str = if filter.is_null() {
+        None
+    } else {
+        match unsafe { crate::c_str_arg(filter, "filter") } {
+            Ok(s) => Some(s),
+            Err(err) => {
+                crate::set_last_error(&err);
+                return -1;
+            }
+        }
+    };
+
+    match table.count_rows_filtered(filter_str) {
+        Ok(count) => count,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Add data to a table from Arrow C Data Interface structures.
+/// Returns 0 on success, -1 on failure.
+/// mode: 0 = Append, 1 = Overwrite
+///
+/// The write is committed synchronously: when this returns 0 the data files
+/// and a new manifest version have been written to storage. The binding does
+/// not buffer writes, so there is no separate flush step.
+#[no_mangle]
+pub extern "C" fn lancedb_table_add(
+    handle: *const TableHandle,
+    array: *mut FFI_ArrowArray,
+    schema: *mut FFI_ArrowSchema,
+    mode: c_int,
+) -> c_int {
+    if handle.is_null() || array.is_null() || schema.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle, array, and schema cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+
+    // Import the record batch from C
+    let batch = match unsafe { import_record_batch_from_c(array, schema) } {
+        Ok(b) => b,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    // Determine the mode
+    let add_mode = match mode {
+        0 => AddDataMode::Append,
+        1 => AddDataMode::Overwrite,
+        _ => {
+            crate::set_last_error(&crate::error::Error::InvalidArgument {
+                message: "invalid mode: must be 0 (Append) or 1 (Overwrite)".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+            return -1;
+        }
+    };
+
+    // Add the data
+    match table.add_data(batch, add_mode) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Add data to a table like lancedb_table_add, but return the number of rows
+/// written on success and -1 on failure.
+///
+/// On append the count is the number of rows in the batch. An overwrite
+/// replaces the table's contents, so the count is also the new table size.
+#[no_mangle]
+pub extern "C" fn lancedb_table_add_counted(
+    handle: *const TableHandle,
+    array: *mut FFI_ArrowArray,
+    schema: *mut FFI_ArrowSchema,
+    mode: c_int,
+) -> i64 {
+    if handle.is_null() || array.is_null() || schema.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle, array, and schema cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+
+    let batch = match unsafe { import_record_batch_from_c(array, schema) } {
+        Ok(b) => b,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    let add_mode = match mode {
+        0 => AddDataMode::Append,
+        1 => AddDataMode::Overwrite,
+        _ => {
+            crate::set_last_error(&crate::error::Error::InvalidArgument {
+                message: "invalid mode: must be 0 (Append) or 1 (Overwrite)".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+            return -1;
+        }
+    };
+
+    match table.add_data(batch, add_mode) {
+        Ok(rows) => rows as i64,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Add data to a table like lancedb_table_add, sizing the written files.
+/// Returns 0 on success, -1 on failure.
+///
+/// `max_rows_per_file` caps the rows of each data file, and so of each new
+/// fragment; `max_rows_per_group` caps the rows of each row group within a
+/// file. 0 keeps the default for either. Bigger files mean fewer fragments
+/// and cheaper compaction later.
+#[no_mangle]
+pub extern "C" fn lancedb_table_add_with_options(
+    handle: *const TableHandle,
+    array: *mut FFI_ArrowArray,
+    schema: *mut FFI_ArrowSchema,
+    mode: c_int,
+    max_rows_per_file: u64,
+    max_rows_per_group: u64,
+) -> c_int {
+    if handle.is_null() || array.is_null() || schema.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle, array, and schema cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+
+    let batch = match unsafe { import_record_batch_from_c(array, schema) } {
+        Ok(b) => b,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    let add_mode = match mode {
+        0 => AddDataMode::Append,
+        1 => AddDataMode::Overwrite,
+        _ => {
+            crate::set_last_error(&crate::error::Error::InvalidArgument {
+                message: "invalid mode: must be 0 (Append) or 1 (Overwrite)".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+            return -1;
+        }
+    };
+
+    let max_rows_per_file = (max_rows_per_file > 0).then_some(max_rows_per_file as usize);
+    let max_rows_per_group = (max_rows_per_group > 0).then_some(max_rows_per_group as usize);
+    match table.add_data_with_options(batch, add_mode, max_rows_per_file, max_rows_per_group) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Add the batches of an Arrow C stream to a table in a single commit.
+/// Returns 0 on success, -1 on failure.
+///
+/// Batches are written as they are read, so the whole dataset never has to
+/// be held in memory. An empty stream leaves the table unchanged. The stream
+/// is consumed and released by this call, even on failure.
+///
+/// # Parameters
+/// * `handle` - The table handle
+/// * `stream` - The ArrowArrayStream to read batches from
+/// * `mode` - 0 to append, 1 to overwrite
+#[no_mangle]
+pub extern "C" fn lancedb_table_add_stream(
+    handle: *const TableHandle,
+    stream: *mut FFI_ArrowArrayStream,
+    mode: c_int,
+) -> c_int {
+    if handle.is_null() || stream.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle and stream cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+
+    let reader = match unsafe { ArrowArrayStreamReader::from_raw(stream) } {
+        Ok(r) => r,
+        Err(err) => {
+            crate::set_last_error(&err.into());
+            return -1;
+        }
+    };
+
+    let add_mode = match mode {
+        0 => AddDataMode::Append,
+        1 => AddDataMode::Overwrite,
+        _ => {
+            crate::set_last_error(&crate::error::Error::InvalidArgument {
+                message: "invalid mode: must be 0 (Append) or 1 (Overwrite)".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+            return -1;
+        }
+    };
+
+    match table.add_stream(Box::new(reader), add_mode) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Append a record batch to a table and update its indices so the new rows
+/// are served by them without a separate optimize.
+/// Returns 0 on success, -1 on failure.
+///
+/// New rows are added to each index incrementally rather than by retraining
+/// it. If the index update fails after the append succeeded, -1 is returned
+/// but the rows remain in the table.
+#[no_mangle]
+pub extern "C" fn lancedb_table_add_and_index(
+    handle: *const TableHandle,
+    array: *mut FFI_ArrowArray,
+    schema: *mut FFI_ArrowSchema,
+) -> c_int {
+    if handle.is_null() || array.is_null() || schema.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle, array, and schema cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+
+    let batch = match unsafe { import_record_batch_from_c(array, schema) } {
+        Ok(b) => b,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match table.add_and_index(batch) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Update rows in place with SQL expressions.
+/// Returns 0 on success, -1 on failure.
+///
+/// # Parameters
+/// * `handle` - The table handle
+/// * `predicate` - SQL predicate selecting the rows to update, or null for all rows
+/// * `columns` - Names of the columns to set
+/// * `values` - SQL expressions for the new values (e.g., "price * 1.1"),
+///   parallel to `columns`
+/// * `n` - Number of entries in `columns` and `values`; must be at least 1
+#[no_mangle]
+pub extern "C" fn lancedb_table_update(
+    handle: *const TableHandle,
+    predicate: *const c_char,
+    columns: *const *const c_char,
+    values: *const *const c_char,
+    n: c_int,
+) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    let predicate_opt = if predicate.is_null() {
+        None
+    } else {
+        match unsafe { crate::c_str_arg(predicate, "predicate") } {
+            Ok(s) => Some(s),
+            Err(err) => {
+                crate::set_last_error(&err);
+                return -1;
+            }
+        }
+    };
+    let column_names = match unsafe { crate::c_str_array_arg(columns, n, "columns") } {
+        Ok(c) => c,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+    let expressions = match unsafe { crate::c_str_array_arg(values, n, "values") } {
+        Ok(v) => v,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+    let updates: Vec<(String, String)> = column_names.into_iter().zip(expressions).collect();
+
+    match table.update(predicate_opt, &updates) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Add columns computed from SQL expressions over existing columns.
+/// Returns 0 on success, -1 on failure.
+///
+/// # Parameters
+/// * `handle` - The table handle
+/// * `names` - Names of the new columns; none may already exist
+/// * `exprs` - SQL expressions computing each column (e.g., "value * 2"),
+///   parallel to `names`. The column type is inferred from the expression.
+/// * `n` - Number of entries in `names` and `exprs`
+#[no_mangle]
+pub extern "C" fn lancedb_table_add_columns(
+    handle: *const TableHandle,
+    names: *const *const c_char,
+    exprs: *const *const c_char,
+    n: c_int,
+) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    let column_names = match unsafe { crate::c_str_array_arg(names, n, "names") } {
+        Ok(c) => c,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+    let expressions = match unsafe { crate::c_str_array_arg(exprs, n, "exprs") } {
+        Ok(e) => e,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+    let definitions: Vec<(String, String)> = column_names.into_iter().zip(expressions).collect();
+
+    match table.add_columns(&definitions) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Drop columns from a table.
+/// Returns 0 on success, -1 on failure (e.g. an unknown column, or dropping
+/// every column).
+///
+/// # Parameters
+/// * `handle` - The table handle
+/// * `columns` - Names of the columns to drop
+/// * `n` - Number of entries in `columns`
+#[no_mangle]
+pub extern "C" fn lancedb_table_drop_columns(
+    handle: *const TableHandle,
+    columns: *const *const c_char,
+    n: c_int,
+) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    let column_names = match unsafe { crate::c_str_array_arg(columns, n, "columns") } {
+        Ok(c) => c,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match table.drop_columns(&column_names) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Alter a column: rename it, cast it to a new type, and/or change its
+/// nullability.
+/// Returns 0 on success, -1 on failure.
+///
+/// # Parameters
+/// * `handle` - The table handle
+/// * `column` - The column to alter
+/// * `new_name` - The new column name, or null/empty to keep the name
+/// * `new_type_str` - The type to cast to (e.g. "float32", "int64", "string"),
+///   or null/empty to keep the type. The cast fails if any stored value cannot
+///   be converted without loss.
+/// * `nullable` - 1 to make the column nullable, 0 to make it non-nullable,
+///   -1 to leave it unchanged
+#[no_mangle]
+pub extern "C" fn lancedb_table_alter_column(
+    handle: *const TableHandle,
+    column: *const c_char,
+    new_name: *const c_char,
+    new_type_str: *const c_char,
+    nullable: c_int,
+) -> c_int {
+    if handle.is_null() || column.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle and column cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    let column_str = match unsafe { crate::c_str_arg(column, "column") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+    let new_name_opt = if new_name.is_null() {
+        None
+    } else {
+        match unsafe { crate::c_str_arg(new_name, "new_name") } {
+            Ok(s) if s.is_empty() => None,
+            Ok(s) => Some(s),
+            Err(err) => {
+                crate::set_last_error(&err);
+                return -1;
+            }
+        }
+    };
+    let new_type_opt = if new_type_str.is_null() {
+        None
+    } else {
+        match unsafe { crate::c_str_arg(new_type_str, "new_type_str") } {
+            Ok(s) if s.is_empty() => None,
+            Ok(s) => match parse_data_type(s) {
+                Ok(t) => Some(t),
+                Err(err) => {
+                    crate::set_last_error(&err);
+                    return -1;
+                }
+            },
+            Err(err) => {
+                crate::set_last_error(&err);
+                return -1;
+            }
+        }
+    };
+    let nullable_opt = match nullable {
+        -1 => None,
+        0 => Some(false),
+        1 => Some(true),
+        _ => {
+            crate::set_last_error(&crate::error::Error::InvalidArgument {
+                message: "invalid nullable: must be -1 (unchanged), 0, or 1".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+            return -1;
+        }
+    };
+
+    match table.alter_column(column_str, new_name_opt, new_type_opt, nullable_opt) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Get the schema of a table as Arrow C Data Interface structure.
+/// Returns 0 on success, -1 on failure.
+#[no_mangle]
+pub extern "C" fn lancedb_table_schema(
+    handle: *const TableHandle,
+    schema_out: *mut FFI_ArrowSchema,
+) -> c_int {
+    if handle.is_null() || schema_out.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle and schema_out cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+
+    // Get the schema
+    let schema = match table.schema() {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    // Export to C
+    unsafe {
+        match crate::arrow_ffi::export_schema_to_c(&schema, schema_out) {
+            Ok(_) => 0,
+            Err(err) => {
+                crate::set_last_error(&err);
+                -1
+            }
+        }
+    }
+}
+
+/// Create a table with a custom schema from Arrow C Data Interface structure.
+/// Returns a pointer to TableHandle on success, null on failure.
+#[no_mangle]
+pub extern "C" fn lancedb_table_create_with_schema(
+    connection: *const super::connection::ConnectionHandle,
+    name: *const c_char,
+    schema: *mut FFI_ArrowSchema,
+) -> *mut TableHandle {
+    lancedb_table_create_with_schema_ex(connection, name, schema, 0)
+}
+
+/// Create a table with a custom schema like lancedb_table_create_with_schema,
+/// choosing what happens when a table of that name already exists.
+/// Returns a pointer to TableHandle on success, null on failure.
+///
+/// mode:
+/// * 0 (Create) - fail with a TableAlreadyExists error
+/// * 1 (ExistOk) - open the existing table, whatever its schema
+/// * 2 (Overwrite) - replace the existing table with an empty one
+///
+/// Overwrite commits a new version of the table. Handles opened before it
+/// keep reading the version they had open until they are refreshed with
+/// lancedb_table_checkout_latest or reopened.
+#[no_mangle]
+pub extern "C" fn lancedb_table_create_with_schema_ex(
+    connection: *const super::connection::ConnectionHandle,
+    name: *const c_char,
+    schema: *mut FFI_ArrowSchema,
+    mode: c_int,
+) -> *mut TableHandle {
+    lancedb_table_create_with_storage_version(connection, name, schema, mode, std::ptr::null())
+}
+
+/// Create a table with a custom schema like lancedb_table_create_with_schema_ex,
+/// pinning the Lance file format version of its data files.
+/// Returns a pointer to TableHandle on success, null on failure.
+///
+/// `data_storage_version` may be null for LanceDB's default, or one of:
+/// * "legacy" (also "0.1") - the original format, readable by every Lance
+///   release
+/// * "stable" (also "2.0") - the v2 format, readable by Lance 0.16 and later
+///
+/// Other values, including unreleased formats, fail with an InvalidArgument
+/// error. With mode 1 an existing table keeps its own version.
+#[no_mangle]
+pub extern "C" fn lancedb_table_create_with_storage_version(
+    connection: *const super::connection::ConnectionHandle,
+    name: *const c_char,
+    schema: *mut FFI_ArrowSchema,
+    mode: c_int,
+    data_storage_version: *const c_char,
+) -> *mut TableHandle {
+    if connection.is_null() || name.is_null() || schema.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "connection, name, and schema cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return std::ptr::null_mut();
+    }
+
+    let connection = unsafe { &*connection };
+    let table_name = c_result!(unsafe { crate::c_str_arg(name, "name") });
+    let create_mode = c_result!(create_table_mode(mode));
+    let storage_version = if data_storage_version.is_null() {
+        None
+    } else {
+        let version =
+            c_result!(unsafe { crate::c_str_arg(data_storage_version, "data_storage_version") });
+        Some(c_result!(parse_storage_version(version)))
+    };
+
+    let imported_schema = c_result!(unsafe { crate::arrow_ffi::import_schema_from_c(schema) });
+
+    let handle = c_result!(TableHandle::create_with_storage_version(
+        connection,
+        table_name,
+        Arc::new(imported_schema),
+        create_mode,
+        storage_version
+    ));
+    Box::into_raw(Box::new(handle))
+}
+
+/// Parse a Lance file format version accepted at table creation
+fn parse_storage_version(version: &str) -> Result<LanceFileVersion> {
+    match version.to_lowercase().as_str() {
+        "legacy" | "0.1" => Ok(LanceFileVersion::Legacy),
+        "stable" | "2.0" => Ok(LanceFileVersion::V2_0),
+        _ => Err(crate::error::Error::InvalidArgument {
+            message: format!(
+                "unsupported data storage version '{}': expected \"legacy\" (0.1) or \"stable\" (2.0)",
+                version
+            ),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        }),
+    }
+}
+
+/// Map a C table creation mode to LanceDB's: 0 fails if the table exists,
+/// 1 opens the existing table instead, 2 replaces it
+fn create_table_mode(mode: c_int) -> Result<CreateTableMode> {
+    match mode {
+        0 => Ok(CreateTableMode::Create),
+        1 => Ok(CreateTableMode::exist_ok(|builder| builder)),
+        2 => Ok(CreateTableMode::Overwrite),
+        _ => Err(crate::error::Error::InvalidArgument {
+            message: "invalid mode: must be 0 (Create), 1 (ExistOk), or 2 (Overwrite)".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        }),
+    }
+}
+
+/// Create a table from a record batch in Arrow C Data Interface structures.
+/// The table takes the batch's schema and holds its rows; an empty batch
+/// creates an empty table. Returns a pointer to TableHandle on success, null
+/// on failure.
+///
+/// mode: 0 = fail if the table exists, 1 = open the existing table instead
+/// (its data and schema are left unchanged and the batch is not written),
+/// 2 = replace the existing table
+#[no_mangle]
+pub extern "C" fn lancedb_table_create_from_data(
+    connection: *const super::connection::ConnectionHandle,
+    name: *const c_char,
+    array: *mut FFI_ArrowArray,
+    schema: *mut FFI_ArrowSchema,
+    mode: c_int,
+) -> *mut TableHandle {
+    if connection.is_null() || name.is_null() || array.is_null() || schema.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "connection, name, array, and schema cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return std::ptr::null_mut();
+    }
+
+    let connection = unsafe { &*connection };
+    let table_name = c_result!(unsafe { crate::c_str_arg(name, "name") });
+    let create_mode = c_result!(create_table_mode(mode));
+    let batch = c_result!(unsafe { import_record_batch_from_c(array, schema) });
+
+    let handle = c_result!(TableHandle::create_from_batch(
+        connection,
+        table_name,
+        batch,
+        create_mode
+    ));
+    Box::into_raw(Box::new(handle))
+}
+
+/// Read data from a table as Arrow C Data Interface structures.
+/// Returns the number of batches on success, -1 on failure.
+/// limit: maximum number of rows to read (-1 for no limit)
+/// arrays_out and schemas_out will be populated with arrays of Arrow C structures.
+/// Caller is responsible for freeing the arrays and schemas with
+/// lancedb_free_batch_arrays.
+#[no_mangle]
+pub extern "C" fn lancedb_table_to_arrow(
+    handle: *const TableHandle,
+    limit: i64,
+    arrays_out: *mut *mut FFI_ArrowArray,
+    schemas_out: *mut *mut FFI_ArrowSchema,
+    count_out: *mut c_int,
+) -> c_int {
+    lancedb_table_to_arrow_ex(handle, limit, 0, arrays_out, schemas_out, count_out)
+}
+
+/// Read a page of a table like lancedb_table_to_arrow, skipping the first
+/// `offset` rows (a negative offset means 0) before reading up to `limit`.
+/// Returns 0 on success, -1 on failure.
+///
+/// Pages follow the table's storage order, which is stable until the table
+/// is modified, so successive offsets page through a full scan.
+#[no_mangle]
+pub extern "C" fn lancedb_table_to_arrow_ex(
+    handle: *const TableHandle,
+    limit: i64,
+    offset: i64,
+    arrays_out: *mut *mut FFI_ArrowArray,
+    schemas_out: *mut *mut FFI_ArrowSchema,
+    count_out: *mut c_int,
+) -> c_int {
+    if handle.is_null() || arrays_out.is_null() || schemas_out.is_null() || count_out.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle, arrays_out, schemas_out, and count_out cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+
+    // Read the data
+    let limit_opt = if limit < 0 { None } else { Some(limit) };
+    let batches = match table.to_arrow(limit_opt, offset.max(0) as usize) {
+        Ok(b) => b,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match unsafe {
+        crate::arrow_ffi::export_batches_to_c(&batches, arrays_out, schemas_out, count_out)
+    } {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Callback invoked with the number of rows exported so far and the
+/// `user_data` pointer passed by the caller
+pub type ProgressCallback = Option<extern "C" fn(rows_exported: i64, user_data: *mut c_void)>;
+
+/// Read data from a table like lancedb_table_to_arrow, reporting progress.
+/// Returns 0 on success, -1 on failure.
+///
+/// `callback` (which may be null) is called on the calling thread after each
+/// batch is read, with the total number of rows read so far and `user_data`.
+/// The callback must not call back into this library for the same table.
+#[no_mangle]
+pub extern "C" fn lancedb_table_to_arrow_progress(
+    handle: *const TableHandle,
+    limit: i64,
+    callback: ProgressCallback,
+    user_data: *mut c_void,
+    arrays_out: *mut *mut FFI_ArrowArray,
+    schemas_out: *mut *mut FFI_ArrowSchema,
+    count_out: *mut c_int,
+) -> c_int {
+    if handle.is_null() || arrays_out.is_null() || schemas_out.is_null() || count_out.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle, arrays_out, schemas_out, and count_out cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+
+    let limit_opt = if limit < 0 { None } else { Some(limit) };
+    let batches = match table.to_arrow_with_progress(limit_opt, 0, |rows| {
+        if let Some(callback) = callback {
+            callback(rows as i64, user_data);
+        }
+    }) {
+        Ok(b) => b,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match unsafe {
+        crate::arrow_ffi::export_batches_to_c(&batches, arrays_out, schemas_out, count_out)
+    } {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Create an index on a table column.
+/// Returns 0 on success, -1 on failure.
+///
+/// # Parameters
+/// * `handle` - The table handle
+/// * `column` - The column name to index (a FixedSizeList of Float16, Float32, or Float64)
+/// * `index_type` - The type of index ("IVF_PQ", "IVF_HNSW_SQ", "IVF_HNSW_PQ", "AUTO",
+///   "FTS", "BTREE")
+/// * `metric` - Distance metric (0=L2, 1=Cosine, 2=Dot)
+/// * `num_partitions` - Number of IVF partitions (0 to derive it from the row count)
+/// * `num_sub_vectors` - Number of PQ sub-vectors (0 for default)
+/// * `replace` - Whether to replace existing index
+///
+/// When `num_partitions` is 0 for an IVF index, the partition count is
+/// `sqrt(row_count)` clamped to `[1, 4096]`.
+#[no_mangle]
+pub extern "C" fn lancedb_table_create_index(
+    handle: *const TableHandle,
+    column: *const c_char,
+    index_type: *const c_char,
+    metric: c_int,
+    num_partitions: c_int,
+    num_sub_vectors: c_int,
+    replace: bool,
+) -> c_int {
+    lancedb_table_create_index_ex(
+        handle,
+        column,
+        index_type,
+        metric,
+        num_partitions,
+        num_sub_vectors,
+        replace,
+        std::ptr::null(),
+    )
+}
+
+/// Create an index on a table with additional build options.
+/// Returns 0 on success, -1 on failure.
+///
+/// Takes the same parameters as lancedb_table_create_index, plus
+/// `options_json`, a JSON object of extra options (or null for defaults):
+/// * `memory_limit` - Upper bound in bytes for the vectors sampled to train
+///   an IVF index. The per-centroid sample rate is lowered to fit; an error
+///   is returned if the limit cannot hold one vector per centroid.
+/// * `language`, `lower_case`, `stem` - FTS tokenizer settings. LanceDB 0.10
+///   cannot configure its tokenizer, which always lowercases and stems
+///   English, so only "English", true, and true are accepted; any other
+///   value is an error.
+/// * `m`, `ef_construction` - HNSW graph settings: the number of neighbors
+///   per node and the build-time candidate list size. LanceDB's defaults
+///   (20 and 300) are used when omitted.
+///
+/// FTS indices require a string column and BTREE indices a scalar (non-list)
+/// column. Both ignore `metric`, `num_partitions`, and `num_sub_vectors`.
+/// IVF_HNSW_SQ and IVF_HNSW_PQ indices require a vector column; IVF_HNSW_SQ
+/// ignores `num_sub_vectors`.
+#[no_mangle]
+pub extern "C" fn lancedb_table_create_index_ex(
+    handle: *const TableHandle,
+    column: *const c_char,
+    index_type: *const c_char,
+    metric: c_int,
+    num_partitions: c_int,
+    num_sub_vectors: c_int,
+    replace: bool,
+    options_json: *const c_char,
+) -> c_int {
+    if handle.is_null() || column.is_null() || index_type.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle, column, and index_type cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    let column_str = match unsafe { crate::c_str_arg(column, "column") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+    let index_type_str = match unsafe { crate::c_str_arg(index_type, "index_type") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    // Convert metric int to DistanceType
+    let distance_type = match metric {
+        0 => DistanceType::L2,
+        1 => DistanceType::Cosine,
+        2 => DistanceType::Dot,
+        _ => {
+            crate::set_last_error(&crate::error::Error::InvalidArgument {
+                message: "Invalid distance metric. Use 0=L2, 1=Cosine, 2=Dot".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+            return -1;
+        }
+    };
+
+    let partitions = if num_partitions > 0 {
+        Some(num_partitions as u32)
+    } else {
+        None
+    };
+
+    let sub_vectors = if num_sub_vectors > 0 {
+        Some(num_sub_vectors as u32)
+    } else {
+        None
+    };
+
+    let options = if options_json.is_null() {
+        IndexOptions::default()
+    } else {
+        let options_str = match unsafe { crate::c_str_arg(options_json, "options_json") } {
+            Ok(s) => s,
+            Err(err) => {
+                crate::set_last_error(&err);
+                return -1;
+            }
+        };
+        match serde_json::from_str::<IndexOptions>(options_str) {
+            Ok(options) => options,
+            Err(err) => {
+                crate::set_last_error_with_context("invalid index options", &err.into());
+                return -1;
+            }
+        }
+    };
+
+    match table.create_index(
+        column_str,
+        index_type_str,
+        distance_type,
+        partitions,
+        sub_vectors,
+        replace,
+        &options,
+    ) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// List all indices on a table.
+/// Returns the number of indices on success, -1 on failure.
+/// indices_json_out will be populated with a JSON array with one object per
+/// index: {"name": ..., "type": ..., "columns": [...], "distance_type": ...,
+/// "num_partitions": ..., "num_sub_vectors": ...}, where type is the name the
+/// index is created with (e.g. "IVF_PQ", "BTREE", "FTS"). The training
+/// parameters are null where they do not apply, e.g. for a BTREE index, or
+/// when they cannot be read from the table's storage.
+/// Caller is responsible for freeing the string with lancedb_free_string.
+#[no_mangle]
+pub extern "C" fn lancedb_table_list_indices(
+    handle: *const TableHandle,
+    indices_json_out: *mut *mut c_char,
+) -> c_int {
+    if handle.is_null() || indices_json_out.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle and indices_json_out cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    let indices = match table.describe_indices() {
+        Ok(idx) => idx,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    let json = match serde_json::to_string(&indices) {
+        Ok(json) => json,
+        Err(err) => {
+            crate::set_last_error(&err.into());
+            return -1;
+        }
+    };
+
+    let c_string = match CString::new(json) {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err.into());
+            return -1;
+        }
+    };
+
+    unsafe {
+        *indices_json_out = c_string.into_raw();
+    }
+
+    indices.len() as c_int
+}
+
+/// Check whether a column has a vector (ANN) index.
+/// Returns 1 if it does, 0 if it does not, -1 on failure.
+///
+/// Scalar and full-text indices on the column are not counted.
+#[no_mangle]
+pub extern "C" fn lancedb_table_has_vector_index(
+    handle: *const TableHandle,
+    column: *const c_char,
+) -> c_int {
+    if handle.is_null() || column.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle and column cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    let column_str = match unsafe { crate::c_str_arg(column, "column") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match table.has_vector_index(column_str) {
+        Ok(true) => 1,
+        Ok(false) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Drop an index from a table.
+/// Returns 0 on success, -1 on failure (including when no index has that name).
+///
+/// # Parameters
+/// * `handle` - The table handle
+/// * `index_name` - The index name, as reported by lancedb_table_list_indices
+#[no_mangle]
+pub extern "C" fn lancedb_table_drop_index(
+    handle: *const TableHandle,
+    index_name: *const c_char,
+) -> c_int {
+    if handle.is_null() || index_name.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle and index_name cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    let name_str = match unsafe { crate::c_str_arg(index_name, "index_name") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match table.drop_index(name_str) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Rebuild an existing index with new parameters.
+/// Returns 0 on success, -1 on failure.
+///
+/// The new index is built before it replaces the old one, and the swap is a
+/// single commit, so queries never run against an unindexed column. Only
+/// indices with the default name `<column>_idx` can be rebuilt.
+///
+/// # Parameters
+/// * `handle` - The table handle
+/// * `name` - The name of the index to rebuild (as reported by lancedb_table_list_indices)
+/// * `new_config_json` - JSON object with `index_type` and optionally `metric`
+///   (0=L2, 1=Cosine, 2=Dot), `num_partitions`, `num_sub_vectors`, and
+///   `options` (as accepted by lancedb_table_create_index_ex)
+#[no_mangle]
+pub extern "C" fn lancedb_table_rebuild_index(
+    handle: *const TableHandle,
+    name: *const c_char,
+    new_config_json: *const c_char,
+) -> c_int {
+    if handle.is_null() || name.is_null() || new_config_json.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle, name, and new_config_json cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    let name_str = match unsafe { crate::c_str_arg(name, "name") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+    let json_str = match unsafe { crate::c_str_arg(new_config_json, "new_config_json") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+    let spec: IndexSpec = match serde_json::from_str(json_str) {
+        Ok(spec) => spec,
+        Err(err) => {
+            crate::set_last_error_with_context("invalid index config", &err.into());
+            return -1;
+        }
+    };
+
+    match table.rebuild_index(name_str, &spec) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Delete rows from a table based on a predicate.
+/// Returns 0 on success, -1 on failure.
+///
+/// The table is compacted after the delete to reclaim space, which purges
+/// the deleted rows from heavily deleted fragments.
+///
+/// # Parameters
+/// * `handle` - The table handle
+/// * `predicate` - SQL-like predicate string (e.g., "id > 100" or "name = 'doc1'")
+#[no_mangle]
+pub extern "C" fn lancedb_table_delete(
+    handle: *const TableHandle,
+    predicate: *const c_char,
+) -> c_int {
+    if handle.is_null() || predicate.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle and predicate cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    let predicate_str = match unsafe { crate::c_str_arg(predicate, "predicate") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    // Delete the rows
+    if let Err(err) = table.delete_rows(predicate_str) {
+        crate::set_last_error_with_context("delete failed", &err);
+        return -1;
+    }
+
+    // Automatically compact to reclaim space
+    if let Err(err) = table.compact() {
+        crate::set_last_error_with_context("compaction failed", &err);
+        return -1;
+    }
+
+    0
+}
+
+/// Optimize a table's storage and indices.
+/// Returns 0 on success, -1 on failure.
+///
+/// This blocks the calling thread until optimization finishes, which can take
+/// a long time on large tables; call it from a background goroutine/thread.
+///
+/// # Parameters
+/// * `handle` - The table handle
+/// * `mode` - 0 = compaction and index optimization, 1 = compaction only,
+///   2 = index optimization only
+/// * `stats_json_out` - Populated with a JSON object with `fragments_removed`,
+///   `fragments_added`, `files_removed`, and `files_added`.
+///   Caller is responsible for freeing the string with lancedb_free_string.
+#[no_mangle]
+pub extern "C" fn lancedb_table_optimize(
+    handle: *const TableHandle,
+    mode: c_int,
+    stats_json_out: *mut *mut c_char,
+) -> c_int {
+    if handle.is_null() || stats_json_out.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle and stats_json_out cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    let optimize_mode = match mode {
+        0 => OptimizeMode::All,
+        1 => OptimizeMode::Compact,
+        2 => OptimizeMode::Index,
+        _ => {
+            crate::set_last_error(&crate::error::Error::InvalidArgument {
+                message: "invalid mode: must be 0 (All), 1 (Compact), or 2 (Index)".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+            return -1;
+        }
+    };
+
+    let summary = match table.optimize(optimize_mode) {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    let json = match serde_json::to_string(&summary) {
+        Ok(j) => j,
+        Err(err) => {
+            crate::set_last_error(&err.into());
+            return -1;
+        }
+    };
+    let c_string = match CString::new(json) {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err.into());
+            return -1;
+        }
+    };
+
+    unsafe {
+        *stats_json_out = c_string.into_raw();
+    }
+
+    0
+}
+
+/// Get storage statistics for a table as a JSON object with the fields
+/// num_rows, num_fragments, num_deleted_rows, num_indexed_rows, and
+/// total_bytes. Returns 0 on success, -1 on failure.
+/// stats_json_out must be freed with lancedb_free_string.
+#[no_mangle]
+pub extern "C" fn lancedb_table_stats(
+    handle: *const TableHandle,
+    stats_json_out: *mut *mut c_char,
+) -> c_int {
+    if handle.is_null() || stats_json_out.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle and stats_json_out cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    let stats = match table.stats() {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    let json = match serde_json::to_string(&stats) {
+        Ok(j) => j,
+        Err(err) => {
+            crate::set_last_error(&err.into());
+            return -1;
+        }
+    };
+    let c_string = match CString::new(json) {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err.into());
+            return -1;
+        }
+    };
+
+    unsafe {
+        *stats_json_out = c_string.into_raw();
+    }
+
+    0
+}
+
+/// Get statistics for the index named `index_name` as a JSON object with the
+/// fields index_type, num_indexed_rows, num_unindexed_rows, distance_type,
+/// and num_partitions (the last two null for scalar indices).
+/// Returns 0 on success, -1 on failure, including for an unknown index name.
+/// stats_json_out must be freed with lancedb_free_string.
+///
+/// A growing num_unindexed_rows shows that appends are not yet indexed and
+/// the index may need optimizing or retraining.
+#[no_mangle]
+pub extern "C" fn lancedb_table_index_stats(
+    handle: *const TableHandle,
+    index_name: *const c_char,
+    stats_json_out: *mut *mut c_char,
+) -> c_int {
+    if handle.is_null() || stats_json_out.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle and stats_json_out cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    let index_name = match unsafe { crate::c_str_arg(index_name, "index_name") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    let stats = match table.index_stats(index_name) {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    let json = match serde_json::to_string(&stats) {
+        Ok(j) => j,
+        Err(err) => {
+            crate::set_last_error(&err.into());
+            return -1;
+        }
+    };
+    let c_string = match CString::new(json) {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err.into());
+            return -1;
+        }
+    };
+
+    unsafe {
+        *stats_json_out = c_string.into_raw();
+    }
+
+    0
+}
+
+/// Wait until each of the `n` indices named in `index_names` exists and
+/// covers every live row, so searches are guaranteed to use them.
+/// Returns 0 once they are ready, -1 on failure, and -2 (WAIT_TIMED_OUT)
+/// if `timeout_millis` elapses first, with a TimedOut last error.
+///
+/// An index that does not exist yet is waited for like one that is still
+/// building, e.g. after lancedb_table_create_index_async.
+#[no_mangle]
+pub extern "C" fn lancedb_table_wait_for_index(
+    handle: *const TableHandle,
+    index_names: *const *const c_char,
+    n: c_int,
+    timeout_millis: u64,
+) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    let names = match unsafe { crate::c_str_array_arg(index_names, n, "index_names") } {
+        Ok(names) => names,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match table.wait_for_index(&names, Duration::from_millis(timeout_millis)) {
+        Ok(_) => 0,
+        Err(err @ crate::error::Error::TimedOut { .. }) => {
+            crate::set_last_error(&err);
+            WAIT_TIMED_OUT
+        }
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// List the versions of a table.
+/// Returns the number of versions on success, -1 on failure.
+/// versions_json_out will be populated with a JSON array of
+/// `{"version": <u64>, "timestamp": "<RFC 3339>"}` objects, oldest first.
+/// Caller is responsible for freeing the string with lancedb_free_string.
+#[no_mangle]
+pub extern "C" fn lancedb_table_list_versions(
+    handle: *const TableHandle,
+    versions_json_out: *mut *mut c_char,
+) -> c_int {
+    if handle.is_null() || versions_json_out.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle and versions_json_out cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    let versions = match table.list_versions() {
+        Ok(v) => v,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    let json_versions: Vec<serde_json::Value> = versions
+        .iter()
+        .map(|(version, timestamp)| {
+            serde_json::json!({
+                "version": version,
+                "timestamp": timestamp.to_rfc3339(),
+            })
+        })
+        .collect();
+    let c_string = match CString::new(serde_json::Value::Array(json_versions).to_string()) {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err.into());
+            return -1;
+        }
+    };
+
+    unsafe {
+        *versions_json_out = c_string.into_raw();
+    }
+
+    versions.len() as c_int
+}
+
+/// Pin a table handle to a historical version.
+/// Returns 0 on success, -1 on failure (e.g. the version does not exist).
+///
+/// Reads through the handle reflect the table as of `version` until
+/// lancedb_table_checkout_latest is called. Writes to a pinned handle fail.
+#[no_mangle]
+pub extern "C" fn lancedb_table_checkout(handle: *const TableHandle, version: u64) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    match table.checkout(version) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Return a table handle to the latest version.
+/// Returns 0 on success, -1 on failure.
+#[no_mangle]
+pub extern "C" fn lancedb_table_checkout_latest(handle: *const TableHandle) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    match table.checkout_latest() {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Restore a table to a previous version by committing it as the new latest
+/// version. Earlier versions remain available for time travel.
+/// Returns 0 on success, -1 on failure (including a version beyond the latest).
+#[no_mangle]
+pub extern "C" fn lancedb_table_restore(handle: *const TableHandle, version: u64) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    match table.restore(version) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Commit externally written fragments into the table's next version.
+/// Returns 0 on success, -1 on failure.
+///
+/// `fragments_json` is a JSON array of Lance fragment metadata, as produced by
+/// workers that wrote data files directly into the table's `data/` directory.
+/// This allows a scatter/gather bulk load where many processes write fragments
+/// in parallel and a single coordinator commits them.
+#[no_mangle]
+pub extern "C" fn lancedb_table_append_fragments(
+    handle: *const TableHandle,
+    fragments_json: *const c_char,
+) -> c_int {
+    if handle.is_null() || fragments_json.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle and fragments_json cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    let json_str = match unsafe { crate::c_str_arg(fragments_json, "fragments_json") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    let fragments: Vec<Fragment> = match serde_json::from_str(json_str) {
+        Ok(f) => f,
+        Err(err) => {
+            crate::set_last_error_with_context("invalid fragment metadata", &err.into());
+            return -1;
+        }
+    };
+
+    match table.append_fragments(fragments) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Check whether two tables have compatible schemas.
+/// Returns 1 if the schemas match, 0 if they differ, -1 on failure.
+/// When 0 is returned, lancedb_get_last_error() describes the first incompatibility.
+#[no_mangle]
+pub extern "C" fn lancedb_tables_schema_compatible(
+    handle_a: *const TableHandle,
+    handle_b: *const TableHandle,
+) -> c_int {
+    if handle_a.is_null() || handle_b.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handles cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table_a = unsafe { &*handle_a };
+    let table_b = unsafe { &*handle_b };
+
+    let schema_a = match table_a.schema() {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+    let schema_b = match table_b.schema() {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match schema_mismatch(&schema_a, &schema_b) {
+        None => 1,
+        Some(reason) => {
+            crate::set_last_error(&crate::error::Error::InvalidArgument {
+                message: format!("schemas are incompatible: {}", reason),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+            0
+        }
+    }
+}
+
+/// Check whether batches with the given schema can be added to a table.
+/// Returns 0 if they can be added as they are, 1 if some fields must first be
+/// cast to the table's types (lossless widening only, e.g. int16 to int32 or
+/// float16 to float32 vectors), and -1 if the schema is incompatible or the
+/// check fails, with the reason in lancedb_get_last_error().
+///
+/// Fields are matched by name. Every table column must be present, except
+/// embedding columns the handle fills on write, and a nullable field cannot
+/// go into a non-nullable column. Adding data never casts it.
+#[no_mangle]
+pub extern "C" fn lancedb_table_check_schema(
+    handle: *const TableHandle,
+    schema: *mut FFI_ArrowSchema,
+) -> c_int {
+    if handle.is_null() || schema.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle and schema cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    let imported_schema = match unsafe { crate::arrow_ffi::import_schema_from_c(schema) } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match table.check_schema(&imported_schema) {
+        Ok(result) => result,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Copy rows from one table into another without crossing the FFI boundary.
+/// Returns 0 on success, -1 on failure.
+///
+/// # Parameters
+/// * `src_handle` - The table to read from
+/// * `dst_handle` - The table to write to; its schema must match the source
+/// * `filter` - SQL predicate selecting the rows to copy (null copies all rows)
+/// * `mode` - 0 = Append, 1 = Overwrite
+#[no_mangle]
+pub extern "C" fn lancedb_table_copy_into(
+    src_handle: *const TableHandle,
+    dst_handle: *const TableHandle,
+    filter: *const c_char,
+    mode: c_int,
+) -> c_int {
+    if src_handle.is_null() || dst_handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "source and destination table handles cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let source = unsafe { &*src_handle };
+    let destination = unsafe { &*dst_handle };
+
+    let filter_opt = if filter.is_null() {
+        None
+    } else {
+        match unsafe { crate::c_str_arg(filter, "filter") } {
+            Ok(s) => Some(s),
+            Err(err) => {
+                crate::set_last_error(&err);
+                return -1;
+            }
+        }
+    };
+
+    let add_mode = match mode {
+        0 => AddDataMode::Append,
+        1 => AddDataMode::Overwrite,
+        _ => {
+            crate::set_last_error(&crate::error::Error::InvalidArgument {
+                message: "invalid mode: must be 0 (Append) or 1 (Overwrite)".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+            return -1;
+        }
+    };
+
+    match source.copy_into(destination, filter_opt, add_mode) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Start building an index in the background.
+/// Returns a pointer to IndexJobHandle on success, null on failure.
+/// Parameters match lancedb_table_create_index. Poll the job with
+/// lancedb_index_job_status and release it with lancedb_index_job_free.
+#[no_mangle]
+pub extern "C" fn lancedb_table_create_index_async(
+    handle: *const TableHandle,
+    column: *const c_char,
+    index_type: *const c_char,
+    metric: c_int,
+    num_partitions: c_int,
+    num_sub_vectors: c_int,
+    replace: bool,
+) -> *mut IndexJobHandle {
+    lancedb_table_create_index_async_ex(
+        handle,
+        column,
+        index_type,
+        metric,
+        num_partitions,
+        num_sub_vectors,
+        replace,
+        std::ptr::null(),
+    )
+}
+
+/// Start building an index in the background with additional build options.
+/// Returns a pointer to IndexJobHandle on success, null on failure.
+///
+/// Parameters match lancedb_table_create_index_ex. Invalid options are
+/// reported here, before the build starts; failures of the build itself are
+/// reported through lancedb_index_job_status.
+#[no_mangle]
+pub extern "C" fn lancedb_table_create_index_async_ex(
+    handle: *const TableHandle,
+    column: *const c_char,
+    index_type: *const c_char,
+    metric: c_int,
+    num_partitions: c_int,
+    num_sub_vectors: c_int,
+    replace: bool,
+    options_json: *const c_char,
+) -> *mut IndexJobHandle {
+    if handle.is_null() || column.is_null() || index_type.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle, column, and index_type cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return std::ptr::null_mut();
+    }
+
+    let table = unsafe { &*handle };
+    let column_str = c_result!(unsafe { crate::c_str_arg(column, "column") });
+    let index_type_str = c_result!(unsafe { crate::c_str_arg(index_type, "index_type") });
+
+    let distance_type = match metric {
+        0 => DistanceType::L2,
+        1 => DistanceType::Cosine,
+        2 => DistanceType::Dot,
+        _ => {
+            crate::set_last_error(&crate::error::Error::InvalidArgument {
+                message: "Invalid distance metric. Use 0=L2, 1=Cosine, 2=Dot".to_string(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            });
+            return std::ptr::null_mut();
+        }
+    };
+
+    let partitions = if num_partitions > 0 {
+        Some(num_partitions as u32)
+    } else {
+        None
+    };
+
+    let sub_vectors = if num_sub_vectors > 0 {
+        Some(num_sub_vectors as u32)
+    } else {
+        None
+    };
+
+    let options = if options_json.is_null() {
+        IndexOptions::default()
+    } else {
+        let options_str = c_result!(unsafe { crate::c_str_arg(options_json, "options_json") });
+        match serde_json::from_str::<IndexOptions>(options_str) {
+            Ok(options) => options,
+            Err(err) => {
+                crate::set_last_error_with_context("invalid index options", &err.into());
+                return std::ptr::null_mut();
+            }
+        }
+    };
+
+    let job = c_result!(table.create_index_async(
+        column_str,
+        index_type_str,
+        distance_type,
+        partitions,
+        sub_vectors,
+        replace,
+        &options,
+    ));
+    Box::into_raw(Box::new(job))
+}
+
+/// Get the status of a background index build.
+/// Returns 0 = pending, 1 = running, 2 = done, 3 = error, -1 on invalid handle.
+/// When 3 is returned, lancedb_get_last_error() holds the build failure.
+#[no_mangle]
+pub extern "C" fn lancedb_index_job_status(job: *const IndexJobHandle) -> c_int {
+    if job.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "index job handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let job = unsafe { &*job };
+    let status = job.status();
+    if status == INDEX_JOB_ERROR {
+        job.with_error(crate::set_last_error);
+    }
+    status
+}
+
+/// Free an index job handle.
+/// The build itself keeps running to completion if it has not finished yet.
+#[no_mangle]
+pub extern "C" fn lancedb_index_job_free(job: *mut IndexJobHandle) {
+    if !job.is_null() {
+        let _ = unsafe { Box::from_raw(job) };
+    }
+}
+
+/// Read a single row by key.
+/// Returns 1 if a row was found, 0 if no row matches, -1 on failure.
+///
+/// # Parameters
+/// * `handle` - The table handle
+/// * `key_column` - The column to match on
+/// * `key_value_json` - The key as a JSON scalar (e.g. `42` or `"doc-1"`)
+/// * `array_out` / `schema_out` - Receive a one-row batch when a row is found
+#[no_mangle]
+pub extern "C" fn lancedb_table_get(
+    handle: *const TableHandle,
+    key_column: *const c_char,
+    key_value_json: *const c_char,
+    array_out: *mut FFI_ArrowArray,
+    schema_out: *mut FFI_ArrowSchema,
+) -> c_int {
+    if handle.is_null()
+        || key_column.is_null()
+        || key_value_json.is_null()
+        || array_out.is_null()
+        || schema_out.is_null()
+    {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle, key_column, key_value_json, array_out, and schema_out cannot be null"
+                .to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    let column_str = match unsafe { crate::c_str_arg(key_column, "key_column") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+    let json_str = match unsafe { crate::c_str_arg(key_value_json, "key_value_json") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+    let key_value: serde_json::Value = match serde_json::from_str(json_str) {
+        Ok(v) => v,
+        Err(err) => {
+            crate::set_last_error_with_context("invalid key_value_json", &err.into());
+            return -1;
+        }
+    };
+
+    let batch = match table.get(column_str, &key_value) {
+        Ok(Some(b)) => b,
+        Ok(None) => return 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match unsafe { crate::arrow_ffi::export_record_batch_to_c(&batch, array_out, schema_out) } {
+        Ok(_) => 1,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Read a single row by an integer key.
+/// Returns 0 on success, -1 on failure.
+///
+/// A key that matches no row is not an error: the batch then has the
+/// table's schema and no rows.
+///
+/// # Parameters
+/// * `handle` - The table handle
+/// * `key_column` - The integer column to match on
+/// * `key_value` - The key
+/// * `array_out` / `schema_out` - Receive a batch of at most one row
+#[no_mangle]
+pub extern "C" fn lancedb_table_get_by_key_int(
+    handle: *const TableHandle,
+    key_column: *const c_char,
+    key_value: i64,
+    array_out: *mut FFI_ArrowArray,
+    schema_out: *mut FFI_ArrowSchema,
+) -> c_int {
+    if handle.is_null() || key_column.is_null() || array_out.is_null() || schema_out.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle, key_column, array_out, and schema_out cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    export_row_by_key(table, key_column, key_value.into(), array_out, schema_out)
+}
+
+/// Read a single row by a string key.
+/// Returns 0 on success, -1 on failure.
+///
+/// A key that matches no row is not an error: the batch then has the
+/// table's schema and no rows. The key is passed as a value, so quotes in it
+/// need no escaping.
+///
+/// # Parameters
+/// * `handle` - The table handle
+/// * `key_column` - The string column to match on
+/// * `key_value` - The key
+/// * `array_out` / `schema_out` - Receive a batch of at most one row
+#[no_mangle]
+pub extern "C" fn lancedb_table_get_by_key_string(
+    handle: *const TableHandle,
+    key_column: *const c_char,
+    key_value: *const c_char,
+    array_out: *mut FFI_ArrowArray,
+    schema_out: *mut FFI_ArrowSchema,
+) -> c_int {
+    if handle.is_null()
+        || key_column.is_null()
+        || key_value.is_null()
+        || array_out.is_null()
+        || schema_out.is_null()
+    {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle, key_column, key_value, array_out, and schema_out cannot be null"
+                .to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    let value_str = match unsafe { crate::c_str_arg(key_value, "key_value") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+    export_row_by_key(table, key_column, value_str.into(), array_out, schema_out)
+}
+
+/// Look up the row whose `key_column` equals `key_value` and export it,
+/// shared by the typed lancedb_table_get_by_key_* functions
+fn export_row_by_key(
+    table: &TableHandle,
+    key_column: *const c_char,
+    key_value: serde_json::Value,
+    array_out: *mut FFI_ArrowArray,
+    schema_out: *mut FFI_ArrowSchema,
+) -> c_int {
+    let column_str = match unsafe { crate::c_str_arg(key_column, "key_column") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    let batch = match table.get_by_key(column_str, &key_value) {
+        Ok(b) => b,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match unsafe { crate::arrow_ffi::export_record_batch_to_c(&batch, array_out, schema_out) } {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Fetch rows by their `_rowid`s as a single record batch.
+/// Returns 0 on success, -1 on failure.
+///
+/// Rows come back in the order of `row_ids` with a `_rowid` column, so
+/// callers can tell which ids were found: ids of deleted or unknown rows are
+/// skipped, not reported as errors.
+///
+/// # Parameters
+/// * `handle` - The table handle
+/// * `row_ids` / `n` - The row ids to fetch
+/// * `columns` / `n_cols` - Columns to read; null (or `n_cols` 0) reads all
+/// * `array_out` / `schema_out` - Receive the batch
+#[no_mangle]
+pub extern "C" fn lancedb_table_take(
+    handle: *const TableHandle,
+    row_ids: *const u64,
+    n: c_int,
+    columns: *const *const c_char,
+    n_cols: c_int,
+    array_out: *mut FFI_ArrowArray,
+    schema_out: *mut FFI_ArrowSchema,
+) -> c_int {
+    if handle.is_null() || row_ids.is_null() || array_out.is_null() || schema_out.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle, row_ids, array_out, and schema_out cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+    if n <= 0 {
+        crate::set_last_error(&crate::error::Error::InvalidArgument {
+            message: "n must be positive".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    let row_ids = unsafe { std::slice::from_raw_parts(row_ids, n as usize) };
+    let columns = if columns.is_null() || n_cols == 0 {
+        None
+    } else {
+        match unsafe { crate::c_str_array_arg(columns, n_cols, "columns") } {
+            Ok(c) => Some(c),
+            Err(err) => {
+                crate::set_last_error(&err);
+                return -1;
+            }
+        }
+    };
+
+    let batch = match table.take(row_ids, columns.as_deref()) {
+        Ok(b) => b,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match unsafe { crate::arrow_ffi::export_record_batch_to_c(&batch, array_out, schema_out) } {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Get the custom metadata stored on a table.
+/// Returns 0 on success, -1 on failure.
+/// json_out will be populated with a JSON object mapping keys to values.
+/// Caller is responsible for freeing the string with lancedb_free_string.
+#[no_mangle]
+pub extern "C" fn lancedb_table_get_metadata(
+    handle: *const TableHandle,
+    json_out: *mut *mut c_char,
+) -> c_int {
+    if handle.is_null() || json_out.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle and json_out cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    let metadata = match table.get_metadata() {
+        Ok(m) => m,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    let json = match serde_json::to_string(&metadata) {
+        Ok(j) => j,
+        Err(err) => {
+            crate::set_last_error(&err.into());
+            return -1;
+        }
+    };
+
+    let c_string = match CString::new(json) {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err.into());
+            return -1;
+        }
+    };
+
+    unsafe {
+        *json_out = c_string.into_raw();
+    }
+
+    0
+}
+
+/// Set a custom metadata key on a table, overwriting any existing value.
+/// Returns 0 on success, -1 on failure.
+#[no_mangle]
+pub extern "C" fn lancedb_table_set_metadata(
+    handle: *const TableHandle,
+    key: *const c_char,
+    value: *const c_char,
+) -> c_int {
+    if handle.is_null() || key.is_null() || value.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle, key, and value cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    let key_str = match unsafe { crate::c_str_arg(key, "key") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+    let value_str = match unsafe { crate::c_str_arg(value, "value") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match table.set_metadata(key_str, value_str) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Set how many times lancedb_table_add retries an append that lost a commit
+/// race with another writer. Each retry re-reads the latest table version.
+/// Returns 0 on success, -1 on failure.
+#[no_mangle]
+pub extern "C" fn lancedb_table_set_commit_retries(
+    handle: *const TableHandle,
+    retries: c_int,
+) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+    if retries < 0 {
+        crate::set_last_error(&crate::error::Error::InvalidArgument {
+            message: "retries must be non-negative".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    table.set_commit_retries(retries as u32);
+    0
+}
+
+/// Fill a vector column by embedding a text column with a registered
+/// embedding function (see lancedb_register_embedding_function).
+/// Returns 0 on success, -1 on failure.
+///
+/// Batches written through this handle without `dest_column` get it computed
+/// from `source_column`. An IVF index requested on `source_column` is built
+/// on `dest_column`, and queries created from this handle can search with
+/// lancedb_query_nearest_to_text. The setting lasts for the lifetime of the
+/// handle and must be repeated after reopening the table.
+#[no_mangle]
+pub extern "C" fn lancedb_table_add_embedding_column(
+    handle: *const TableHandle,
+    source_column: *const c_char,
+    dest_column: *const c_char,
+    function_name: *const c_char,
+) -> c_int {
+    if handle.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    let source = match unsafe { crate::c_str_arg(source_column, "source_column") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+    let dest = match unsafe { crate::c_str_arg(dest_column, "dest_column") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+    let function = match unsafe { crate::c_str_arg(function_name, "function_name") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match table.add_embedding_column(source, dest, function) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Evaluate the recall of a vector index against exact brute-force search.
+/// Returns 0 on success, -1 on failure.
+///
+/// # Parameters
+/// * `handle` - The table handle
+/// * `column` - The indexed vector column
+/// * `query_vectors` - `num * dim` floats, one query vector after another
+/// * `num` - Number of query vectors
+/// * `dim` - Dimension of each query vector
+/// * `k` - Number of neighbors to compare per query
+/// * `recall_out` - Receives the mean recall@k in `[0, 1]`
+#[no_mangle]
+pub extern "C" fn lancedb_table_evaluate_recall(
+    handle: *const TableHandle,
+    column: *const c_char,
+    query_vectors: *const c_float,
+    num: c_int,
+    dim: c_int,
+    k: c_int,
+    recall_out: *mut c_double,
+) -> c_int {
+    if handle.is_null() || column.is_null() || query_vectors.is_null() || recall_out.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle, column, query_vectors, and recall_out cannot be null"
+                .to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+    if num <= 0 || dim <= 0 || k <= 0 {
+        crate::set_last_error(&crate::error::Error::InvalidArgument {
+            message: "num, dim, and k must be positive".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    let column_str = match unsafe { crate::c_str_arg(column, "column") } {
+        Ok(s) => s,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    let vectors = unsafe { std::slice::from_raw_parts(query_vectors, num as usize * dim as usize) };
+
+    match table.evaluate_recall(column_str, vectors, dim as usize, k as usize) {
+        Ok(recall) => {
+            unsafe {
+                *recall_out = recall;
+            }
+            0
+        }
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Search for the `k` nearest neighbours of several query vectors at once.
+/// Returns 0 on success, -1 on failure.
+///
+/// `vectors` holds `n_vectors * dim` floats, one query vector after another,
+/// and `dim` must match the table's only vector column; a mismatch fails
+/// before any search runs. Each result row carries a UInt32 `_query_index`
+/// column naming its query, and results are grouped by query in order.
+/// Outputs follow the same conventions as lancedb_table_to_arrow.
+#[no_mangle]
+pub extern "C" fn lancedb_table_batch_search(
+    handle: *const TableHandle,
+    vectors: *const c_float,
+    n_vectors: c_int,
+    dim: c_int,
+    k: c_int,
+    arrays_out: *mut *mut FFI_ArrowArray,
+    schemas_out: *mut *mut FFI_ArrowSchema,
+    count_out: *mut c_int,
+) -> c_int {
+    if handle.is_null()
+        || vectors.is_null()
+        || arrays_out.is_null()
+        || schemas_out.is_null()
+        || count_out.is_null()
+    {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle, vectors, arrays_out, schemas_out, and count_out cannot be null"
+                .to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+    if n_vectors <= 0 || dim <= 0 || k <= 0 {
+        crate::set_last_error(&crate::error::Error::InvalidArgument {
+            message: "n_vectors, dim, and k must be positive".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    let vectors = unsafe { std::slice::from_raw_parts(vectors, n_vectors as usize * dim as usize) };
+
+    let batches = match table.batch_search(vectors, dim as usize, k as usize) {
+        Ok(b) => b,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match unsafe {
+        crate::arrow_ffi::export_batches_to_c(&batches, arrays_out, schemas_out, count_out)
+    } {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Read all rows of a table, including deleted rows that compaction has not
+/// yet purged. Returns 0 on success, -1 on failure.
+///
+/// This is an administrative path intended for auditing and forensics: it
+/// bypasses deletion filtering, and its output must not be treated as the
+/// table's live contents. Each batch carries an extra `_deleted` boolean
+/// column that is true for tombstoned rows. Outputs follow the same
+/// conventions as lancedb_table_to_arrow.
+///
+/// lancedb_table_delete compacts the table after every delete, and compaction
+/// rewrites any fragment with more than 10% of its rows deleted without them.
+/// Rows it deletes are therefore usually purged before this scan can see
+/// them; tombstones left by updates, upserts, or other writers remain.
+#[no_mangle]
+pub extern "C" fn lancedb_table_scan_with_deleted(
+    handle: *const TableHandle,
+    arrays_out: *mut *mut FFI_ArrowArray,
+    schemas_out: *mut *mut FFI_ArrowSchema,
+    count_out: *mut c_int,
+) -> c_int {
+    if handle.is_null() || arrays_out.is_null() || schemas_out.is_null() || count_out.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "handle, arrays_out, schemas_out, and count_out cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+
+    let batches = match table.scan_with_deleted() {
+        Ok(b) => b,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match unsafe {
+        crate::arrow_ffi::export_batches_to_c(&batches, arrays_out, schemas_out, count_out)
+    } {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Insert rows, or update existing rows that share the same key.
+/// Returns 0 on success, -1 on failure.
+///
+/// # Parameters
+/// * `handle` - The table handle
+/// * `array` / `schema` - The rows to upsert
+/// * `key_columns` - Array of column names that together identify a row
+/// * `count` - Number of key columns
+#[no_mangle]
+pub extern "C" fn lancedb_table_upsert(
+    handle: *const TableHandle,
+    array: *mut FFI_ArrowArray,
+    schema: *mut FFI_ArrowSchema,
+    key_columns: *const *const c_char,
+    count: c_int,
+) -> c_int {
+    if handle.is_null() || array.is_null() || schema.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle, array, and schema cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    let keys = match unsafe { crate::c_str_array_arg(key_columns, count, "key_columns") } {
+        Ok(k) => k,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    let batch = match unsafe { import_record_batch_from_c(array, schema) } {
+        Ok(b) => b,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match table.upsert(batch, &keys) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Merge rows into a table, matching on one or more key columns.
+/// Returns 0 on success, -1 on failure.
+///
+/// # Parameters
+/// * `handle` - The table handle
+/// * `on_columns` - Array of column names used to match incoming rows to existing rows
+/// * `n_cols` - Number of entries in `on_columns`
+/// * `when_matched_update_all` - Replace existing rows that match an incoming row
+/// * `when_not_matched_insert_all` - Insert incoming rows that match no existing row
+/// * `array` / `schema` - The rows to merge; the schema must match the table's
+///
+/// At least one of the two flags must be set.
+#[no_mangle]
+pub extern "C" fn lancedb_table_merge_insert(
+    handle: *const TableHandle,
+    on_columns: *const *const c_char,
+    n_cols: c_int,
+    when_matched_update_all: bool,
+    when_not_matched_insert_all: bool,
+    array: *mut FFI_ArrowArray,
+    schema: *mut FFI_ArrowSchema,
+) -> c_int {
+    if handle.is_null() || array.is_null() || schema.is_null() {
+        crate::set_last_error(&crate::error::Error::NullPointer {
+            message: "table handle, array, and schema cannot be null".to_string(),
+            location: snafu::Location::new(file!(), line!(), column!()),
+        });
+        return -1;
+    }
+
+    let table = unsafe { &*handle };
+    let keys = match unsafe { crate::c_str_array_arg(on_columns, n_cols, "on_columns") } {
+        Ok(k) => k,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    let batch = match unsafe { import_record_batch_from_c(array, schema) } {
+        Ok(b) => b,
+        Err(err) => {
+            crate::set_last_error(&err);
+            return -1;
+        }
+    };
+
+    match table.merge_insert(
+        batch,
+        &keys,
+        when_matched_update_all,
+        when_not_matched_insert_all,
+    ) {
+        Ok(_) => 0,
+        Err(err) => {
+            crate::set_last_error(&err);
+            -1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{temp_connection, vector_batch, vector_table};
+    use arrow_array::{Int32Array, StringArray};
+    use std::ffi::CStr;
+
+    fn tenant_batch(tenants: Vec<i32>, docs: Vec<i32>, values: Vec<&str>) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("tenant_id", DataType::Int32, false),
+            Field::new("doc_id", DataType::Int32, false),
+            Field::new("value", DataType::Utf8, true),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from(tenants)),
+                Arc::new(Int32Array::from(docs)),
+                Arc::new(StringArray::from(values)),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn count_matching(table: &TableHandle, filter: &str) -> usize {
+        table.count_rows_filtered(Some(filter)).unwrap() as usize
+    }
+
+    #[test]
+    fn test_upsert_composite_key() {
+        let (_dir, conn) = temp_connection();
+        let initial = tenant_batch(vec![1, 1, 2], vec![1, 2, 1], vec!["a", "b", "c"]);
+        let table = TableHandle::create(&conn, "docs", initial.schema()).unwrap();
+        table.add_data(initial, AddDataMode::Append).unwrap();
+
+        // (1, 2) exists and is updated; (2, 2) is new and is inserted
+        let changes = tenant_batch(vec![1, 2], vec![2, 2], vec!["updated", "d"]);
+        let keys = vec!["tenant_id".to_string(), "doc_id".to_string()];
+        table.upsert(changes, &keys).unwrap();
+
+        assert_eq!(table.count_rows().unwrap(), 4);
+        assert_eq!(count_matching(&table, "tenant_id = 1 AND doc_id = 2"), 1);
+        assert_eq!(
+            count_matching(&table, "tenant_id = 1 AND doc_id = 2 AND value = 'updated'"),
+            1
+        );
+        // Same doc_id under another tenant is untouched
+        assert_eq!(
+            count_matching(&table, "tenant_id = 2 AND doc_id = 1 AND value = 'c'"),
+            1
+        );
+    }
+
+    #[test]
+    fn test_create_from_data() {
+        let (_dir, conn) = temp_connection();
+        let batch = vector_batch(0, 12, 2);
+
+        let mut array = FFI_ArrowArray::empty();
+        let mut schema = FFI_ArrowSchema::empty();
+        unsafe { crate::arrow_ffi::export_record_batch_to_c(&batch, &mut array, &mut schema) }
+            .unwrap();
+        let name = CString::new("populated").unwrap();
+        let handle =
+            lancedb_table_create_from_data(&conn, name.as_ptr(), &mut array, &mut schema, 0);
+        assert!(!handle.is_null());
+        let table = unsafe { Box::from_raw(handle) };
+        assert_eq!(table.count_rows().unwrap(), 12);
+        assert_eq!(table.schema().unwrap(), batch.schema());
+
+        let empty = TableHandle::create_from_batch(
+            &conn,
+            "empty",
+            vector_batch(0, 0, 2),
+            CreateTableMode::Create,
+        )
+        .unwrap();
+        assert_eq!(empty.count_rows().unwrap(), 0);
+        assert_eq!(empty.schema().unwrap(), batch.schema());
+
+        // Opening the existing table leaves its rows untouched
+        let existing = TableHandle::create_from_batch(
+            &conn,
+            "populated",
+            vector_batch(100, 3, 2),
+            create_table_mode(1).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(existing.count_rows().unwrap(), 12);
+    }
+
+    /// Call lancedb_table_create_with_schema_ex with the vector schema
+    fn create_with_schema_mode(
+        conn: &crate::connection::ConnectionHandle,
+        name: &str,
+        mode: c_int,
+    ) -> *mut TableHandle {
+        let mut schema = FFI_ArrowSchema::empty();
+        unsafe {
+            crate::arrow_ffi::export_schema_to_c(&crate::test_util::vector_schema(2), &mut schema)
+        }
+        .unwrap();
+        let name = CString::new(name).unwrap();
+        lancedb_table_create_with_schema_ex(conn, name.as_ptr(), &mut schema, mode)
+    }
+
+    #[test]
+    fn test_list_indices_json() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "listed", 10, 2);
+        table
+            .create_index(
+                "id",
+                "BTREE",
+                DistanceType::L2,
+                None,
+                None,
+                false,
+                &IndexOptions::default(),
+            )
+            .unwrap();
+
+        let mut json_out: *mut c_char = std::ptr::null_mut();
+        assert_eq!(lancedb_table_list_indices(&table, &mut json_out), 1);
+        let json = unsafe { CString::from_raw(json_out) }
+            .into_string()
+            .unwrap();
+        let listed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(listed[0]["name"], "id_idx");
+        assert_eq!(listed[0]["type"], "BTREE");
+        assert_eq!(listed[0]["columns"], serde_json::json!(["id"]));
+        assert!(listed[0]["distance_type"].is_null());
+
+        let vectors = vector_table(&conn, "listed_vectors", 256, 4);
+        vectors
+            .create_index(
+                "vector",
+                "IVF_PQ",
+                DistanceType::Cosine,
+                Some(2),
+                Some(2),
+                false,
+                &IndexOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(lancedb_table_list_indices(&vectors, &mut json_out), 1);
+        let json = unsafe { CString::from_raw(json_out) }
+            .into_string()
+            .unwrap();
+        let listed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(listed[0]["type"], "IVF_PQ");
+        assert_eq!(listed[0]["distance_type"], "cosine");
+        assert_eq!(listed[0]["num_partitions"], 2);
+        assert_eq!(listed[0]["num_sub_vectors"], 2);
+
+        // Names are escaped rather than pasted into the JSON
+        let description = IndexDescription {
+            name: "odd\"name".to_string(),
+            index_type: index_type_name(&IndexType::FTS),
+            columns: vec!["say \"hi\"\\\n".to_string()],
+            parameters: IndexParameters::default(),
+        };
+        let json = serde_json::to_string(&[description]).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed[0]["name"], "odd\"name");
+        assert_eq!(parsed[0]["columns"][0], "say \"hi\"\\\n");
+    }
+
+    #[test]
+    fn test_get_quotes_key_column() {
+        let (_dir, conn) = temp_connection();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("odd`key", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec!["a", "b"])),
+            ],
+        )
+        .unwrap();
+        let table = TableHandle::create(&conn, "quoted", schema).unwrap();
+        table.add_data(batch, AddDataMode::Append).unwrap();
+
+        let column = CString::new("odd`key").unwrap();
+        let found = CString::new("\"b\"").unwrap();
+        let missing = CString::new("\"c\"").unwrap();
+        let mut array = FFI_ArrowArray::empty();
+        let mut ffi_schema = FFI_ArrowSchema::empty();
+        assert_eq!(
+            lancedb_table_get(
+                &table,
+                column.as_ptr(),
+                found.as_ptr(),
+                &mut array,
+                &mut ffi_schema
+            ),
+            1
+        );
+        let batch =
+            unsafe { crate::arrow_ffi::import_record_batch_from_c(&mut array, &mut ffi_schema) }
+                .unwrap();
+        let ids = batch
+            .column_by_name("id")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .unwrap();
+        assert_eq!(ids.value(0), 2);
+
+        let mut array = FFI_ArrowArray::empty();
+        let mut ffi_schema = FFI_ArrowSchema::empty();
+        assert_eq!(
+            lancedb_table_get(
+                &table,
+                column.as_ptr(),
+                missing.as_ptr(),
+                &mut array,
+                &mut ffi_schema
+            ),
+            0
+        );
+    }
+
+    #[test]
+    fn test_get_by_key() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "kv", 10, 2);
+        let column = CString::new("id").unwrap();
+        let fetch = |result: c_int, mut array: FFI_ArrowArray, mut schema: FFI_ArrowSchema| {
+            assert_eq!(result, 0);
+            unsafe { crate::arrow_ffi::import_record_batch_from_c(&mut array, &mut schema) }
+                .unwrap()
+        };
+
+        let mut array = FFI_ArrowArray::empty();
+        let mut schema = FFI_ArrowSchema::empty();
+        let result =
+            lancedb_table_get_by_key_int(&table, column.as_ptr(), 7, &mut array, &mut schema);
+        let batch = fetch(result, array, schema);
+        assert_eq!(batch.num_rows(), 1);
+        let ids = batch
+            .column_by_name("id")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .unwrap();
+        assert_eq!(ids.value(0), 7);
+
+        // A missing key is an empty batch, not an error
+        let mut array = FFI_ArrowArray::empty();
+        let mut schema = FFI_ArrowSchema::empty();
+        let result =
+            lancedb_table_get_by_key_int(&table, column.as_ptr(), 99, &mut array, &mut schema);
+        let batch = fetch(result, array, schema);
+        assert_eq!(batch.num_rows(), 0);
+        assert_eq!(batch.schema().fields(), table.schema().unwrap().fields());
+
+        let text_column = CString::new("text").unwrap();
+        let key = CString::new("doc 3").unwrap();
+        let mut array = FFI_ArrowArray::empty();
+        let mut schema = FFI_ArrowSchema::empty();
+        let result = lancedb_table_get_by_key_string(
+            &table,
+            text_column.as_ptr(),
+            key.as_ptr(),
+            &mut array,
+            &mut schema,
+        );
+        let batch = fetch(result, array, schema);
+        assert_eq!(batch.num_rows(), 1);
+
+        let quoted = CString::new("it's missing").unwrap();
+        let mut array = FFI_ArrowArray::empty();
+        let mut schema = FFI_ArrowSchema::empty();
+        let result = lancedb_table_get_by_key_string(
+            &table,
+            text_column.as_ptr(),
+            quoted.as_ptr(),
+            &mut array,
+            &mut schema,
+        );
+        assert_eq!(fetch(result, array, schema).num_rows(), 0);
+    }
+
+    #[test]
+    fn test_get_by_key_quotes_column() {
+        let (_dir, conn) = temp_connection();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("odd`key", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec!["a", "b"])),
+            ],
+        )
+        .unwrap();
+        let table = TableHandle::create(&conn, "quoted_keys", schema).unwrap();
+        table.add_data(batch, AddDataMode::Append).unwrap();
+
+        let column = CString::new("odd`key").unwrap();
+        for (key, rows) in [("a", 1), ("c", 0)] {
+            let key = CString::new(key).unwrap();
+            let mut array = FFI_ArrowArray::empty();
+            let mut ffi_schema = FFI_ArrowSchema::empty();
+            let result = lancedb_table_get_by_key_string(
+                &table,
+                column.as_ptr(),
+                key.as_ptr(),
+                &mut array,
+                &mut ffi_schema,
+            );
+            assert_eq!(result, 0);
+            let batch = unsafe {
+                crate::arrow_ffi::import_record_batch_from_c(&mut array, &mut ffi_schema)
+            }
+            .unwrap();
+            assert_eq!(batch.num_rows(), rows);
+        }
+    }
+
+    #[test]
+    fn test_check_schema() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "checked", 3, 2);
+        let check = |schema: Schema| {
+            let mut ffi_schema = FFI_ArrowSchema::empty();
+            unsafe { crate::arrow_ffi::export_schema_to_c(&schema, &mut ffi_schema) }.unwrap();
+            lancedb_table_check_schema(&table, &mut ffi_schema)
+        };
+        let last_error = || {
+            unsafe { CStr::from_ptr(crate::lancedb_get_last_error()) }
+                .to_string_lossy()
+                .into_owned()
+        };
+        let vector =
+            |item: DataType| DataType::FixedSizeList(Arc::new(Field::new("item", item, true)), 2);
+
+        assert_eq!(
+            check(crate::test_util::vector_schema(2).as_ref().clone()),
+            SCHEMA_COMPATIBLE
+        );
+
+        // A narrower id and half-precision vectors widen losslessly
+        let castable = Schema::new(vec![
+            Field::new("id", DataType::Int16, false),
+            Field::new("text", DataType::Utf8, true),
+            Field::new("vector", vector(DataType::Float16), true),
+        ]);
+        assert_eq!(check(castable), SCHEMA_CASTABLE);
+
+        let wrong_type = Schema::new(vec![
+            Field::new("id", DataType::Utf8, false),
+            Field::new("text", DataType::Utf8, true),
+            Field::new("vector", vector(DataType::Float32), true),
+        ]);
+        assert_eq!(check(wrong_type), -1);
+        assert!(
+            last_error().contains("field 'id' has type Utf8"),
+            "{}",
+            last_error()
+        );
+
+        let nullable_id = Schema::new(vec![
+            Field::new("id", DataType::Int32, true),
+            Field::new("text", DataType::Utf8, true),
+            Field::new("vector", vector(DataType::Float32), true),
+        ]);
+        assert_eq!(check(nullable_id), -1);
+        assert!(
+            last_error().contains("'id' is nullable"),
+            "{}",
+            last_error()
+        );
+
+        let missing_vector = Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("text", DataType::Utf8, true),
+        ]);
+        assert_eq!(check(missing_vector), -1);
+        assert!(
+            last_error().contains("'vector' is missing"),
+            "{}",
+            last_error()
+        );
+
+        let wrong_dim = Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("text", DataType::Utf8, true),
+            Field::new(
+                "vector",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 3),
+                true,
+            ),
+        ]);
+        assert_eq!(check(wrong_dim), -1);
+    }
+
+    #[test]
+    fn test_create_with_storage_version() {
+        let (_dir, conn) = temp_connection();
+        let create = |name: &str, version: &str| {
+            let mut schema = FFI_ArrowSchema::empty();
+            unsafe {
+                crate::arrow_ffi::export_schema_to_c(
+                    &crate::test_util::vector_schema(2),
+                    &mut schema,
+                )
+            }
+            .unwrap();
+            let name = CString::new(name).unwrap();
+            let version = CString::new(version).unwrap();
+            lancedb_table_create_with_storage_version(
+                &conn,
+                name.as_ptr(),
+                &mut schema,
+                0,
+                version.as_ptr(),
+            )
+        };
+
+        let handle = create("pinned", "legacy");
+        assert!(!handle.is_null());
+        drop(unsafe { Box::from_raw(handle) });
+
+        let table = TableHandle::open(&conn, "pinned").unwrap();
+        table
+            .add_data(vector_batch(0, 5, 2), AddDataMode::Append)
+            .unwrap();
+        assert_eq!(table.count_rows().unwrap(), 5);
+        let format = table
+            .dataset()
+            .unwrap()
+            .manifest()
+            .data_storage_format
+            .clone();
+        assert_eq!(format.version, LanceFileVersion::Legacy.to_string());
+
+        assert!(create("unpinned", "3.7").is_null());
+        assert_eq!(
+            crate::lancedb_get_last_error_code(),
+            crate::error::ERROR_INVALID_ARGUMENT
+        );
+    }
+
+    #[test]
+    fn test_create_mode_create_fails_on_existing() {
+        let (_dir, conn) = temp_connection();
+        let _existing = vector_table(&conn, "existing", 5, 2);
+
+        assert!(create_with_schema_mode(&conn, "existing", 0).is_null());
+        assert_eq!(
+            crate::lancedb_get_last_error_code(),
+            crate::error::ERROR_TABLE_ALREADY_EXISTS
+        );
+    }
+
+    #[test]
+    fn test_create_mode_exist_ok_opens_existing() {
+        let (_dir, conn) = temp_connection();
+        let _existing = vector_table(&conn, "existing", 5, 2);
+
+        let handle = create_with_schema_mode(&conn, "existing", 1);
+        assert!(!handle.is_null());
+        let table = unsafe { Box::from_raw(handle) };
+        assert_eq!(table.count_rows().unwrap(), 5);
+    }
+
+    #[test]
+    fn test_create_mode_overwrite_replaces_existing() {
+        let (_dir, conn) = temp_connection();
+        let existing = vector_table(&conn, "existing", 5, 2);
+
+        let handle = create_with_schema_mode(&conn, "existing", 2);
+        assert!(!handle.is_null());
+        let table = unsafe { Box::from_raw(handle) };
+        assert_eq!(table.count_rows().unwrap(), 0);
+
+        // A handle opened before the overwrite sees it once refreshed
+        existing.checkout_latest().unwrap();
+        assert_eq!(existing.count_rows().unwrap(), 0);
+
+        assert!(create_with_schema_mode(&conn, "existing", 3).is_null());
+    }
+
+    #[test]
+    fn test_table_stats() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "monitored", 10, 2);
+        table
+            .add_data(vector_batch(10, 5, 2), AddDataMode::Append)
+            .unwrap();
+        table.delete_rows("id < 2").unwrap();
+
+        let mut stats_out: *mut c_char = std::ptr::null_mut();
+        assert_eq!(lancedb_table_stats(&table, &mut stats_out), 0);
+        let json = unsafe { CString::from_raw(stats_out) }
+            .into_string()
+            .unwrap();
+        let stats: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(stats["num_fragments"], 2);
+        assert_eq!(stats["num_rows"], 13);
+        assert_eq!(stats["num_deleted_rows"], 2);
+        assert_eq!(stats["num_indexed_rows"], 0);
+        assert!(stats["total_bytes"].as_u64().unwrap() > 0);
+    }
+
+    #[test]
+    fn test_dataset_follows_checkout() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = vec![("allow_http".to_string(), "true".to_string())];
+        let conn = crate::connection::ConnectionHandle::create_with_options(
+            dir.path().to_str().unwrap(),
+            &options,
+            None,
+        )
+        .unwrap();
+        let table = vector_table(&conn, "pinned_stats", 3, 2);
+        assert_eq!(table.storage_options.get("allow_http").unwrap(), "true");
+        let first = table.version().unwrap();
+        table
+            .add_data(vector_batch(3, 4, 2), AddDataMode::Append)
+            .unwrap();
+        assert_eq!(table.stats().unwrap().num_rows, 7);
+
+        table.checkout(first).unwrap();
+        assert_eq!(table.dataset().unwrap().version().version, first);
+        let stats = table.stats().unwrap();
+        assert_eq!(stats.num_rows, 3);
+        assert_eq!(stats.num_fragments, 1);
+
+        table.checkout_latest().unwrap();
+        assert_eq!(table.stats().unwrap().num_rows, 7);
+    }
+
+    #[test]
+    fn test_add_counted() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "counted", 4, 2);
+
+        let add = |batch: RecordBatch, mode: c_int| {
+            let mut array = std::mem::MaybeUninit::<FFI_ArrowArray>::uninit();
+            let mut schema = std::mem::MaybeUninit::<FFI_ArrowSchema>::uninit();
+            unsafe {
+                crate::arrow_ffi::export_record_batch_to_c(
+                    &batch,
+                    array.as_mut_ptr(),
+                    schema.as_mut_ptr(),
+                )
+                .unwrap();
+            }
+            lancedb_table_add_counted(&table, array.as_mut_ptr(), schema.as_mut_ptr(), mode)
+        };
+
+        assert_eq!(add(vector_batch(4, 7, 2), 0), 7);
+        assert_eq!(table.count_rows().unwrap(), 11);
+
+        // An overwrite reports the new table size
+        assert_eq!(add(vector_batch(0, 3, 2), 1), 3);
+        assert_eq!(table.count_rows().unwrap(), 3);
+
+        assert_eq!(add(vector_batch(0, 3, 2), 2), -1);
+    }
+
+    #[test]
+    fn test_add_with_options_sizes_fragments() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "sized", 1, 2);
+        assert_eq!(table.stats().unwrap().num_fragments, 1);
+
+        // Ten rows at no more than three per file make four fragments
+        let batch = vector_batch(1, 10, 2);
+        let mut array = std::mem::MaybeUninit::<FFI_ArrowArray>::uninit();
+        let mut schema = std::mem::MaybeUninit::<FFI_ArrowSchema>::uninit();
+        unsafe {
+            crate::arrow_ffi::export_record_batch_to_c(
+                &batch,
+                array.as_mut_ptr(),
+                schema.as_mut_ptr(),
+            )
+            .unwrap();
+        }
+        let rc = lancedb_table_add_with_options(
+            &table,
+            array.as_mut_ptr(),
+            schema.as_mut_ptr(),
+            0,
+            3,
+            3,
+        );
+        assert_eq!(rc, 0);
+        let stats = table.stats().unwrap();
+        assert_eq!(stats.num_rows, 11);
+        assert_eq!(stats.num_fragments, 5);
+
+        // Without limits each write adds a single fragment
+        table
+            .add_data_with_options(vector_batch(11, 10, 2), AddDataMode::Append, None, None)
+            .unwrap();
+        assert_eq!(table.stats().unwrap().num_fragments, 6);
+    }
+
+    #[test]
+    fn test_create_index_async_with_options() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "background", 256, 4);
+        let column = CString::new("vector").unwrap();
+        let index_type = CString::new("IVF_HNSW_SQ").unwrap();
+
+        // Options are validated before the build starts
+        let too_small = CString::new(r#"{"memory_limit": 1}"#).unwrap();
+        let job = lancedb_table_create_index_async_ex(
+            &table,
+            column.as_ptr(),
+            index_type.as_ptr(),
+            0,
+            2,
+            0,
+            false,
+            too_small.as_ptr(),
+        );
+        assert!(job.is_null());
+        let invalid = CString::new(r#"{"unknown": 1}"#).unwrap();
+        let job = lancedb_table_create_index_async_ex(
+            &table,
+            column.as_ptr(),
+            index_type.as_ptr(),
+            0,
+            2,
+            0,
+            false,
+            invalid.as_ptr(),
+        );
+        assert!(job.is_null());
+
+        let options = CString::new(r#"{"m": 8, "ef_construction": 64}"#).unwrap();
+        let job = lancedb_table_create_index_async_ex(
+            &table,
+            column.as_ptr(),
+            index_type.as_ptr(),
+            0,
+            2,
+            0,
+            false,
+            options.as_ptr(),
+        );
+        assert!(!job.is_null());
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let status = loop {
+            let status = lancedb_index_job_status(job);
+            if status == INDEX_JOB_DONE || status == INDEX_JOB_ERROR || Instant::now() > deadline {
+                break status;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        lancedb_index_job_free(job);
+        assert_eq!(status, INDEX_JOB_DONE);
+        assert!(table.has_vector_index("vector").unwrap());
+    }
+
+    #[test]
+    fn test_wait_for_index() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "awaited", 256, 4);
+        let job = table
+            .create_index_async(
+                "vector",
+                "IVF_PQ",
+                DistanceType::L2,
+                Some(2),
+                Some(2),
+                false,
+                &IndexOptions::default(),
+            )
+            .unwrap();
+
+        let names = [CString::new("vector_idx").unwrap()];
+        let name_ptrs: Vec<*const c_char> = names.iter().map(|n| n.as_ptr()).collect();
+        assert_eq!(
+            lancedb_table_wait_for_index(&table, name_ptrs.as_ptr(), 1, 60_000),
+            0
+        );
+        assert_ne!(job.status(), INDEX_JOB_ERROR);
+        assert_eq!(
+            table.index_stats("vector_idx").unwrap().num_unindexed_rows,
+            0
+        );
+
+        // Appended rows stay unindexed until the index is optimized
+        table
+            .add_data(vector_batch(256, 4, 4), AddDataMode::Append)
+            .unwrap();
+        assert_eq!(
+            lancedb_table_wait_for_index(&table, name_ptrs.as_ptr(), 1, 50),
+            WAIT_TIMED_OUT
+        );
+        assert_eq!(
+            crate::lancedb_get_last_error_code(),
+            crate::error::ERROR_TIMED_OUT
+        );
+    }
+
+    #[test]
+    fn test_index_stats() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "indexed", 256, 4);
+        table
+            .create_index(
+                "vector",
+                "IVF_PQ",
+                DistanceType::Cosine,
+                Some(2),
+                Some(2),
+                false,
+                &IndexOptions::default(),
+            )
+            .unwrap();
+        table
+            .add_data(vector_batch(256, 10, 4), AddDataMode::Append)
+            .unwrap();
+
+        let name = CString::new("vector_idx").unwrap();
+        let mut stats_out: *mut c_char = std::ptr::null_mut();
+        assert_eq!(
+            lancedb_table_index_stats(&table, name.as_ptr(), &mut stats_out),
+            0
+        );
+        let json = unsafe { CString::from_raw(stats_out) }
+            .into_string()
+            .unwrap();
+        let stats: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(stats["index_type"], "IVF_PQ");
+        assert_eq!(stats["num_indexed_rows"], 256);
+        assert_eq!(stats["num_unindexed_rows"], 10);
+        assert_eq!(stats["distance_type"], "cosine");
+        assert_eq!(stats["num_partitions"], 2);
+
+        let missing = CString::new("missing_idx").unwrap();
+        assert_eq!(
+            lancedb_table_index_stats(&table, missing.as_ptr(), &mut stats_out),
+            -1
+        );
+    }
+
+    #[test]
+    fn test_count_rows_filtered() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "counted", 25, 2);
+
+        let filter = CString::new("id > 10").unwrap();
+        assert_eq!(
+            lancedb_table_count_rows_filtered(&table, filter.as_ptr()),
+            14
+        );
+        assert_eq!(
+            lancedb_table_count_rows_filtered(&table, std::ptr::null()),
+            25
+        );
+
+        let invalid = CString::new("id >").unwrap();
+        assert_eq!(
+            lancedb_table_count_rows_filtered(&table, invalid.as_ptr()),
+            -1
+        );
+        assert!(!crate::lancedb_get_last_error().is_null());
+    }
+
+    #[test]
+    fn test_count_rows_approx() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "approx", 25, 2);
+        let first = table.version().unwrap();
+
+        // With few fragments the estimate is exact
+        let filter = CString::new("id > 10").unwrap();
+        assert_eq!(lancedb_table_count_rows_approx(&table, filter.as_ptr()), 14);
+        assert_eq!(
+            lancedb_table_count_rows_approx(&table, std::ptr::null()),
+            25
+        );
+
+        // Deleted rows are subtracted using the counts the deletion files record
+        table.delete_rows("id < 5").unwrap();
+        assert_eq!(
+            lancedb_table_count_rows_approx(&table, std::ptr::null()),
+            20
+        );
+        assert_eq!(lancedb_table_count_rows_approx(&table, filter.as_ptr()), 14);
+
+        // Spread the rows over more fragments than are sampled; the match
+        // rate of the live rows is scaled to the live rows of the table
+        for start in (25..205).step_by(10) {
+            table
+                .add_data(vector_batch(start, 10, 2), AddDataMode::Append)
+                .unwrap();
+        }
+        assert!(table.dataset().unwrap().get_fragments().len() > APPROX_COUNT_SAMPLE_FRAGMENTS);
+        assert_eq!(table.count_rows().unwrap(), 200);
+        assert_eq!(
+            lancedb_table_count_rows_approx(&table, std::ptr::null()),
+            200
+        );
+        let everything = CString::new("id >= 0").unwrap();
+        assert_eq!(
+            lancedb_table_count_rows_approx(&table, everything.as_ptr()),
+            200
+        );
+
+        // A pinned handle is counted at its version
+        let name = CString::new("approx").unwrap();
+        let pinned = lancedb_table_open_version(&conn, name.as_ptr(), first);
+        assert!(!pinned.is_null());
+        let pinned = unsafe { Box::from_raw(pinned) };
+        assert_eq!(
+            lancedb_table_count_rows_approx(&pinned, std::ptr::null()),
+            25
+        );
+        assert_eq!(
+            lancedb_table_count_rows_approx(&pinned, filter.as_ptr()),
+            14
+        );
+
+        let invalid = CString::new("id >").unwrap();
+        assert_eq!(
+            lancedb_table_count_rows_approx(&table, invalid.as_ptr()),
+            -1
+        );
+    }
+
+    #[test]
+    fn test_open_with_index_cache_size() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "cached", 256, 4);
+        table
+            .create_index(
+                "vector",
+                "IVF_PQ",
+                DistanceType::L2,
+                Some(2),
+                Some(2),
+                false,
+                &IndexOptions::default(),
+            )
+            .unwrap();
+
+        let name = CString::new("cached").unwrap();
+        for size in [16, 0] {
+            let handle = lancedb_table_open_with_options(&conn, name.as_ptr(), size);
+            assert!(!handle.is_null());
+            let reopened = unsafe { Box::from_raw(handle) };
+
+            let mut query = crate::query::QueryHandle::new(reopened.inner.clone());
+            query.nearest_to(vec![3.0; 4]).unwrap();
+            query.limit(5).unwrap();
+            let rows: usize = query.execute().unwrap().iter().map(|b| b.num_rows()).sum();
+            assert_eq!(rows, 5);
+        }
+    }
+
+    #[test]
+    fn test_to_arrow_pages() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "paged", 10, 2);
+
+        let mut seen = Vec::new();
+        let mut offset = 0;
+        loop {
+            let mut arrays: *mut FFI_ArrowArray = std::ptr::null_mut();
+            let mut schemas: *mut FFI_ArrowSchema = std::ptr::null_mut();
+            let mut count: c_int = 0;
+            let rc =
+                lancedb_table_to_arrow_ex(&table, 3, offset, &mut arrays, &mut schemas, &mut count);
+            assert_eq!(rc, 0);
+
+            let mut page = Vec::new();
+            for i in 0..count as usize {
+                let batch =
+                    unsafe { import_record_batch_from_c(arrays.add(i), schemas.add(i)) }.unwrap();
+                let ids = batch
+                    .column_by_name("id")
+                    .unwrap()
+                    .as_any()
+                    .downcast_ref::<Int32Array>()
+                    .unwrap();
+                page.extend(ids.values().iter().copied());
+            }
+            unsafe {
+                libc::free(arrays as *mut c_void);
+                libc::free(schemas as *mut c_void);
+            }
+
+            if page.is_empty() {
+                break;
+            }
+            assert!(page.len() <= 3);
+            seen.extend(page);
+            offset += 3;
+        }
+        assert_eq!(seen, (0..10).collect::<Vec<i32>>());
+
+        // A negative offset reads from the start
+        let mut arrays: *mut FFI_ArrowArray = std::ptr::null_mut();
+        let mut schemas: *mut FFI_ArrowSchema = std::ptr::null_mut();
+        let mut count: c_int = 0;
+        let rc = lancedb_table_to_arrow_ex(&table, 2, -5, &mut arrays, &mut schemas, &mut count);
+        assert_eq!(rc, 0);
+        assert_eq!(count, 1);
+        let batch = unsafe { import_record_batch_from_c(arrays, schemas) }.unwrap();
+        let ids = batch
+            .column_by_name("id")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .unwrap();
+        assert_eq!(ids.values().to_vec(), vec![0, 1]);
+        unsafe {
+            libc::free(arrays as *mut c_void);
+            libc::free(schemas as *mut c_void);
+        }
+    }
+
+    #[test]
+    fn test_commit_conflict_retries() {
+        let conflict = || lancedb::Error::Lance {
+            source: lance::Error::CommitConflict {
+                version: 1,
+                source: "another writer committed first".into(),
+                location: snafu::Location::new(file!(), line!(), column!()),
+            },
+        };
+        // Fails with a conflict the first `conflicts` times it is called
+        let run = |max_retries: u32, conflicts: u32| {
+            let (commits, refreshes) = (AtomicU32::new(0), AtomicU32::new(0));
+            let result = RT.block_on(retry_on_conflict(
+                max_retries,
+                || {
+                    let attempt = commits.fetch_add(1, Ordering::SeqCst);
+                    async move {
+                        if attempt < conflicts {
+                            Err(conflict())
+                        } else {
+                            Ok(())
+                        }
+                    }
+                },
+                || {
+                    refreshes.fetch_add(1, Ordering::SeqCst);
+                    async { Ok(()) }
+                },
+            ));
+            (result, commits.into_inner(), refreshes.into_inner())
+        };
+
+        let (result, commits, refreshes) = run(DEFAULT_COMMIT_RETRIES, 2);
+        assert!(result.is_ok());
+        assert_eq!((commits, refreshes), (3, 2));
+
+        // Conflicts beyond the retry count are returned
+        let (result, commits, refreshes) = run(1, 3);
+        assert!(is_commit_conflict(&result.unwrap_err()));
+        assert_eq!((commits, refreshes), (2, 1));
+
+        // Other errors are never retried
+        let (commits, refreshes) = (AtomicU32::new(0), AtomicU32::new(0));
+        let result = RT.block_on(retry_on_conflict(
+            DEFAULT_COMMIT_RETRIES,
+            || {
+                commits.fetch_add(1, Ordering::SeqCst);
+                async {
+                    Err(lancedb::Error::InvalidInput {
+                        message: "bad batch".to_string(),
+                    })
+                }
+            },
+            || {
+                refreshes.fetch_add(1, Ordering::SeqCst);
+                async { Ok(()) }
+            },
+        ));
+        assert!(result.is_err());
+        assert_eq!((commits.into_inner(), refreshes.into_inner()), (1, 0));
+    }
+
+    #[test]
+    fn test_copy_into() {
+        let (_dir, conn) = temp_connection();
+        let source = vector_table(&conn, "source", 10, 2);
+        let destination = vector_table(&conn, "destination", 3, 2);
+
+        let filter = CString::new("id >= 5").unwrap();
+        assert_eq!(
+            lancedb_table_copy_into(&source, &destination, filter.as_ptr(), 0),
+            0
+        );
+        assert_eq!(destination.count_rows().unwrap(), 8);
+        assert_eq!(source.count_rows().unwrap(), 10);
+
+        // Copying no rows appends nothing
+        let version = destination.version().unwrap();
+        assert_eq!(
+            source
+                .copy_into(&destination, Some("id > 100"), AddDataMode::Append)
+                .unwrap(),
+            0
+        );
+        assert_eq!(destination.version().unwrap(), version);
+
+        assert_eq!(
+            source
+                .copy_into(&destination, Some("id < 2"), AddDataMode::Overwrite)
+                .unwrap(),
+            2
+        );
+        assert_eq!(destination.count_rows().unwrap(), 2);
+
+        // The schemas are compared before anything is read
+        let wider = vector_table(&conn, "wider", 5, 3);
+        let err = wider
+            .copy_into(&destination, None, AddDataMode::Append)
+            .unwrap_err();
+        assert!(err.to_string().contains("schemas differ"), "{}", err);
+        assert_eq!(destination.count_rows().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_take_by_row_id() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "lookup", 10, 2);
+
+        // Row ids of the single fragment are the row offsets
+        let columns = [CString::new("id").unwrap()];
+        let column_ptrs: Vec<*const c_char> = columns.iter().map(|c| c.as_ptr()).collect();
+        let mut array = std::mem::MaybeUninit::<FFI_ArrowArray>::uninit();
+        let mut schema = std::mem::MaybeUninit::<FFI_ArrowSchema>::uninit();
+        let rc = lancedb_table_take(
+            &table,
+            [7u64, 2].as_ptr(),
+            2,
+            column_ptrs.as_ptr(),
+            1,
+            array.as_mut_ptr(),
+            schema.as_mut_ptr(),
+        );
+        assert_eq!(rc, 0);
+        let batch =
+            unsafe { import_record_batch_from_c(array.as_mut_ptr(), schema.as_mut_ptr()) }.unwrap();
+        let ids = batch
+            .column_by_name("id")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .unwrap();
+        assert_eq!(ids.values().to_vec(), vec![7, 2]);
+        assert!(batch.column_by_name("_rowid").is_some());
+        assert!(batch.column_by_name("vector").is_none());
+
+        // Deleted rows are skipped
+        table.delete_rows("id = 2").unwrap();
+        let batch = table.take(&[7, 2], None).unwrap();
+        assert_eq!(batch.num_rows(), 1);
+        let row_ids = batch
+            .column_by_name("_rowid")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        assert_eq!(row_ids.value(0), 7);
+
+        // Unknown fragments and offsets are skipped and repeats yield one row
+        let batch = table.take(&[1 << 32, 3, 10, 3, 0], None).unwrap();
+        let row_ids = batch
+            .column_by_name("_rowid")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        assert_eq!(row_ids.values().to_vec(), vec![3, 0]);
+        assert_eq!(table.take(&[1 << 32], None).unwrap().num_rows(), 0);
+    }
+
+    #[test]
+    fn test_batch_search() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "batched", 20, 2);
+
+        // Vector elements equal the id, so each query's nearest rows are
+        // known
+        let vectors = [3.0f32, 3.0, 10.0, 10.0, 17.0, 17.0];
+        let mut arrays: *mut FFI_ArrowArray = std::ptr::null_mut();
+        let mut schemas: *mut FFI_ArrowSchema = std::ptr::null_mut();
+        let mut count: c_int = 0;
+        let rc = lancedb_table_batch_search(
+            &table,
+            vectors.as_ptr(),
+            3,
+            2,
+            2,
+            &mut arrays,
+            &mut schemas,
+            &mut count,
+        );
+        assert_eq!(rc, 0);
+
+        let mut results: Vec<(u32, i32)> = Vec::new();
+        for i in 0..count as usize {
+            let batch =
+                unsafe { import_record_batch_from_c(arrays.add(i), schemas.add(i)) }.unwrap();
+            let query_index = batch
+                .column_by_name(QUERY_INDEX_COLUMN)
+                .unwrap()
+                .as_any()
+                .downcast_ref::<UInt32Array>()
+                .unwrap();
+            let ids = batch
+                .column_by_name("id")
+                .unwrap()
+                .as_any()
+                .downcast_ref::<Int32Array>()
+                .unwrap();
+            results.extend(
+                query_index
+                    .values()
+                    .iter()
+                    .copied()
+                    .zip(ids.values().iter().copied()),
+            );
+        }
+        unsafe {
+            libc::free(arrays as *mut c_void);
+            libc::free(schemas as *mut c_void);
+        }
+        assert_eq!(results.len(), 6);
+        for (query, expected) in [(0, 3), (1, 10), (2, 17)] {
+            let ids: Vec<i32> = results
+                .iter()
+                .filter(|(q, _)| *q == query)
+                .map(|(_, id)| *id)
+                .collect();
+            assert_eq!(ids.len(), 2);
+            assert_eq!(ids[0], expected);
+        }
+
+        // A dimension that does not match the vector column fails up front
+        let err = table.batch_search(&vectors, 3, 2).unwrap_err();
+        assert!(err.to_string().contains("dimension 3"), "{}", err);
+    }
+
+    #[test]
+    fn test_delete_rows() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "prune", 10, 2);
+
+        let predicate = CString::new("id >= 6").unwrap();
+        assert_eq!(lancedb_table_delete(&table, predicate.as_ptr()), 0);
+        assert_eq!(table.count_rows().unwrap(), 6);
+
+        assert_eq!(lancedb_table_delete(&table, std::ptr::null()), -1);
+
+        let invalid = CString::new("id >=").unwrap();
+        assert_eq!(lancedb_table_delete(&table, invalid.as_ptr()), -1);
+        let error = unsafe { CStr::from_ptr(crate::lancedb_get_last_error()) };
+        assert!(!error.to_str().unwrap().is_empty());
+        assert_eq!(table.count_rows().unwrap(), 6);
+    }
+
+    #[test]
+    fn test_update_with_predicate() {
+        let (_dir, conn) = temp_connection();
+        let initial = tenant_batch(vec![1, 1, 2], vec![1, 2, 1], vec!["a", "b", "c"]);
+        let table = TableHandle::create(&conn, "updates", initial.schema()).unwrap();
+        table.add_data(initial, AddDataMode::Append).unwrap();
+
+        let updates = vec![
+            ("value".to_string(), "'archived'".to_string()),
+            ("doc_id".to_string(), "doc_id + 10".to_string()),
+        ];
+        table.update(Some("tenant_id = 1"), &updates).unwrap();
+
+        assert_eq!(count_matching(&table, "value = 'archived'"), 2);
+        assert_eq!(count_matching(&table, "doc_id > 10"), 2);
+        assert_eq!(count_matching(&table, "tenant_id = 2 AND value = 'c'"), 1);
+
+        let err = table.update(None, &[]).unwrap_err();
+        assert!(matches!(err, crate::error::Error::InvalidArgument { .. }));
+    }
+
+    #[test]
+    fn test_merge_insert_existing_key() {
+        let (_dir, conn) = temp_connection();
+        let initial = tenant_batch(vec![1, 1], vec![1, 2], vec!["a", "b"]);
+        let table = TableHandle::create(&conn, "merge", initial.schema()).unwrap();
+        table.add_data(initial, AddDataMode::Append).unwrap();
+        let keys = vec!["tenant_id".to_string(), "doc_id".to_string()];
+
+        // Merging the same key twice must not duplicate the row
+        for value in ["first", "second"] {
+            let batch = tenant_batch(vec![1], vec![2], vec![value]);
+            table.merge_insert(batch, &keys, true, true).unwrap();
+        }
+        assert_eq!(table.count_rows().unwrap(), 2);
+        assert_eq!(count_matching(&table, "doc_id = 2 AND value = 'second'"), 1);
+
+        // Insert-only leaves matched rows untouched
+        let batch = tenant_batch(vec![1, 1], vec![1, 3], vec!["ignored", "c"]);
+        table.merge_insert(batch, &keys, false, true).unwrap();
+        assert_eq!(table.count_rows().unwrap(), 3);
+        assert_eq!(count_matching(&table, "doc_id = 1 AND value = 'a'"), 1);
+
+        let other_schema = Arc::new(Schema::new(vec![
+            Field::new("tenant_id", DataType::Int32, false),
+            Field::new("doc_id", DataType::Utf8, false),
+        ]));
+        let mismatched = RecordBatch::try_new(
+            other_schema,
+            vec![
+                Arc::new(Int32Array::from(vec![1])),
+                Arc::new(StringArray::from(vec!["1"])),
+            ],
+        )
+        .unwrap();
+        let err = table
+            .merge_insert(mismatched, &keys, true, true)
+            .unwrap_err();
+        assert!(matches!(err, crate::error::Error::InvalidArgument { .. }));
+    }
+
+    #[test]
+    fn test_drop_index() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "indexed", 512, 8);
+        table
+            .create_index(
+                "vector",
+                "IVF_PQ",
+                DistanceType::L2,
+                Some(2),
+                Some(2),
+                true,
+                &IndexOptions::default(),
+            )
+            .unwrap();
+        let indices = table.list_indices().unwrap();
+        assert_eq!(indices.len(), 1);
+
+        table.drop_index(&indices[0].name).unwrap();
+        assert!(table.list_indices().unwrap().is_empty());
+
+        let err = table.drop_index(&indices[0].name).unwrap_err();
+        assert!(
+            err.to_string().contains("not found"),
+            "unexpected error: {}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_optimize_compacts_fragments() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "fragmented", 2, 2);
+        for start in 1..10 {
+            table
+                .add_data(vector_batch(start * 2, 2, 2), AddDataMode::Append)
+                .unwrap();
+        }
+        let fragments_before = table.dataset().unwrap().get_fragments().len();
+        assert_eq!(fragments_before, 10);
+
+        let summary = table.optimize(OptimizeMode::Compact).unwrap();
+        assert_eq!(summary.fragments_removed, fragments_before);
+        assert_eq!(summary.fragments_added, 1);
+        assert_eq!(table.dataset().unwrap().get_fragments().len(), 1);
+        assert_eq!(table.count_rows().unwrap(), 20);
+    }
+
+    #[test]
+    fn test_checkout_version() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "history", 3, 2);
+        let version = table.version().unwrap();
+        table
+            .add_data(vector_batch(3, 4, 2), AddDataMode::Append)
+            .unwrap();
+        assert_eq!(table.count_rows().unwrap(), 7);
+
+        let versions = table.list_versions().unwrap();
+        assert!(versions.iter().any(|(v, _)| *v == version));
+        assert!(versions.windows(2).all(|w| w[0].0 < w[1].0));
+
+        table.checkout(version).unwrap();
+        assert_eq!(table.count_rows().unwrap(), 3);
+
+        assert!(table.checkout(version + 100).is_err());
+
+        table.checkout_latest().unwrap();
+        assert_eq!(table.count_rows().unwrap(), 7);
+    }
+
+    #[test]
+    fn test_open_version() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "pinned", 3, 2);
+        let first = table.version().unwrap();
+        table
+            .add_data(vector_batch(3, 4, 2), AddDataMode::Append)
+            .unwrap();
+
+        let name = CString::new("pinned").unwrap();
+        let handle = lancedb_table_open_version(&conn, name.as_ptr(), first);
+        assert!(!handle.is_null());
+        let pinned = unsafe { Box::from_raw(handle) };
+        assert_eq!(pinned.version().unwrap(), first);
+        assert_eq!(pinned.count_rows().unwrap(), 3);
+
+        let handle = lancedb_table_open_version(&conn, name.as_ptr(), 0);
+        assert!(!handle.is_null());
+        let latest = unsafe { Box::from_raw(handle) };
+        assert_eq!(latest.count_rows().unwrap(), 7);
+
+        let handle = lancedb_table_open_version(&conn, name.as_ptr(), first + 100);
+        assert!(handle.is_null());
+    }
+
+    #[test]
+    fn test_restore_after_overwrite() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "restore", 5, 2);
+        let good_version = table.version().unwrap();
+
+        table
+            .add_data(vector_batch(100, 1, 2), AddDataMode::Overwrite)
+            .unwrap();
+        assert_eq!(table.count_rows().unwrap(), 1);
+        let bad_version = table.version().unwrap();
+
+        let err = table.restore(bad_version + 1).unwrap_err();
+        assert!(matches!(err, crate::error::Error::InvalidArgument { .. }));
+
+        table.restore(good_version).unwrap();
+        assert_eq!(table.count_rows().unwrap(), 5);
+        assert_eq!(count_matching(&table, "id < 5"), 5);
+        assert!(table.version().unwrap() > bad_version);
+
+        // The overwrite is still in the history
+        table.checkout(bad_version).unwrap();
+        assert_eq!(table.count_rows().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_add_derived_column() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "derived", 4, 2);
+
+        let definitions = vec![("doubled".to_string(), "id * 2".to_string())];
+        table.add_columns(&definitions).unwrap();
+
+        let batches = table.to_arrow(None, 0).unwrap();
+        for batch in &batches {
+            let ids = batch
+                .column_by_name("id")
+                .unwrap()
+                .as_any()
+                .downcast_ref::<Int32Array>()
+                .unwrap();
+            let doubled =
+                arrow::compute::cast(batch.column_by_name("doubled").unwrap(), &DataType::Int64)
+                    .unwrap();
+            let doubled = doubled
+                .as_any()
+                .downcast_ref::<arrow_array::Int64Array>()
+                .unwrap();
+            for i in 0..batch.num_rows() {
+                assert_eq!(doubled.value(i), ids.value(i) as i64 * 2);
+            }
+        }
+
+        let err = table.add_columns(&definitions).unwrap_err();
+        assert!(
+            err.to_string().contains("already exists"),
+            "unexpected error: {}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_drop_columns() {
+        let (_dir, conn) = temp_connection();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("label", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec!["a", "b"])),
+            ],
+        )
+        .unwrap();
+        let table = TableHandle::create(&conn, "narrow", schema).unwrap();
+        table.add_data(batch, AddDataMode::Append).unwrap();
+
+        assert!(table.drop_columns(&["missing".to_string()]).is_err());
+        let all = vec!["id".to_string(), "label".to_string()];
+        assert!(table.drop_columns(&all).is_err());
+
+        table.drop_columns(&["label".to_string()]).unwrap();
+        let schema = table.schema().unwrap();
+        assert_eq!(schema.fields().len(), 1);
+        assert_eq!(schema.field(0).name(), "id");
+        assert_eq!(table.count_rows().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_alter_column_rename_and_cast() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "altered", 3, 2);
+
+        table
+            .alter_column("text", Some("body"), None, None)
+            .unwrap();
+        let schema = table.schema().unwrap();
+        assert!(schema.field_with_name("text").is_err());
+        assert_eq!(
+            schema.field_with_name("body").unwrap().data_type(),
+            &DataType::Utf8
+        );
+
+        table
+            .alter_column("id", None, Some(parse_data_type("int64").unwrap()), None)
+            .unwrap();
+        let schema = table.schema().unwrap();
+        assert_eq!(
+            schema.field_with_name("id").unwrap().data_type(),
+            &DataType::Int64
+        );
+
+        // "doc 0" is not a number, so the cast must fail rather than produce nulls
+        let err = table
+            .alter_column("body", None, Some(DataType::Int32), None)
+            .unwrap_err();
+        assert!(!err.to_string().is_empty());
+        assert_eq!(table.count_rows().unwrap(), 3);
+
+        // Several alterations commit together, and none applies if one of
+        // the casts would lose information
+        let version = table.version().unwrap();
+        let err = table
+            .alter_columns(&[
+                ColumnAlteration::new("id".to_string()).rename("key".to_string()),
+                ColumnAlteration::new("body".to_string()).cast_to(DataType::Int32),
+            ])
+            .unwrap_err();
+        assert!(!err.to_string().is_empty());
+        assert_eq!(table.version().unwrap(), version);
+        assert!(table.schema().unwrap().field_with_name("id").is_ok());
+
+        table
+            .alter_columns(&[
+                ColumnAlteration::new("id".to_string()).rename("key".to_string()),
+                ColumnAlteration::new("body".to_string()).set_nullable(true),
+            ])
+            .unwrap();
+        assert_eq!(table.version().unwrap(), version + 1);
+        let schema = table.schema().unwrap();
+        assert!(schema.field_with_name("key").is_ok());
+        assert!(schema.field_with_name("body").unwrap().is_nullable());
+    }
+
+    #[test]
+    fn test_create_fts_index() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "searchable", 10, 2);
+        let options = IndexOptions {
+            language: Some("English".to_string()),
+            ..Default::default()
+        };
+        table
+            .create_index("text", "FTS", DistanceType::L2, None, None, false, &options)
+            .unwrap();
+        let indices = table.list_indices().unwrap();
+        assert_eq!(indices.len(), 1);
+        assert_eq!(indices[0].columns, vec!["text".to_string()]);
+
+        let err = table
+            .create_index(
+                "id",
+                "FTS",
+                DistanceType::L2,
+                None,
+                None,
+                false,
+                &IndexOptions::default(),
+            )
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("string column"),
+            "unexpected error: {}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_create_btree_index() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "scalar", 200, 2);
+        let options = IndexOptions::default();
+        table
+            .create_index("id", "BTREE", DistanceType::L2, None, None, false, &options)
+            .unwrap();
+        assert_eq!(table.list_indices().unwrap().len(), 1);
+
+        let mut query = crate::query::QueryHandle::new(table.inner.clone());
+        query.filter("id > 150").unwrap();
+        let batches = query.execute().unwrap();
+        let mut ids: Vec<i32> = batches
+            .iter()
+            .flat_map(|b| {
+                b.column_by_name("id")
+                    .unwrap()
+                    .as_any()
+                    .downcast_ref::<Int32Array>()
+                    .unwrap()
+                    .values()
+                    .to_vec()
+            })
+            .collect();
+        ids.sort();
+        assert_eq!(ids, (151..200).collect::<Vec<_>>());
+
+        let err = table
+            .create_index(
+                "vector",
+                "BTREE",
+                DistanceType::L2,
+                None,
+                None,
+                false,
+                &options,
+            )
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("scalar column"),
+            "unexpected error: {}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_create_ivf_hnsw_sq_index() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "hnsw", 512, 8);
+        let options = IndexOptions {
+            m: Some(8),
+            ef_construction: Some(64),
+            ..Default::default()
+        };
+        table
+            .create_index(
+                "vector",
+                "IVF_HNSW_SQ",
+                DistanceType::Cosine,
+                Some(2),
+                None,
+                false,
+                &options,
+            )
+            .unwrap();
+        let indices = table.list_indices().unwrap();
+        assert_eq!(indices.len(), 1);
+        assert!(matches!(indices[0].index_type, IndexType::IvfHnswSq));
+        assert_eq!(indices[0].columns, vec!["vector".to_string()]);
+
+        let err = table
+            .create_index(
+                "id",
+                "IVF_HNSW_SQ",
+                DistanceType::L2,
+                None,
+                None,
+                false,
+                &IndexOptions::default(),
+            )
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("vector column"),
+            "unexpected error: {}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_create_ivf_hnsw_pq_index() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "hnsw_pq", 512, 8);
+        let options = IndexOptions {
+            ef_construction: Some(100),
+            ..Default::default()
+        };
+        table
+            .create_index(
+                "vector",
+                "IVF_HNSW_PQ",
+                DistanceType::L2,
+                Some(2),
+                Some(4),
+                false,
+                &options,
+            )
+            .unwrap();
+        let indices = table.list_indices().unwrap();
+        assert_eq!(indices.len(), 1);
+        assert!(matches!(indices[0].index_type, IndexType::IvfHnswPq));
+        assert!(table.has_vector_index("vector").unwrap());
+
+        let err = table
+            .create_index(
+                "text",
+                "IVF_HNSW_PQ",
+                DistanceType::L2,
+                None,
+                None,
+                false,
+                &IndexOptions::default(),
+            )
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("vector column"),
+            "unexpected error: {}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_add_stream() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "stream", 5, 2);
+
+        let batches = vec![
+            Ok(vector_batch(5, 10, 2)),
+            Ok(vector_batch(15, 10, 2)),
+            Ok(vector_batch(25, 3, 2)),
+        ];
+        let reader = RecordBatchIterator::new(batches, crate::test_util::vector_schema(2));
+        let mut stream = FFI_ArrowArrayStream::new(Box::new(reader));
+
+        assert_eq!(lancedb_table_add_stream(&table, &mut stream, 0), 0);
+        assert_eq!(table.count_rows().unwrap(), 28);
+    }
 
-    // Determine the mode
-    let add_mode = match mode {
-        0 => AddDataMode::Append,
-        1 => AddDataMode::Overwrite,
-        _ => {
-            let error_msg = "invalid mode: must be 0 (Append) or 1 (Overwrite)";
-            let c_error = CString::new(error_msg).unwrap();
-            crate::lancedb_set_last_error(c_error.as_ptr());
-            return -1;
-        }
-    };
+    #[test]
+    fn test_add_stream_empty() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "stream", 5, 2);
+        let version = table.version().unwrap();
 
-    // Add the data
-    match table.add_data(batch, add_mode) {
-        Ok(_) => 0,
-        Err(err) => {
-            let error_msg = format!("{}", err);
-            let c_error = CString::new(error_msg).unwrap();
-            crate::lancedb_set_last_error(c_error.as_ptr());
-            -1
-        }
+        let reader = RecordBatchIterator::new(vec![], crate::test_util::vector_schema(2));
+        table
+            .add_stream(Box::new(reader), AddDataMode::Append)
+            .unwrap();
+
+        assert_eq!(table.count_rows().unwrap(), 5);
+        assert_eq!(table.version().unwrap(), version);
     }
-}
 
-/// Get the schema of a table as Arrow C Data Interface structure.
-/// Returns 0 on success, -1 on failure.
-#[no_mangle]
-pub extern "C" fn lancedb_table_schema(
-    handle: *const TableHandle,
-    schema_out: *mut FFI_ArrowSchema,
-) -> c_int {
-    if handle.is_null() || schema_out.is_null() {
-        let error_msg = "table handle and schema_out cannot be null";
-        let c_error = CString::new(error_msg).unwrap();
-        crate::lancedb_set_last_error(c_error.as_ptr());
-        return -1;
+    #[test]
+    fn test_add_stream_schema_mismatch() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "stream", 5, 2);
+
+        let other = tenant_batch(vec![1], vec![1], vec!["a"]);
+        let reader = RecordBatchIterator::new(
+            vec![Ok(vector_batch(5, 10, 2)), Ok(other)],
+            crate::test_util::vector_schema(2),
+        );
+        let err = table
+            .add_stream(Box::new(reader), AddDataMode::Append)
+            .unwrap_err();
+
+        assert!(err
+            .to_string()
+            .contains("batch 1 does not match the schema of the first batch"));
+        assert_eq!(table.count_rows().unwrap(), 5);
     }
 
-    let table = unsafe { &*handle };
+    #[test]
+    fn test_append_fragments() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "bulk", 10, 2);
+        let uri = table.inner.dataset_uri().to_string();
+        let format = table
+            .dataset()
+            .unwrap()
+            .manifest()
+            .data_storage_format
+            .clone();
+        let storage_version = parse_storage_version(&format.version).unwrap();
 
-    // Get the schema
-    let schema = match table.schema() {
-        Ok(s) => s,
-        Err(err) => {
-            let error_msg = format!("{}", err);
-            let c_error = CString::new(error_msg).unwrap();
-            crate::lancedb_set_last_error(c_error.as_ptr());
-            return -1;
-        }
-    };
+        // Workers write fragments into the table directory without committing
+        let fragments: Vec<Fragment> = [10, 15]
+            .into_iter()
+            .map(|start| {
+                let batch = vector_batch(start, 5, 2);
+                let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], batch.schema());
+                let params = WriteParams {
+                    data_storage_version: Some(storage_version),
+                    ..Default::default()
+                };
+                RT.block_on(lance::dataset::fragment::FileFragment::create(
+                    &uri,
+                    0,
+                    reader,
+                    Some(params),
+                ))
+                .unwrap()
+            })
+            .collect();
+        assert_eq!(table.count_rows().unwrap(), 10);
 
-    // Export to C
-    unsafe {
-        match crate::arrow_ffi::export_schema_to_c(&schema, schema_out) {
-            Ok(_) => 0,
-            Err(err) => {
-                let error_msg = format!("{}", err);
-                let c_error = CString::new(error_msg).unwrap();
-                crate::lancedb_set_last_error(c_error.as_ptr());
-                -1
-            }
+        let json = CString::new(serde_json::to_string(&fragments).unwrap()).unwrap();
+        assert_eq!(lancedb_table_append_fragments(&table, json.as_ptr()), 0);
+        assert_eq!(table.count_rows().unwrap(), 20);
+        assert_eq!(count_matching(&table, "id >= 15"), 5);
+
+        for invalid in ["[]", "not json"] {
+            let json = CString::new(invalid).unwrap();
+            assert_eq!(lancedb_table_append_fragments(&table, json.as_ptr()), -1);
         }
+        assert_eq!(table.count_rows().unwrap(), 20);
     }
-}
 
-/// Create a table with a custom schema from Arrow C Data Interface structure.
-/// Returns a pointer to TableHandle on success, null on failure.
-#[no_mangle]
-pub extern "C" fn lancedb_table_create_with_schema(
-    connection: *const super::connection::ConnectionHandle,
-    name: *const c_char,
-    schema: *mut FFI_ArrowSchema,
-) -> *mut TableHandle {
-    if connection.is_null() || name.is_null() || schema.is_null() {
-        let error_msg = "connection, name, and schema cannot be null";
-        let c_error = CString::new(error_msg).unwrap();
-        crate::lancedb_set_last_error(c_error.as_ptr());
-        return std::ptr::null_mut();
-    }
+    #[test]
+    fn test_tables_schema_compatible() {
+        let (_dir, conn) = temp_connection();
+        let left = vector_table(&conn, "left", 3, 2);
+        let same = vector_table(&conn, "same", 5, 2);
+        let wider = vector_table(&conn, "wider", 3, 4);
+        let tenants = TableHandle::create(
+            &conn,
+            "tenants",
+            tenant_batch(vec![1], vec![1], vec!["a"]).schema(),
+        )
+        .unwrap();
+        let last_error = || {
+            unsafe { CStr::from_ptr(crate::lancedb_get_last_error()) }
+                .to_string_lossy()
+                .into_owned()
+        };
 
-    let connection = unsafe { &*connection };
-    let c_str = unsafe { CStr::from_ptr(name) };
-    let table_name = c_result!(c_str.to_str());
+        assert_eq!(lancedb_tables_schema_compatible(&left, &same), 1);
 
-    // Import the schema
-    let imported_schema = c_result!(unsafe { crate::arrow_ffi::import_schema_from_c(schema) });
+        assert_eq!(lancedb_tables_schema_compatible(&left, &wider), 0);
+        assert!(
+            last_error().contains("field 'vector' has type"),
+            "{}",
+            last_error()
+        );
 
-    let handle = c_result!(TableHandle::create(
-        connection,
-        table_name,
-        Arc::new(imported_schema)
-    ));
-    Box::into_raw(Box::new(handle))
-}
+        assert_eq!(lancedb_tables_schema_compatible(&left, &tenants), 0);
+        assert!(
+            last_error().contains("missing from the second schema"),
+            "{}",
+            last_error()
+        );
 
-/// Read data from a table as Arrow C Data Interface structures.
-/// Returns the number of batches on success, -1 on failure.
-/// limit: maximum number of rows to read (-1 for no limit)
-/// arrays_out and schemas_out will be populated with arrays of Arrow C structures.
-/// Caller is responsible for freeing the arrays and schemas.
-#[no_mangle]
-pub extern "C" fn lancedb_table_to_arrow(
-    handle: *const TableHandle,
-    limit: i64,
-    arrays_out: *mut *mut FFI_ArrowArray,
-    schemas_out: *mut *mut FFI_ArrowSchema,
-    count_out: *mut c_int,
-) -> c_int {
-    if handle.is_null() || arrays_out.is_null() || schemas_out.is_null() || count_out.is_null() {
-        let error_msg = "handle, arrays_out, schemas_out, and count_out cannot be null";
-        let c_error = CString::new(error_msg).unwrap();
-        crate::lancedb_set_last_error(c_error.as_ptr());
-        return -1;
+        assert_eq!(
+            lancedb_tables_schema_compatible(&left, std::ptr::null()),
+            -1
+        );
     }
 
-    let table = unsafe { &*handle };
+    #[test]
+    fn test_create_index_derives_num_partitions() {
+        assert_eq!(auto_num_partitions(0), 1);
+        assert_eq!(auto_num_partitions(10_000), 100);
+        assert_eq!(auto_num_partitions(usize::MAX), MAX_AUTO_PARTITIONS);
 
-    // Read the data
-    let limit_opt = if limit < 0 { None } else { Some(limit) };
-    let batches = match table.to_arrow(limit_opt) {
-        Ok(b) => b,
-        Err(err) => {
-            let error_msg = format!("{}", err);
-            let c_error = CString::new(error_msg).unwrap();
-            crate::lancedb_set_last_error(c_error.as_ptr());
-            return -1;
-        }
-    };
+        // 0 partitions through the C API means sqrt(400) = 20
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "derived", 400, 4);
+        let column = CString::new("vector").unwrap();
+        let index_type = CString::new("IVF_PQ").unwrap();
+        assert_eq!(
+            lancedb_table_create_index(
+                &table,
+                column.as_ptr(),
+                index_type.as_ptr(),
+                0,
+                0,
+                2,
+                false
+            ),
+            0
+        );
 
-    let num_batches = batches.len();
+        let name = CString::new("vector_idx").unwrap();
+        let mut stats_out: *mut c_char = std::ptr::null_mut();
+        assert_eq!(
+            lancedb_table_index_stats(&table, name.as_ptr(), &mut stats_out),
+            0
+        );
+        let json = unsafe { CString::from_raw(stats_out) }
+            .into_string()
+            .unwrap();
+        let stats: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(stats["num_partitions"], 20);
+    }
 
-    if num_batches == 0 {
-        // No data - return empty arrays
-        unsafe {
-            *arrays_out = std::ptr::null_mut();
-            *schemas_out = std::ptr::null_mut();
-            *count_out = 0;
-        }
-        return 0;
+    #[test]
+    fn test_table_metadata() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "annotated", 3, 2);
+        let get = |table: &TableHandle| {
+            let mut json_out: *mut c_char = std::ptr::null_mut();
+            assert_eq!(lancedb_table_get_metadata(table, &mut json_out), 0);
+            let json = unsafe { CString::from_raw(json_out) }
+                .into_string()
+                .unwrap();
+            serde_json::from_str::<BTreeMap<String, String>>(&json).unwrap()
+        };
+        let set = |key: &str, value: &str| {
+            let key = CString::new(key).unwrap();
+            let value = CString::new(value).unwrap();
+            lancedb_table_set_metadata(&table, key.as_ptr(), value.as_ptr())
+        };
+        assert!(!get(&table).contains_key("owner"));
+
+        let version = table.version().unwrap();
+        assert_eq!(set("owner", "search"), 0);
+        assert_eq!(set("schema_rev", "1"), 0);
+        assert_eq!(set("schema_rev", "2"), 0);
+        assert_eq!(table.version().unwrap(), version + 3);
+
+        // The metadata lives in the manifest, so a fresh handle sees it
+        let reopened = TableHandle::open(&conn, "annotated").unwrap();
+        let metadata = get(&reopened);
+        assert_eq!(metadata["owner"], "search");
+        assert_eq!(metadata["schema_rev"], "2");
+
+        assert_eq!(
+            lancedb_table_set_metadata(&table, std::ptr::null(), std::ptr::null()),
+            -1
+        );
     }
 
-    // Allocate arrays for Arrow C structures
-    let arrays_size = num_batches * std::mem::size_of::<FFI_ArrowArray>();
-    let schemas_size = num_batches * std::mem::size_of::<FFI_ArrowSchema>();
+    #[test]
+    fn test_evaluate_recall() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "recall", 300, 4);
+        let column = CString::new("vector").unwrap();
+        // Query points at both ends of the data, so no neighbours tie
+        let queries: Vec<f32> = [0.0f32; 4].into_iter().chain([1000.0; 4]).collect();
+        let evaluate = |k: c_int| {
+            let mut recall = -1.0;
+            let status = lancedb_table_evaluate_recall(
+                &table,
+                column.as_ptr(),
+                queries.as_ptr(),
+                2,
+                4,
+                k,
+                &mut recall,
+            );
+            (status, recall)
+        };
+
+        // Without an index both searches are exact
+        assert_eq!(evaluate(5), (0, 1.0));
 
-    let arrays_ptr = unsafe { libc::malloc(arrays_size) as *mut FFI_ArrowArray };
-    let schemas_ptr = unsafe { libc::malloc(schemas_size) as *mut FFI_ArrowSchema };
+        table
+            .create_index(
+                "vector",
+                "IVF_PQ",
+                DistanceType::L2,
+                Some(2),
+                Some(2),
+                false,
+                &IndexOptions::default(),
+            )
+            .unwrap();
+        let (status, recall) = evaluate(5);
+        assert_eq!(status, 0);
+        assert!((0.0..=1.0).contains(&recall), "recall {}", recall);
 
-    if arrays_ptr.is_null() || schemas_ptr.is_null() {
-        if !arrays_ptr.is_null() {
-            unsafe { libc::free(arrays_ptr as *mut libc::c_void) };
-        }
-        if !schemas_ptr.is_null() {
-            unsafe { libc::free(schemas_ptr as *mut libc::c_void) };
-        }
-        let error_msg = "failed to allocate memory for output arrays";
-        let c_error = CString::new(error_msg).unwrap();
-        crate::lancedb_set_last_error(c_error.as_ptr());
-        return -1;
+        assert_eq!(evaluate(0).0, -1);
+        let missing = CString::new("missing").unwrap();
+        let mut recall = 0.0;
+        assert_eq!(
+            lancedb_table_evaluate_recall(
+                &table,
+                missing.as_ptr(),
+                queries.as_ptr(),
+                2,
+                4,
+                5,
+                &mut recall
+            ),
+            -1
+        );
     }
 
-    // Export each batch
-    for (i, batch) in batches.iter().enumerate() {
-        let array_ptr = unsafe { arrays_ptr.add(i) };
-        let schema_ptr = unsafe { schemas_ptr.add(i) };
+    /// Sorted ids of the live and the deleted rows in a lancedb_table_scan_with_deleted scan
+    fn scan_ids_with_deleted(table: &TableHandle) -> (Vec<i32>, Vec<i32>) {
+        let mut arrays: *mut FFI_ArrowArray = std::ptr::null_mut();
+        let mut schemas: *mut FFI_ArrowSchema = std::ptr::null_mut();
+        let mut count: c_int = 0;
+        assert_eq!(
+            lancedb_table_scan_with_deleted(table, &mut arrays, &mut schemas, &mut count),
+            0
+        );
 
-        if let Err(err) =
-            unsafe { crate::arrow_ffi::export_record_batch_to_c(batch, array_ptr, schema_ptr) }
-        {
-            // Clean up on error
-            for j in 0..i {
-                unsafe {
-                    crate::arrow_ffi::lancedb_arrow_array_release(arrays_ptr.add(j));
-                    crate::arrow_ffi::lancedb_arrow_schema_release(schemas_ptr.add(j));
+        let mut live = Vec::new();
+        let mut deleted = Vec::new();
+        for i in 0..count as usize {
+            let batch =
+                unsafe { import_record_batch_from_c(arrays.add(i), schemas.add(i)) }.unwrap();
+            let ids = batch
+                .column_by_name("id")
+                .unwrap()
+                .as_any()
+                .downcast_ref::<Int32Array>()
+                .unwrap();
+            let flags = batch
+                .column_by_name("_deleted")
+                .unwrap()
+                .as_any()
+                .downcast_ref::<arrow_array::BooleanArray>()
+                .unwrap();
+            for (id, flag) in ids.values().iter().zip(flags.values().iter()) {
+                if flag {
+                    deleted.push(*id);
+                } else {
+                    live.push(*id);
                 }
             }
-            unsafe {
-                libc::free(arrays_ptr as *mut libc::c_void);
-                libc::free(schemas_ptr as *mut libc::c_void);
-            }
-            let error_msg = format!("{}", err);
-            let c_error = CString::new(error_msg).unwrap();
-            crate::lancedb_set_last_error(c_error.as_ptr());
-            return -1;
         }
-    }
+        unsafe {
+            libc::free(arrays as *mut c_void);
+            libc::free(schemas as *mut c_void);
+        }
 
-    unsafe {
-        *arrays_out = arrays_ptr;
-        *schemas_out = schemas_ptr;
-        *count_out = num_batches as c_int;
+        live.sort();
+        deleted.sort();
+        (live, deleted)
     }
 
-    0
-}
+    #[test]
+    fn test_scan_with_deleted() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "audited", 10, 2);
+        table.delete_rows("id < 3").unwrap();
+        assert_eq!(table.count_rows().unwrap(), 7);
 
-/// Create an index on a table column.
-/// Returns 0 on success, -1 on failure.
-///
-/// # Parameters
-/// * `handle` - The table handle
-/// * `column` - The column name to index
-/// * `index_type` - The type of index ("IVF_PQ", "AUTO")
-/// * `metric` - Distance metric (0=L2, 1=Cosine, 2=Dot)
-/// * `num_partitions` - Number of IVF partitions (0 for default)
-/// * `num_sub_vectors` - Number of PQ sub-vectors (0 for default)
-/// * `replace` - Whether to replace existing index
-#[no_mangle]
-pub extern "C" fn lancedb_table_create_index(
-    handle: *const TableHandle,
-    column: *const c_char,
-    index_type: *const c_char,
-    metric: c_int,
-    num_partitions: c_int,
-    num_sub_vectors: c_int,
-    replace: bool,
-) -> c_int {
-    if handle.is_null() || column.is_null() || index_type.is_null() {
-        let error_msg = "table handle, column, and index_type cannot be null";
-        let c_error = CString::new(error_msg).unwrap();
-        crate::lancedb_set_last_error(c_error.as_ptr());
-        return -1;
+        let (live, deleted) = scan_ids_with_deleted(&table);
+        assert_eq!(deleted, vec![0, 1, 2]);
+        assert_eq!(live, (3..10).collect::<Vec<_>>());
+        // The audit scan does not change what regular reads see
+        assert_eq!(table.count_rows().unwrap(), 7);
     }
 
-    let table = unsafe { &*handle };
-    let column_str = match unsafe { CStr::from_ptr(column) }.to_str() {
-        Ok(s) => s,
-        Err(err) => {
-            let error_msg = format!("{}", err);
-            let c_error = CString::new(error_msg).unwrap();
-            crate::lancedb_set_last_error(c_error.as_ptr());
-            return -1;
-        }
-    };
-    let index_type_str = match unsafe { CStr::from_ptr(index_type) }.to_str() {
-        Ok(s) => s,
-        Err(err) => {
-            let error_msg = format!("{}", err);
-            let c_error = CString::new(error_msg).unwrap();
-            crate::lancedb_set_last_error(c_error.as_ptr());
-            return -1;
-        }
-    };
+    #[test]
+    fn test_scan_with_deleted_after_c_delete() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "purged", 10, 2);
+        let predicate = CString::new("id < 3").unwrap();
+        assert_eq!(lancedb_table_delete(&table, predicate.as_ptr()), 0);
 
-    // Convert metric int to DistanceType
-    let distance_type = match metric {
-        0 => DistanceType::L2,
-        1 => DistanceType::Cosine,
-        2 => DistanceType::Dot,
-        _ => {
-            let error_msg = "Invalid distance metric. Use 0=L2, 1=Cosine, 2=Dot";
-            let c_error = CString::new(error_msg).unwrap();
-            crate::lancedb_set_last_error(c_error.as_ptr());
-            return -1;
-        }
-    };
+        // The delete compacted the table, purging 30% of its only fragment
+        let (live, deleted) = scan_ids_with_deleted(&table);
+        assert!(deleted.is_empty(), "{:?}", deleted);
+        assert_eq!(live, (3..10).collect::<Vec<_>>());
+    }
 
-    let partitions = if num_partitions > 0 {
-        Some(num_partitions as u32)
-    } else {
-        None
-    };
+    #[test]
+    fn test_create_index_ex_memory_limit() {
+        // 256 PQ centroids of 4 f32 elements need 4096 bytes per sample
+        assert_eq!(training_sample_rate(4096, 2, 4, 4).unwrap(), 1);
+        assert_eq!(training_sample_rate(u64::MAX, 2, 4, 4).unwrap(), 256);
+        assert!(training_sample_rate(4095, 2, 4, 4).is_err());
 
-    let sub_vectors = if num_sub_vectors > 0 {
-        Some(num_sub_vectors as u32)
-    } else {
-        None
-    };
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "limited", 300, 4);
+        let last_error = || {
+            unsafe { CStr::from_ptr(crate::lancedb_get_last_error()) }
+                .to_string_lossy()
+                .into_owned()
+        };
+        let create = |index_type: &str, column: &str, options: Option<&str>| {
+            let column = CString::new(column).unwrap();
+            let index_type = CString::new(index_type).unwrap();
+            let options = options.map(|o| CString::new(o).unwrap());
+            lancedb_table_create_index_ex(
+                &table,
+                column.as_ptr(),
+                index_type.as_ptr(),
+                0,
+                2,
+                2,
+                true,
+                options.as_ref().map_or(std::ptr::null(), |o| o.as_ptr()),
+            )
+        };
 
-    match table.create_index(
-        column_str,
-        index_type_str,
-        distance_type,
-        partitions,
-        sub_vectors,
-        replace,
-    ) {
-        Ok(_) => 0,
-        Err(err) => {
-            let error_msg = format!("{}", err);
-            let c_error = CString::new(error_msg).unwrap();
-            crate::lancedb_set_last_error(c_error.as_ptr());
+        assert_eq!(
+            create("IVF_PQ", "vector", Some(r#"{"memory_limit": 4095}"#)),
             -1
-        }
-    }
-}
+        );
+        assert!(
+            last_error().contains("at least 4096 bytes"),
+            "{}",
+            last_error()
+        );
+        assert!(table.list_indices().unwrap().is_empty());
 
-/// List all indices on a table.
-/// Returns the number of indices on success, -1 on failure.
-/// indices_json_out will be populated with a JSON string containing the indices.
-/// Caller is responsible for freeing the string with lancedb_free_string.
-#[no_mangle]
-pub extern "C" fn lancedb_table_list_indices(
-    handle: *const TableHandle,
-    indices_json_out: *mut *mut c_char,
-) -> c_int {
-    if handle.is_null() || indices_json_out.is_null() {
-        let error_msg = "table handle and indices_json_out cannot be null";
-        let c_error = CString::new(error_msg).unwrap();
-        crate::lancedb_set_last_error(c_error.as_ptr());
-        return -1;
-    }
+        assert_eq!(
+            create("IVF_PQ", "vector", Some(r#"{"memory_limit": 65536}"#)),
+            0
+        );
+        assert_eq!(table.list_indices().unwrap().len(), 1);
 
-    let table = unsafe { &*handle };
-    let indices = match table.list_indices() {
-        Ok(idx) => idx,
-        Err(err) => {
-            let error_msg = format!("{}", err);
-            let c_error = CString::new(error_msg).unwrap();
-            crate::lancedb_set_last_error(c_error.as_ptr());
-            return -1;
-        }
-    };
+        assert_eq!(create("FTS", "text", Some(r#"{"memory_limit": 4096}"#)), -1);
+        assert!(
+            last_error().contains("only supported for IVF"),
+            "{}",
+            last_error()
+        );
+        assert_eq!(create("IVF_PQ", "vector", Some(r#"{"memory": 4096}"#)), -1);
+        assert_eq!(create("IVF_PQ", "vector", Some("not json")), -1);
 
-    // Manually build JSON array from IndexConfig structs
-    let json_objects: Vec<String> = indices
-        .iter()
-        .map(|idx| {
-            format!(
-                r#"{{"name":"{}","type":"{:?}","columns":[{}]}}"#,
-                idx.name,
-                idx.index_type,
-                idx.columns
-                    .iter()
-                    .map(|c| format!(r#""{}""#, c))
-                    .collect::<Vec<_>>()
-                    .join(",")
+        // Null options build with the defaults
+        assert_eq!(create("IVF_PQ", "vector", None), 0);
+    }
+
+    #[test]
+    fn test_rebuild_index() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "rebuilt", 300, 4);
+        table
+            .create_index(
+                "vector",
+                "IVF_PQ",
+                DistanceType::L2,
+                Some(2),
+                Some(2),
+                false,
+                &IndexOptions::default(),
             )
-        })
-        .collect();
+            .unwrap();
+        let stats = || {
+            let name = CString::new("vector_idx").unwrap();
+            let mut stats_out: *mut c_char = std::ptr::null_mut();
+            assert_eq!(
+                lancedb_table_index_stats(&table, name.as_ptr(), &mut stats_out),
+                0
+            );
+            let json = unsafe { CString::from_raw(stats_out) }
+                .into_string()
+                .unwrap();
+            serde_json::from_str::<serde_json::Value>(&json).unwrap()
+        };
+        let rebuild = |name: &str, config: &str| {
+            let name = CString::new(name).unwrap();
+            let config = CString::new(config).unwrap();
+            lancedb_table_rebuild_index(&table, name.as_ptr(), config.as_ptr())
+        };
 
-    let json = format!("[{}]", json_objects.join(","));
+        let config =
+            r#"{"index_type": "IVF_PQ", "metric": 1, "num_partitions": 4, "num_sub_vectors": 2}"#;
+        assert_eq!(rebuild("vector_idx", config), 0);
+        let rebuilt = stats();
+        assert_eq!(rebuilt["num_partitions"], 4);
+        assert_eq!(rebuilt["distance_type"], "cosine");
+        assert_eq!(table.list_indices().unwrap().len(), 1);
 
-    let c_string = match CString::new(json) {
-        Ok(s) => s,
-        Err(err) => {
-            let error_msg = format!("{}", err);
-            let c_error = CString::new(error_msg).unwrap();
-            crate::lancedb_set_last_error(c_error.as_ptr());
-            return -1;
-        }
-    };
+        // Failed rebuilds leave the index as it was
+        assert_eq!(rebuild("missing_idx", config), -1);
+        assert_eq!(
+            rebuild("vector_idx", r#"{"index_type": "IVF_PQ", "metric": 7}"#),
+            -1
+        );
+        assert_eq!(rebuild("vector_idx", r#"{"metric": 1}"#), -1);
+        assert_eq!(stats()["num_partitions"], 4);
 
-    unsafe {
-        *indices_json_out = c_string.into_raw();
+        // The replacement would be named id_idx, so a custom name is rejected
+        let mut dataset = table.latest_dataset().unwrap();
+        RT.block_on(dataset.create_index(
+            &["id"],
+            lance_index::IndexType::Scalar,
+            Some("id_lookup".to_string()),
+            &lance_index::scalar::ScalarIndexParams::default(),
+            false,
+        ))
+        .unwrap();
+        table.checkout_latest().unwrap();
+        assert_eq!(rebuild("id_lookup", r#"{"index_type": "BTREE"}"#), -1);
+        assert_eq!(
+            crate::lancedb_get_last_error_code(),
+            crate::error::ERROR_INVALID_ARGUMENT
+        );
+        let mut names: Vec<_> = table
+            .list_indices()
+            .unwrap()
+            .into_iter()
+            .map(|idx| idx.name)
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["id_lookup", "vector_idx"]);
     }
 
-    indices.len() as c_int
-}
+    #[test]
+    fn test_has_vector_index() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "probed", 300, 4);
+        let has_index = |column: &str| {
+            let column = CString::new(column).unwrap();
+            lancedb_table_has_vector_index(&table, column.as_ptr())
+        };
+        assert_eq!(has_index("vector"), 0);
 
-/// Delete rows from a table based on a predicate.
-/// Returns 0 on success, -1 on failure.
-///
-/// # Parameters
-/// * `handle` - The table handle
-/// * `predicate` - SQL-like predicate string (e.g., "id > 100" or "name = 'doc1'")
-#[no_mangle]
-pub extern "C" fn lancedb_table_delete(
-    handle: *const TableHandle,
-    predicate: *const c_char,
-) -> c_int {
-    if handle.is_null() || predicate.is_null() {
-        let error_msg = "table handle and predicate cannot be null";
-        let c_error = CString::new(error_msg).unwrap();
-        crate::lancedb_set_last_error(c_error.as_ptr());
-        return -1;
+        // A full-text index is not a vector index
+        table
+            .create_index(
+                "text",
+                "FTS",
+                DistanceType::L2,
+                None,
+                None,
+                false,
+                &IndexOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(has_index("text"), 0);
+
+        table
+            .create_index(
+                "vector",
+                "IVF_PQ",
+                DistanceType::L2,
+                Some(2),
+                Some(2),
+                false,
+                &IndexOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(has_index("vector"), 1);
+        assert_eq!(has_index("id"), 0);
+        assert_eq!(lancedb_table_has_vector_index(&table, std::ptr::null()), -1);
     }
 
-    let table = unsafe { &*handle };
-    let predicate_str = match unsafe { CStr::from_ptr(predicate) }.to_str() {
-        Ok(s) => s,
-        Err(err) => {
-            let error_msg = format!("invalid UTF-8 in predicate: {}", err);
-            let c_error = CString::new(error_msg).unwrap();
-            crate::lancedb_set_last_error(c_error.as_ptr());
-            return -1;
+    /// Records each progress report in the `Vec<i64>` behind `user_data`
+    extern "C" fn record_progress(rows_exported: i64, user_data: *mut c_void) {
+        let reports = unsafe { &mut *(user_data as *mut Vec<i64>) };
+        reports.push(rows_exported);
+    }
+
+    #[test]
+    fn test_to_arrow_progress() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "watched", 10, 2);
+        // One fragment per append, so the scan yields several batches
+        for start in [10, 20] {
+            table
+                .add_data(vector_batch(start, 10, 2), AddDataMode::Append)
+                .unwrap();
         }
-    };
+        let export = |limit: i64, callback: ProgressCallback, reports: &mut Vec<i64>| {
+            let mut arrays: *mut FFI_ArrowArray = std::ptr::null_mut();
+            let mut schemas: *mut FFI_ArrowSchema = std::ptr::null_mut();
+            let mut count: c_int = 0;
+            let rc = lancedb_table_to_arrow_progress(
+                &table,
+                limit,
+                callback,
+                reports as *mut Vec<i64> as *mut c_void,
+                &mut arrays,
+                &mut schemas,
+                &mut count,
+            );
+            assert_eq!(rc, 0);
+            unsafe { crate::arrow_ffi::lancedb_free_batch_arrays(arrays, schemas, count) };
+            count as usize
+        };
 
-    // Delete the rows
-    if let Err(err) = table.delete_rows(predicate_str) {
-        let error_msg = format!("delete failed: {}", err);
-        let c_error = CString::new(error_msg).unwrap();
-        crate::lancedb_set_last_error(c_error.as_ptr());
-        return -1;
+        let mut reports = Vec::new();
+        let batches = export(-1, Some(record_progress), &mut reports);
+        assert_eq!(reports.len(), batches);
+        assert!(reports.windows(2).all(|w| w[0] < w[1]), "{:?}", reports);
+        assert_eq!(reports.last(), Some(&30));
+
+        let mut reports = Vec::new();
+        export(15, Some(record_progress), &mut reports);
+        assert_eq!(reports.last(), Some(&15));
+
+        // A null callback exports without reporting
+        let mut reports = Vec::new();
+        assert!(export(-1, None, &mut reports) > 0);
+        assert!(reports.is_empty());
     }
 
-    // Automatically compact to reclaim space
-    if let Err(err) = table.compact() {
-        let error_msg = format!("compaction failed: {}", err);
-        let c_error = CString::new(error_msg).unwrap();
-        crate::lancedb_set_last_error(c_error.as_ptr());
-        return -1;
+    #[test]
+    fn test_add_and_index() {
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "incremental", 300, 4);
+        table
+            .create_index(
+                "vector",
+                "IVF_PQ",
+                DistanceType::L2,
+                Some(2),
+                Some(2),
+                false,
+                &IndexOptions::default(),
+            )
+            .unwrap();
+
+        let batch = vector_batch(300, 10, 4);
+        let mut array = FFI_ArrowArray::empty();
+        let mut schema = FFI_ArrowSchema::empty();
+        unsafe { crate::arrow_ffi::export_record_batch_to_c(&batch, &mut array, &mut schema) }
+            .unwrap();
+        assert_eq!(
+            lancedb_table_add_and_index(&table, &mut array, &mut schema),
+            0
+        );
+        assert_eq!(table.count_rows().unwrap(), 310);
+
+        // The new rows are already covered by the index
+        let name = CString::new("vector_idx").unwrap();
+        let mut stats_out: *mut c_char = std::ptr::null_mut();
+        assert_eq!(
+            lancedb_table_index_stats(&table, name.as_ptr(), &mut stats_out),
+            0
+        );
+        let json = unsafe { CString::from_raw(stats_out) }
+            .into_string()
+            .unwrap();
+        let stats: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(stats["num_indexed_rows"], 310);
+        assert_eq!(stats["num_unindexed_rows"], 0);
+
+        assert_eq!(
+            lancedb_table_add_and_index(&table, std::ptr::null_mut(), std::ptr::null_mut()),
+            -1
+        );
     }
 
-    0
+    #[test]
+    fn test_null_handle_error_code() {
+        assert_eq!(lancedb_table_set_commit_retries(std::ptr::null(), 1), -1);
+        assert_eq!(
+            crate::lancedb_get_last_error_code(),
+            crate::error::ERROR_NULL_POINTER
+        );
+        let error = unsafe { CStr::from_ptr(crate::lancedb_get_last_error()) };
+        assert!(error
+            .to_str()
+            .unwrap()
+            .starts_with("Null pointer: table handle cannot be null"));
+
+        // Bad values on a valid handle are still argument errors
+        let (_dir, conn) = temp_connection();
+        let table = vector_table(&conn, "retries", 1, 2);
+        assert_eq!(lancedb_table_set_commit_retries(&table, -1), -1);
+        assert_eq!(
+            crate::lancedb_get_last_error_code(),
+            crate::error::ERROR_INVALID_ARGUMENT
+        );
+    }
 }
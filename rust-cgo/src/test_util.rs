@@ -0,0 +1,72 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The LanceDB Authors
+
+//! Shared fixtures for unit tests that need a real database
+
+use std::sync::Arc;
+
+use arrow_array::{FixedSizeListArray, Float32Array, Int32Array, RecordBatch, StringArray};
+use arrow_schema::{DataType, Field, Schema};
+use lancedb::table::AddDataMode;
+use tempfile::TempDir;
+
+use crate::connection::ConnectionHandle;
+use crate::table::TableHandle;
+
+/// Connect to a fresh database in a temporary directory.
+/// The directory is removed when the returned `TempDir` is dropped.
+pub fn temp_connection() -> (TempDir, ConnectionHandle) {
+    let dir = tempfile::tempdir().unwrap();
+    let conn = ConnectionHandle::create(dir.path().to_str().unwrap()).unwrap();
+    (dir, conn)
+}
+
+/// Schema with an `id` column, a `text` column, and a `vector` column of `dim` floats
+pub fn vector_schema(dim: i32) -> Arc<Schema> {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("text", DataType::Utf8, true),
+        Field::new(
+            "vector",
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), dim),
+            true,
+        ),
+    ]))
+}
+
+/// Batch of `num_rows` rows matching [`vector_schema`], with ids starting at
+/// `start_id` and vectors whose elements all equal the row id
+pub fn vector_batch(start_id: i32, num_rows: i32, dim: i32) -> RecordBatch {
+    let ids: Vec<i32> = (start_id..start_id + num_rows).collect();
+    let texts: Vec<String> = ids.iter().map(|id| format!("doc {}", id)).collect();
+    let values: Vec<f32> = ids
+        .iter()
+        .flat_map(|id| std::iter::repeat(*id as f32).take(dim as usize))
+        .collect();
+    let vectors = FixedSizeListArray::try_new(
+        Arc::new(Field::new("item", DataType::Float32, true)),
+        dim,
+        Arc::new(Float32Array::from(values)),
+        None,
+    )
+    .unwrap();
+
+    RecordBatch::try_new(
+        vector_schema(dim),
+        vec![
+            Arc::new(Int32Array::from(ids)),
+            Arc::new(StringArray::from(texts)),
+            Arc::new(vectors),
+        ],
+    )
+    .unwrap()
+}
+
+/// Create a table named `name` holding `num_rows` rows from [`vector_batch`]
+pub fn vector_table(conn: &ConnectionHandle, name: &str, num_rows: i32, dim: i32) -> TableHandle {
+    let table = TableHandle::create(conn, name, vector_schema(dim)).unwrap();
+    table
+        .add_data(vector_batch(0, num_rows, dim), AddDataMode::Append)
+        .unwrap();
+    table
+}